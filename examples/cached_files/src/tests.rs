@@ -2,7 +2,7 @@ use std::fs::File;
 use std::io::Read;
 
 use rocket::local::Client;
-use rocket::http::Status;
+use rocket::http::{Status, ContentType};
 
 use super::rocket;
 
@@ -33,4 +33,21 @@ fn test_get_file() {
     test_query_file("/test.txt", "www/test.txt", Status::Ok);
     test_query_file("/test.txt?v=1", "www/test.txt", Status::Ok);
     test_query_file("/test.txt?this=should&be=ignored", "www/test.txt", Status::Ok);
-}
\ No newline at end of file
+}
+
+#[test]
+fn test_head_file() {
+    let client = Client::new(rocket()).unwrap();
+    // Get the file first so it is served from the cache.
+    client.get("/test.txt").dispatch();
+
+    let mut response = client.head("/test.txt").dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    assert_eq!(response.content_type(), Some(ContentType::Plain));
+
+    let expected_length: String = read_file_content("www/test.txt").len().to_string();
+    assert_eq!(response.headers().get_one("Content-Length"), Some(expected_length.as_str()));
+
+    let body_data = response.body().and_then(|body| body.into_bytes());
+    assert!(body_data.map_or(true, |s| s.is_empty()));
+}
@@ -0,0 +1,192 @@
+use std::io;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::fmt;
+use zip::ZipArchive;
+
+
+/// The size of the blocks that make up a tar archive. Each entry's header takes one block,
+/// and its contents are padded to a multiple of the block size.
+const BLOCK_SIZE: u64 = 512;
+
+/// The signatures that zip archives start with: that of a local file header, or that of the
+/// end of central directory record, which is all an empty zip archive contains.
+const ZIP_SIGNATURES: [&'static [u8; 4]; 2] = [b"PK\x03\x04", b"PK\x05\x06"];
+
+/// A reader that can also seek.
+pub(crate) trait ReadSeek: Read + Seek {}
+
+impl<T: Read + Seek> ReadSeek for T {}
+
+/// The format of an archive, holding what is needed to read the files in it.
+enum Format {
+    /// The contents of each file are stored uncompressed in the archive, after the file's header.
+    Tar(Mutex<Box<dyn ReadSeek + Send>>),
+    /// The files are found through the archive's central directory, and may be compressed.
+    Zip(Mutex<ZipArchive<Box<dyn ReadSeek + Send>>>),
+}
+
+/// An index of the files in a tar or zip archive.
+///
+/// Only the headers of the entries, or the central directory of a zip archive, are read when the archive is indexed.
+/// The contents of an entry are read from the archive when they are requested.
+pub(crate) struct Archive {
+    format: Format,
+    entries: HashMap<PathBuf, (u64, usize)>, // The offset of the contents of each tar entry, or the index of each zip entry, and its size.
+}
+
+impl fmt::Debug for Archive {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Archive {{ entries: {:?} }}", self.entries.keys().collect::<Vec<&PathBuf>>())
+    }
+}
+
+impl Archive {
+    /// Indexes the regular files in a zip archive, or in an uncompressed tar archive.
+    ///
+    /// The format is recognized by the signature at the start of zip archives.
+    pub(crate) fn index<R: Read + Seek + Send + 'static>(mut reader: R) -> io::Result<Archive> {
+        let mut signature = [0u8; 4];
+        let signature_length: usize = reader.read(&mut signature)?;
+        reader.seek(SeekFrom::Start(0))?;
+        if signature_length == signature.len() && ZIP_SIGNATURES.iter().any(|zip_signature| **zip_signature == signature) {
+            Archive::index_zip(reader)
+        } else {
+            Archive::index_tar(reader)
+        }
+    }
+
+    /// Indexes the regular files in an uncompressed tar archive.
+    fn index_tar<R: Read + Seek + Send + 'static>(mut reader: R) -> io::Result<Archive> {
+        let mut entries: HashMap<PathBuf, (u64, usize)> = HashMap::new();
+        let mut header = [0u8; BLOCK_SIZE as usize];
+        loop {
+            match reader.read_exact(&mut header) {
+                Ok(()) => {}
+                Err(ref e) if e.kind() == io::ErrorKind::UnexpectedEof => break,
+                Err(e) => return Err(e),
+            }
+            // The end of the archive is marked by empty blocks.
+            if header.iter().all(|byte| *byte == 0) {
+                break;
+            }
+
+            let size: u64 = parse_octal(&header[124..136])?;
+            let offset: u64 = reader.seek(SeekFrom::Current(0))?;
+            let type_flag: u8 = header[156];
+            if type_flag == b'0' || type_flag == 0 {
+                entries.insert(entry_path(&header), (offset, size as usize));
+            }
+
+            let padded_size: u64 = (size + BLOCK_SIZE - 1) / BLOCK_SIZE * BLOCK_SIZE;
+            reader.seek(SeekFrom::Current(padded_size as i64))?;
+        }
+
+        Ok(Archive {
+            format: Format::Tar(Mutex::new(Box::new(reader))),
+            entries,
+        })
+    }
+
+    /// Indexes the files in a zip archive, using its central directory.
+    ///
+    /// Entries whose paths would escape the archive, such as those containing `..`, are left out.
+    fn index_zip<R: Read + Seek + Send + 'static>(reader: R) -> io::Result<Archive> {
+        let reader: Box<dyn ReadSeek + Send> = Box::new(reader);
+        let mut zip: ZipArchive<Box<dyn ReadSeek + Send>> = ZipArchive::new(reader)?;
+        let mut entries: HashMap<PathBuf, (u64, usize)> = HashMap::new();
+        for index in 0..zip.len() {
+            let file = zip.by_index_raw(index)?;
+            if !file.is_file() {
+                continue;
+            }
+            if let Some(path) = file.enclosed_name() {
+                entries.insert(path.to_path_buf(), (index as u64, file.size() as usize));
+            }
+        }
+
+        Ok(Archive {
+            format: Format::Zip(Mutex::new(zip)),
+            entries,
+        })
+    }
+
+    /// Reads the contents of the file in the archive at the path, decompressing it if it is compressed.
+    pub(crate) fn read<P: AsRef<Path>>(&self, path: P) -> io::Result<Vec<u8>> {
+        let (position, size): (u64, usize) = match self.entries.get(path.as_ref()) {
+            Some(entry) => *entry,
+            None => return Err(io::Error::new(io::ErrorKind::NotFound, "The archive does not contain the file.")),
+        };
+        match self.format {
+            Format::Tar(ref reader) => {
+                let mut reader = match reader.lock() {
+                    Ok(guard) => guard,
+                    Err(poisoned) => poisoned.into_inner(),
+                };
+                reader.seek(SeekFrom::Start(position))?;
+                let mut bytes: Vec<u8> = vec![0; size];
+                reader.read_exact(&mut bytes)?;
+                Ok(bytes)
+            }
+            Format::Zip(ref zip) => {
+                let mut zip = match zip.lock() {
+                    Ok(guard) => guard,
+                    Err(poisoned) => poisoned.into_inner(),
+                };
+                let file = zip.by_index(position as usize)?;
+                // The size is taken from the archive, so it only limits how much is read, instead of being allocated up front.
+                let mut bytes: Vec<u8> = vec![];
+                file.take(size as u64).read_to_end(&mut bytes)?;
+                Ok(bytes)
+            }
+        }
+    }
+
+    /// Indicates if the archive contains a file at the path.
+    pub(crate) fn contains<P: AsRef<Path>>(&self, path: P) -> bool {
+        self.entries.contains_key(path.as_ref())
+    }
+}
+
+/// Gets the path of the entry described by the header.
+///
+/// Ustar archives can split long paths between a prefix and a name.
+/// Leading `./` components are removed, so entries can be looked up by their relative paths.
+fn entry_path(header: &[u8]) -> PathBuf {
+    let name: String = null_terminated(&header[0..100]);
+    let path: String = if &header[257..262] == b"ustar" {
+        let prefix: String = null_terminated(&header[345..500]);
+        if prefix.is_empty() {
+            name
+        } else {
+            format!("{}/{}", prefix, name)
+        }
+    } else {
+        name
+    };
+    let mut path: &str = path.as_str();
+    while path.starts_with("./") {
+        path = &path[2..];
+    }
+    PathBuf::from(path)
+}
+
+/// Gets the string stored in a null terminated field of a header.
+fn null_terminated(field: &[u8]) -> String {
+    let end: usize = field.iter().position(|byte| *byte == 0).unwrap_or(field.len());
+    String::from_utf8_lossy(&field[..end]).into_owned()
+}
+
+/// Parses a number stored as null or space terminated octal digits in a field of a header.
+fn parse_octal(field: &[u8]) -> io::Result<u64> {
+    let digits: String = null_terminated(field);
+    let digits: &str = digits.trim_matches(' ');
+    if digits.is_empty() {
+        return Ok(0);
+    }
+    u64::from_str_radix(digits, 8).map_err(|_| {
+        io::Error::new(io::ErrorKind::InvalidData, "The archive contains an invalid header.")
+    })
+}
@@ -0,0 +1,83 @@
+use concurrent_hashmap::ConcHashMap;
+
+use std::hash::BuildHasher;
+use std::path::{Path, PathBuf};
+
+use in_memory_file::InMemoryFile;
+
+
+/// The store that holds the files of a `Cache`.
+///
+/// The cache decides which files to store and which to evict, and keeps their access counts itself,
+/// so a backend only needs to hold the files it is given.
+/// By default, files are held in a concurrent hashmap in the memory of the process.
+/// Implementing this for another store, such as Redis or memcached, allows several servers to share their cached files.
+///
+/// The bytes of an `InMemoryFile` are held behind an `Arc`, so the copies of files passed to and returned
+/// from a backend don't copy their contents.
+pub trait CacheBackend: Send + Sync {
+    /// Gets a copy of the file stored under the path.
+    fn get(&self, path: &Path) -> Option<InMemoryFile>;
+
+    /// Stores the file under the path, returning the file it replaced.
+    fn insert(&self, path: PathBuf, file: InMemoryFile) -> Option<InMemoryFile>;
+
+    /// Removes the file stored under the path, returning it.
+    fn remove(&self, path: &Path) -> Option<InMemoryFile>;
+
+    /// Gets the total size of the stored files, according to their stats.
+    ///
+    /// This is only called when a cache is created with the backend. Afterwards, the cache keeps count of
+    /// the bytes it has stored, so files stored in the backend by anything other than the cache aren't counted.
+    fn size_bytes(&self) -> usize;
+
+    /// Gets copies of every stored file, along with the paths they are stored under.
+    fn entries(&self) -> Vec<(PathBuf, InMemoryFile)>;
+
+    /// Changes the file stored under the path, returning false if there is no such file.
+    ///
+    /// By default, a copy of the file is changed and then stored in its place, so changes made to the file
+    /// in between are lost. Backends that can change their files in place should override this.
+    fn update<F: FnOnce(&mut InMemoryFile)>(&self, path: &Path, f: F) -> bool {
+        match self.get(path) {
+            Some(mut file) => {
+                f(&mut file);
+                self.insert(path.to_path_buf(), file);
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+impl<S: BuildHasher + Send + Sync> CacheBackend for ConcHashMap<PathBuf, InMemoryFile, S> {
+    fn get(&self, path: &Path) -> Option<InMemoryFile> {
+        self.find(path).map(|file| file.get().clone())
+    }
+
+    fn insert(&self, path: PathBuf, file: InMemoryFile) -> Option<InMemoryFile> {
+        ConcHashMap::insert(self, path, file)
+    }
+
+    fn remove(&self, path: &Path) -> Option<InMemoryFile> {
+        ConcHashMap::remove(self, path)
+    }
+
+    fn size_bytes(&self) -> usize {
+        self.iter().fold(0usize, |size, (_, file)| size + file.stats.size)
+    }
+
+    fn entries(&self) -> Vec<(PathBuf, InMemoryFile)> {
+        self.iter().map(|(path, file)| (path.clone(), file.clone())).collect()
+    }
+
+    fn update<F: FnOnce(&mut InMemoryFile)>(&self, path: &Path, f: F) -> bool {
+        match self.find_mut(path) {
+            Some(mut file) => {
+                f(file.get());
+                true
+            }
+            None => false,
+        }
+    }
+}
@@ -0,0 +1,126 @@
+use rocket::http::{Status, ContentType};
+use rocket::response::{Response, Responder};
+use rocket::request::Request;
+use cache::Cache;
+use cached_file::CachedFile;
+use checksum::ChecksumReader;
+use owned_in_memory_file::SharedBytes;
+use content_type::content_type_from_extension;
+
+use std::path::{Path, PathBuf};
+use std::io::{self, Read, Cursor};
+
+
+/// Several files that are served one after another in a single response, such as stylesheets that
+/// are joined together.
+///
+/// Each file is gotten from the cache when responding, so the files are stored in the cache
+/// individually, just as if they were requested on their own.
+///
+/// The Content-Type of the response is the one implied by the files' extensions if they all agree,
+/// otherwise it is left unset. It can also be set explicitly using `with_content_type()`.
+/// If any of the files can't be found, the response fails with a 404 Not Found.
+///
+/// # Example
+///
+/// ```
+/// #![feature(attr_literals)]
+/// #![feature(custom_attribute)]
+/// # extern crate rocket;
+/// # extern crate rocket_file_cache;
+///
+/// # fn main() {
+/// use rocket_file_cache::{Cache, Bundle};
+/// use rocket::State;
+///
+/// #[get("/bundle.css")]
+/// fn stylesheets<'a>(cache: State<'a, Cache>) -> Bundle<'a> {
+///     Bundle::open(&["www/reset.css", "www/layout.css", "www/theme.css"], cache.inner())
+/// }
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct Bundle<'a> {
+    paths: Vec<PathBuf>,
+    cache: &'a Cache,
+    content_type: Option<ContentType>,
+}
+
+impl<'a> Bundle<'a> {
+    /// Creates a Bundle that will get the files from the cache, in the given order, when responding.
+    pub fn open<I, P>(paths: I, cache: &'a Cache) -> Bundle<'a>
+    where
+        I: IntoIterator<Item = P>,
+        P: AsRef<Path>,
+    {
+        Bundle {
+            paths: paths.into_iter().map(|path| path.as_ref().to_path_buf()).collect(),
+            cache,
+            content_type: None,
+        }
+    }
+
+    /// Sets the Content-Type of the response, instead of inferring it from the files' extensions.
+    pub fn with_content_type(mut self, content_type: ContentType) -> Bundle<'a> {
+        self.content_type = Some(content_type);
+        self
+    }
+
+    /// Gets the Content-Type implied by the extensions of the files, if every file implies the same one.
+    fn shared_content_type(&self) -> Option<ContentType> {
+        let mut content_types = self.paths.iter().map(|path| {
+            path.extension()
+                .and_then(|ext| content_type_from_extension(&ext.to_string_lossy()))
+        });
+        let first: ContentType = match content_types.next() {
+            Some(Some(content_type)) => content_type,
+            _ => return None,
+        };
+        if content_types.all(|content_type| content_type.as_ref() == Some(&first)) {
+            Some(first)
+        } else {
+            None
+        }
+    }
+}
+
+/// Converts a file into a reader of its contents, so it can be chained with the other files in the bundle.
+///
+/// Files in memory are converted into `OwnedInMemoryFile`s, so the body doesn't borrow from the cache.
+fn into_reader<'a>(file: CachedFile<'a>) -> Option<Box<dyn Read + 'a>> {
+    match file {
+        CachedFile::InMemory(named_in_memory_file) => {
+            Some(Box::new(Cursor::new(SharedBytes(named_in_memory_file.into_owned().bytes))))
+        }
+        CachedFile::Owned(owned_file) => Some(Box::new(Cursor::new(SharedBytes(owned_file.bytes)))),
+        CachedFile::FileSystem(streamed_file) => Some(Box::new(streamed_file.take_file())),
+        CachedFile::Verified(verified_file) => {
+            let path: PathBuf = verified_file.path().to_path_buf();
+            let reader = ChecksumReader::new(verified_file.file.take_file(), path, verified_file.expected_checksum, verified_file.on_mismatch);
+            Some(Box::new(reader))
+        }
+        CachedFile::NotFound => None,
+    }
+}
+
+impl<'a> Responder<'a> for Bundle<'a> {
+    fn respond_to(self, _: &Request) -> Result<Response<'a>, Status> {
+        let mut body: Box<dyn Read + 'a> = Box::new(io::empty());
+        for path in self.paths.iter() {
+            match into_reader(self.cache.get(path)) {
+                Some(reader) => body = Box::new(body.chain(reader)),
+                None => {
+                    error!("The file {:?} in the bundle could not be found.", path);
+                    return Err(Status::NotFound);
+                }
+            }
+        }
+
+        let mut response = Response::new();
+        if let Some(content_type) = self.content_type.clone().or_else(|| self.shared_content_type()) {
+            response.set_header(content_type);
+        }
+        response.set_streamed_body(body);
+        Ok(response)
+    }
+}
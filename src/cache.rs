@@ -1,20 +1,57 @@
 use std::path::{PathBuf, Path};
 use std::usize;
-use rocket::response::NamedFile;
+use std::cmp;
+use named_file::NamedFile;
 use std::fs::Metadata;
 use std::fs;
 use named_in_memory_file::NamedInMemoryFile;
 use cached_file::CachedFile;
+use owned_in_memory_file::OwnedInMemoryFile;
 use in_memory_file::InMemoryFile;
-use priority_function::default_priority_function;
+use priority_function::{default_priority_function, priority_function_by_name, UnknownPriorityFunction};
 use concurrent_hashmap::ConcHashMap;
+#[cfg(feature = "rocket")]
+use rocket::http::{ContentType, Header};
 use std::collections::hash_map::RandomState;
 use std::fmt::Debug;
 use std::fmt;
 use std::fmt::Formatter;
 use in_memory_file::FileStats;
-use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::{AtomicUsize, AtomicU64};
 use std::sync::atomic::Ordering;
+use std::sync::{Arc, Weak, Mutex, MutexGuard, Condvar};
+use std::collections::HashMap;
+use std::io;
+use std::io::{Read, Write, Seek};
+use std::thread;
+use std::thread::JoinHandle;
+use std::sync::mpsc;
+use std::time::{SystemTime, UNIX_EPOCH, Duration, Instant};
+use checksum::{checksum, ChecksumMismatch};
+use verified_file::VerifiedFile;
+use streamed_file::StreamedFile;
+use archive::Archive;
+use dump;
+#[cfg(feature = "rocket")]
+use precompressed_file::{default_compressible_types, sidecar_path, sidecar_paths};
+#[cfg(feature = "rocket")]
+use fairing::CacheFairing;
+#[cfg(feature = "rocket")]
+use rocket::request::Request;
+use memory::SystemMemory;
+use backend::CacheBackend;
+#[cfg(feature = "rocket")]
+use zstd;
+
+/// The size limit used by `Cache::default()`: 50 MB.
+pub const DEFAULT_SIZE_LIMIT: usize = 1024 * 1024 * 50;
+
+/// The number of missing paths that are remembered between sweeps for paths whose time to live has passed.
+const MISSING_PATHS_PER_SWEEP: usize = 256;
+
+// The number of times the current thread has read the metadata of a file, so tests can tell if the filesystem was checked.
+#[cfg(test)]
+thread_local!(static STAT_CALLS: ::std::cell::Cell<usize> = ::std::cell::Cell::new(0));
 
 #[derive(Debug, PartialEq)]
 enum CacheError {
@@ -24,6 +61,14 @@ enum CacheError {
     InvalidPath,
 }
 
+/// Error types that can be encountered when a file is forcibly stored in the cache,
+/// which invalidates whichever files have to be removed to make room for it.
+#[derive(Debug, PartialEq)]
+pub enum CacheInvalidationError {
+    FileIsLargerThanSizeLimit,
+    PinnedFilesLeaveTooLittleSpace,
+}
+
 /// Holds related information used for "ageing out" files in the cache.
 pub struct AgeOut {
     /// If the cachewide age out access count modulo this value in 0, then the age out function will execute.
@@ -34,6 +79,182 @@ pub struct AgeOut {
     pub age_out_function: fn(&AtomicUsize),
 }
 
+/// A snapshot of statistics describing the state of the cache.
+#[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct CacheStats {
+    /// The number of files currently held in the cache.
+    pub file_count: usize,
+    /// The sum of the sizes of the files currently held in the cache.
+    pub used_bytes: usize,
+    /// The number of files that have been removed from the cache to make room for other files.
+    /// A high number of evictions indicates that the cache's `size_limit` should be increased.
+    pub evictions: u64,
+    /// The number of bytes that were removed from the cache beyond those needed to make room for other files.
+    /// Files can't be partially removed, so making room for a file usually frees more space than it needs.
+    pub eviction_waste_bytes: u64,
+    /// The number of times a file has been read from the filesystem into memory.
+    pub files_read: u64,
+    /// The number of times `get()` found the file already in the cache.
+    pub hits: u64,
+    /// The number of times `get()` didn't find the file in the cache.
+    pub misses: u64,
+    /// The number of responses that have been built for files served from the cache.
+    pub responses_built: u64,
+    /// The average time spent building a response for a file served from the cache.
+    pub average_response_time: Duration,
+}
+
+/// A point-in-time snapshot of the files held in the cache, without their contents.
+///
+/// With the `serde` feature enabled, the snapshot can be serialized, so it can be dumped to a file and inspected offline.
+#[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct CacheSnapshot {
+    /// The number of bytes that the cache is allowed to hold.
+    pub size_limit: usize,
+    /// The cached files, sorted by their paths.
+    pub files: Vec<FileSnapshot>,
+    /// Statistics describing the state of the cache.
+    pub stats: CacheStats,
+}
+
+/// Describes a single file held in the cache.
+#[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct FileSnapshot {
+    /// The path the file is stored under.
+    pub path: PathBuf,
+    /// The number of bytes the file contains.
+    pub size: usize,
+    /// The number of times the file has been requested.
+    pub access_count: usize,
+    /// The priority score of the file.
+    pub priority: usize,
+}
+
+/// Accumulates the time spent building responses for files served from the cache.
+#[derive(Debug)]
+pub(crate) struct ResponseTimer {
+    nanos: AtomicU64,
+    count: AtomicU64,
+}
+
+impl ResponseTimer {
+    pub(crate) fn new() -> ResponseTimer {
+        ResponseTimer {
+            nanos: AtomicU64::new(0),
+            count: AtomicU64::new(0),
+        }
+    }
+
+    /// Records the time spent building one response.
+    #[cfg(feature = "rocket")]
+    pub(crate) fn record(&self, elapsed: Duration) {
+        let nanos: u64 = elapsed.as_secs() * 1_000_000_000 + elapsed.subsec_nanos() as u64;
+        self.nanos.fetch_add(nanos, Ordering::Relaxed);
+        self.count.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Gets the number of responses recorded, and the average time spent building them.
+    fn average(&self) -> (u64, Duration) {
+        let count: u64 = self.count.load(Ordering::Relaxed);
+        let average_nanos: u64 = match count {
+            0 => 0,
+            count => self.nanos.load(Ordering::Relaxed) / count,
+        };
+        (count, Duration::new(average_nanos / 1_000_000_000, (average_nanos % 1_000_000_000) as u32))
+    }
+}
+
+/// Limits the number of files that are read from the filesystem at once.
+#[derive(Debug)]
+pub(crate) struct ReadLimiter {
+    reading: Mutex<usize>,
+    read_finished: Condvar,
+    #[cfg(test)]
+    peak: AtomicUsize,
+}
+
+/// Allows a file to be read while it is held, and lets a waiting read proceed once it is dropped.
+pub(crate) struct ReadPermit<'a> {
+    limiter: &'a ReadLimiter,
+}
+
+impl ReadLimiter {
+    pub(crate) fn new() -> ReadLimiter {
+        ReadLimiter {
+            reading: Mutex::new(0),
+            read_finished: Condvar::new(),
+            #[cfg(test)]
+            peak: AtomicUsize::new(0),
+        }
+    }
+
+    /// Waits until fewer than the limit of files are being read, then counts another file as being read.
+    /// Without a limit, this never waits.
+    fn acquire(&self, limit: Option<usize>) -> ReadPermit {
+        let mut reading = match self.reading.lock() {
+            Ok(guard) => guard,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        if let Some(limit) = limit {
+            while *reading >= cmp::max(limit, 1) {
+                reading = match self.read_finished.wait(reading) {
+                    Ok(guard) => guard,
+                    Err(poisoned) => poisoned.into_inner(),
+                };
+            }
+        }
+        *reading += 1;
+        self.record_peak(*reading);
+        ReadPermit { limiter: self }
+    }
+
+    /// Remembers the most files that were read at once, so tests can check that the limit is enforced.
+    #[cfg(test)]
+    fn record_peak(&self, reading: usize) {
+        // This is only called while holding the lock on the count, so the peak can't change in between.
+        if reading > self.peak.load(Ordering::Relaxed) {
+            self.peak.store(reading, Ordering::Relaxed);
+        }
+    }
+
+    #[cfg(not(test))]
+    fn record_peak(&self, _: usize) {}
+}
+
+impl<'a> Drop for ReadPermit<'a> {
+    fn drop(&mut self) {
+        let mut reading = match self.limiter.reading.lock() {
+            Ok(guard) => guard,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        *reading -= 1;
+        self.limiter.read_finished.notify_one();
+    }
+}
+
+/// Indicates where the file returned by `Cache::get_with_origin()` was found.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Origin {
+    /// The file was already in the cache.
+    Cache,
+    /// The file was not in the cache, and was read from the filesystem.
+    Filesystem,
+}
+
+/// Determines how the cache chooses which files to remove when room must be made for a new file.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum EvictionPolicy {
+    /// The files with the lowest priority are removed first.
+    LowestPriority,
+    /// Files are chosen randomly, with files that have a lower priority being more likely to be chosen.
+    /// This avoids repeatedly removing the same files when many files have nearly equal priorities.
+    /// The random number generator can be seeded with `CacheBuilder::eviction_seed()` to make the choices reproducible.
+    WeightedRandom,
+}
+
 impl Debug for AgeOut {
     fn fmt(&self, f: &mut Formatter) -> fmt::Result {
         write!(f, "accesses_limit: {}, access_count: {}", self.accesses_limit, self.access_count.load(Ordering::Relaxed))
@@ -61,34 +282,157 @@ impl Debug for AgeOut {
 /// This will repeat until either enough space can be freed for the new file, and the new file is
 /// inserted, or until the priority of the cached files is greater than that of the new file,
 /// in which case, the new file isn't inserted.
-pub struct Cache {
+///
+/// The files and their access counts are held in concurrent hashmaps, so every operation, including
+/// recording an access, only needs a shared reference to the cache.
+/// This allows the cache to be shared between threads without wrapping it in a lock.
+///
+/// The files can instead be held in another `CacheBackend`, such as one shared by several servers,
+/// by creating the cache with `Cache::with_backend()` or `CacheBuilder::build_with_backend()`.
+pub struct Cache<B: CacheBackend = ConcHashMap<PathBuf, InMemoryFile, RandomState>> {
     /// The number of bytes the file_map should be able hold at once.
-    pub size_limit: usize,
+    /// This includes the bytes of any compressed sidecar files that are stored in the cache.
+    /// If this is 0, no files are stored, and every file is served from the filesystem.
+    /// It can be changed while the cache is shared using `set_size_limit()`.
+    pub(crate) size_limit: AtomicUsize,
     /// The minimum number of bytes a file must have in order to be accepted into the Cache.
     pub min_file_size: usize,
     /// The maximum number of bytes a file can have in order to be accepted into the Cache.
     pub max_file_size: usize,
+    /// The largest fraction of the `size_limit` that a single file may occupy.
+    /// Files larger than this are served from the filesystem, so one large file can't keep many smaller files out of the cache.
+    pub max_single_file_fraction: Option<f64>,
     /// The function that is used to calculate the priority score that is used to determine which files should be in the cache.
+    /// If it returns 0 for a file, that file will never be stored in the cache, even if the cache has room for it.
     pub priority_function: fn(usize, usize) -> usize,
     /// Related data used for "aging out" files in the cache.
     pub age_out: Option<AgeOut>,
     /// If a given file's access count modulo this value equals 0, then that file will be refreshed from the FileSystem instead of from the Cache.
     pub accesses_per_refresh: Option<usize>,
-    pub(crate) file_map: ConcHashMap<PathBuf, InMemoryFile, RandomState>, // Holds the files that the cache is caching
+    /// If this is set, files that were read longer ago than this are refreshed the next time they are gotten.
+    pub ttl: Option<Duration>,
+    /// If this is set, no more than this many files are read from the filesystem into the cache at once.
+    /// Other reads wait for one of them to finish first.
+    pub max_concurrent_reads: Option<usize>,
+    /// If the number of bytes used by the cache exceeds this value, files will be evicted until the `low_watermark` is reached.
+    pub high_watermark: Option<usize>,
+    /// The number of bytes the cache will evict down to once the `high_watermark` has been crossed.
+    pub low_watermark: Option<usize>,
+    /// The fraction of the `size_limit` that is kept free, so new files can usually be inserted without removing other files.
+    pub reserve_fraction: f64,
+    /// The number of seconds that clients may cache files with a given extension, used to set the Cache-Control header.
+    /// Extensions are stored in lowercase, without a leading period.
+    pub max_ages: HashMap<String, usize>,
+    /// The number of seconds that clients may cache files whose extensions don't have a max-age in `max_ages`.
+    /// If this is `None`, the Cache-Control header won't be set for those files.
+    pub default_max_age: Option<usize>,
+    /// The Content-Type used when responding with files whose extensions don't imply a Content-Type.
+    /// If this is `None`, the Content-Type header won't be set for those files.
+    #[cfg(feature = "rocket")]
+    pub default_content_type: Option<ContentType>,
+    /// Headers that are set on the responses of every file served from the cache.
+    /// Headers registered for a specific path using `set_headers()` take precedence over these.
+    #[cfg(feature = "rocket")]
+    pub headers: Vec<Header<'static>>,
+    /// The Content-Types that `PrecompressedFile` will serve from compressed sidecar files.
+    /// Files of other types are always served uncompressed. A subtype of `*` matches every subtype.
+    /// By default, this contains `text/*`, JSON, JavaScript, XML, SVG and WebAssembly.
+    #[cfg(feature = "rocket")]
+    pub compressible_types: Vec<ContentType>,
+    /// The most bytes a Range header can request from a file served from memory, as a multiple of the file's size.
+    /// Ranges may overlap, so without this limit a request could ask for many times the file's size.
+    /// Requests for more bytes are answered with 416 Range Not Satisfiable. The default is 1.
+    #[cfg(feature = "rocket")]
+    pub max_range_multiple: usize,
+    /// The level that `PrecompressedFile` compresses files in the cache with, for clients that accept zstd.
+    /// A file is compressed the first time such a client requests it while it is in the cache, and the compressed
+    /// bytes are stored as if they had been read from the file's `.zst` sidecar file.
+    /// If this is `None`, the cache never compresses files itself.
+    pub zstd_level: Option<i32>,
+    /// The zstd dictionary that files are compressed with when the cache has a `zstd_level`.
+    /// A dictionary trained on samples of the cached files captures what they have in common, so small files
+    /// compress far better than they would on their own. Clients need the same dictionary to decode
+    /// the compressed files, so this should only be set when every client that accepts zstd has it.
+    pub compression_dictionary: Option<Vec<u8>>,
+    /// Multipliers applied to the priority scores of files with a given extension, so files of some types are preferred over others.
+    /// Extensions are stored in lowercase, without a leading period.
+    pub extension_weights: HashMap<String, f64>,
+    /// A function that is given the path and contents of each file that is evicted to make room for other files,
+    /// just before the file is dropped.
+    pub on_evict: Option<Box<dyn Fn(&PathBuf, &InMemoryFile) + Send + Sync>>,
+    /// If this is true, files are always served from the filesystem and are never stored in the cache,
+    /// so changes to files are visible immediately. This is intended for use during development.
+    pub passthrough: bool,
+    /// The policy used to choose which files to remove when room must be made for new files.
+    pub eviction_policy: EvictionPolicy,
+    /// Determines what happens when a file that is streamed from the filesystem doesn't match its expected checksum.
+    pub on_checksum_mismatch: ChecksumMismatch,
+    /// If this is true, decisions about which files to evict from the cache are logged at the debug level.
+    /// This allows eviction to be inspected for a single cache, without enabling the logs of every cache.
+    pub log_evictions: bool,
+    /// A function that is given the path and size of each file that could be stored in the cache.
+    /// If it returns false, the file is streamed from the filesystem instead of being stored.
+    pub should_cache: Option<Box<dyn Fn(&Path, usize) -> bool + Send + Sync>>,
+    /// If this is true, the operating system is advised to drop files from its page cache once they
+    /// have been read into the cache, so they aren't held in memory twice. This only has an effect on Linux.
+    pub release_page_cache: bool,
+    /// If this is set, paths that couldn't be found are remembered for this long, so repeatedly getting
+    /// a missing file won't check the filesystem for it every time.
+    pub negative_cache_ttl: Option<Duration>,
+    /// The name of the file that is served when a directory is gotten, such as `index.html`.
+    /// If this is `None`, getting a directory returns `CachedFile::NotFound`.
+    pub index_file: Option<String>,
+    /// If this is true, paths that only differ by case refer to the same file.
+    /// A path that doesn't exist is matched against the files on disk ignoring case,
+    /// and the file is stored under the path it has on disk, so every casing shares a single entry.
+    pub case_insensitive: bool,
+    pub(crate) file_map: B, // Holds the files that the cache is caching
+    pub(crate) stored_bytes: AtomicUsize, // The sum of the sizes of the files in the file_map, so the map doesn't have to be walked to get it.
     pub(crate) access_count_map: ConcHashMap<PathBuf, usize, RandomState>, // Every file that is accessed will have the number of times it is accessed logged in this map.
+    pub(crate) last_access_map: ConcHashMap<PathBuf, Instant, RandomState>, // Every file that is accessed will have the time it was last accessed logged in this map.
+    pub(crate) evictions: AtomicU64, // The number of files that have been evicted to make room for other files.
+    pub(crate) eviction_waste_bytes: AtomicU64, // The bytes that were evicted beyond those needed to make room for other files.
+    pub(crate) expected_checksums: ConcHashMap<PathBuf, u64, RandomState>, // Files read from the filesystem must match these checksums to be stored.
+    pub(crate) pinned: ConcHashMap<PathBuf, (), RandomState>, // Files at these paths are never removed to make room for other files.
+    pub(crate) missing: ConcHashMap<PathBuf, Instant, RandomState>, // The paths that recently couldn't be found, and when they were looked for.
+    pub(crate) missing_inserts: AtomicUsize, // The number of missing paths remembered, used to schedule sweeps of the expired ones.
+    pub(crate) case_folded_paths: ConcHashMap<PathBuf, PathBuf, RandomState>, // Lowercased paths, and the paths of the files on disk they matched.
+    #[cfg(feature = "rocket")]
+    pub(crate) path_headers: ConcHashMap<PathBuf, Vec<Header<'static>>, RandomState>, // Headers set on the responses of specific files.
+    #[cfg(feature = "rocket")]
+    pub(crate) language_variants: ConcHashMap<PathBuf, Vec<(String, PathBuf)>, RandomState>, // The languages and paths of the variants registered under a logical path.
+    #[cfg(feature = "rocket")]
+    pub(crate) zstd_sources: ConcHashMap<PathBuf, String, RandomState>, // The ETags of the files that the compressed variants in the cache were compressed from.
+    pub(crate) files_read: AtomicU64, // The number of times a file has been read into memory.
+    pub(crate) hits: AtomicU64, // The number of times a file was gotten from the cache.
+    pub(crate) misses: AtomicU64, // The number of times a file wasn't in the cache when it was gotten.
+    pub(crate) in_flight: Mutex<HashMap<PathBuf, Arc<Mutex<()>>>>, // Locks for the paths of files that are currently being added to the cache.
+    pub(crate) background_inserts: Mutex<Option<mpsc::SyncSender<PathBuf>>>, // If a background evictor is running, files that aren't in the cache are sent to it to be stored.
+    pub(crate) background_refreshes: Mutex<Option<mpsc::SyncSender<PathBuf>>>, // If a background refresher is running, files that are due to be refreshed are sent to it.
+    pub(crate) random_state: Mutex<u64>, // The state of the random number generator used by the weighted random eviction policy.
+    pub(crate) response_timer: ResponseTimer, // The time spent building responses for files served from the cache.
+    pub(crate) read_limiter: ReadLimiter, // Counts the files being read from the filesystem, so the `max_concurrent_reads` can be enforced.
+    pub(crate) archive: Option<Archive>, // If the cache serves files from an archive, instead of the filesystem, this indexes the archive's files.
 }
 
 
-impl Debug for Cache {
+impl<B: CacheBackend> Debug for Cache<B> {
     fn fmt(&self, fmt: &mut Formatter) -> fmt::Result {
         fmt.debug_map()
-            .entries(self.file_map.iter().map(
-                |(ref k, ref v)| (k.clone(), v.clone()),
-            ))
+            .entries(self.file_map.entries())
             .finish()
     }
 }
 
+/// Creates a cache that can hold `DEFAULT_SIZE_LIMIT` (50 MB) bytes of files,
+/// with no limits on individual file size, and the default priority function.
+impl Default for Cache {
+    #[allow(deprecated)]
+    fn default() -> Cache {
+        Cache::new(DEFAULT_SIZE_LIMIT)
+    }
+}
+
 impl Cache {
 
     /// Creates a new Cache with the given size limit, no limits on individual file size, and the default priority function.
@@ -106,1112 +450,6973 @@ impl Cache {
     /// ```
     #[deprecated(since="0.11.1", note="Please use CacheBuilder::new().size_limit(usize).build() instead.")]
     pub fn new(size_limit: usize) -> Cache {
-        Cache {
-            size_limit,
-            min_file_size: 0,
-            max_file_size: usize::MAX,
-            priority_function: default_priority_function,
-            accesses_per_refresh: None,
-            age_out: None,
-            file_map: ConcHashMap::<PathBuf, InMemoryFile, RandomState>::new(),
-            access_count_map: ConcHashMap::<PathBuf, usize, RandomState>::new(),
-        }
-    }
-
-    fn record_access_and_possibly_age_out(&self){
-
+        Cache::with_backend(size_limit, ConcHashMap::new())
     }
 
-    /// Either gets the file from the cache if it exists there, gets it from the filesystem and
-    /// tries to cache it, or fails to find the file.
+    /// Creates a new Cache that serves the files in a tar or zip archive, instead of files in the filesystem.
+    ///
+    /// Only the headers of the archive's entries, or the central directory of a zip archive, are read when the cache is created.
+    /// The contents of each file are read from the archive and stored in the cache the first time the file is gotten.
+    /// Because files can't be streamed from the archive, files from the archive will replace other
+    /// files regardless of their priority if room must be made for them.
     ///
-    /// The CachedFile that is returned takes a lock out on that file in the cache, if that file happens to exist in the cache.
-    /// This lock will release when the CachedFile goes out of scope.
+    /// Files are gotten using their paths within the archive, without a leading `./`.
+    /// Zip archives are recognized by their signature, and their files may be stored or compressed with deflate.
+    /// Any other archive is read as an uncompressed tar archive.
     ///
     /// # Arguments
     ///
-    /// * `path` - A path that represents the path of the file in the filesystem. The path
-    /// also acts as a key for the file in the cache.
-    /// The path will be used to find a cached file in the cache or find a file in the filesystem if
-    /// an entry in the cache doesn't exist.
+    /// * `archive` - The bytes of the tar or zip archive.
+    /// * `size_limit` - The number of bytes that the Cache is allowed to hold at a given time.
+    ///
+    /// # Return
+    ///
+    /// An error will be returned if the archive can't be read, or if it isn't a valid tar or zip archive.
     ///
     /// # Example
     ///
     /// ```
-    /// #![feature(attr_literals)]
-    /// #![feature(custom_attribute)]
-    /// # extern crate rocket;
-    /// # extern crate rocket_file_cache;
+    /// use rocket_file_cache::Cache;
+    /// use std::io::Cursor;
     ///
-    /// # fn main() {
-    /// use rocket_file_cache::{Cache, CachedFile};
-    /// use std::path::{Path, PathBuf};
-    /// use rocket::State;
-    /// use std::sync::Arc;
-    /// use std::sync::atomic::AtomicPtr;
+    /// // An archive without any entries consists of two empty blocks.
+    /// let cache = Cache::from_archive(Cursor::new(vec![0u8; 1024]), 1024 * 1024 * 10).unwrap();
+    /// assert!(!cache.contains_key("index.html"));
+    /// ```
+    pub fn from_archive<R: Read + Seek + Send + 'static>(archive: R, size_limit: usize) -> io::Result<Cache> {
+        #[allow(deprecated)]
+        let mut cache: Cache = Cache::new(size_limit);
+        cache.archive = Some(Archive::index(archive)?);
+        Ok(cache)
+    }
+
+    /// Creates a new Cache that uses the built in priority function with the given name,
+    /// so the priority function can be chosen by a configuration file instead of at compile time.
     ///
+    /// The known names are `"default"` (also `"balanced"`), `"normal"`, `"access"`, `"small_files"`
+    /// and `"small_files_access"`, which refer to the priority functions of the same name.
+    ///
+    /// # Return
+    ///
+    /// An error holding the name will be returned if it doesn't belong to a built in priority function.
+    ///
+    /// # Example
     ///
-    /// #[get("/<file..>")]
-    /// fn files<'a>(file: PathBuf,  cache: State<'a, Cache> ) -> CachedFile<'a> {
-    ///     let path: PathBuf = Path::new("www/").join(file).to_owned();
-    ///     cache.inner().get(path)
-    /// }
-    /// # }
     /// ```
-    pub fn get<'a, P: AsRef<Path>>(&'a self, path: P) -> CachedFile<'a> {
-        trace!("{:#?}", self);
-        // First, try to get the file in the cache that corresponds to the desired path.
-
-        if self.contains_key(&path.as_ref().to_path_buf()) {
-            // File is in the cache, increment the count, update the stats attached to the cache entry.
-            self.increment_access_count(&path);
-            self.update_stats(&path);
-
-            // See if the file should be refreshed
-            if let Some(accesses_per_refresh) = self.accesses_per_refresh {
-                match self.access_count_map.find(&path.as_ref().to_path_buf()) {
-                    Some(accesses) => {
-                        let access_count: usize = accesses.get().clone();
-                        // If the access count is a multiple of the refresh parameter, then refresh the file.
-                        if access_count % accesses_per_refresh == 0 {
-                            debug!( "Refreshing entry for path: {:?}", path.as_ref() );
-                            return self.refresh(path.as_ref())
-                        }
-                    }
-                    None => warn!("Cache contains entry for {:?}, but does not tract its access counts.", path.as_ref())
-                }
-            }
-
-        } else {
-            return self.try_insert(path);
-        }
-
-        self.get_from_cache(&path)
+    /// use rocket_file_cache::{Cache, UnknownPriorityFunction};
+    ///
+    /// let cache = Cache::new_with_named_priority(1024 * 1024 * 10, "access").unwrap();
+    /// assert_eq!(cache.size_limit(), 1024 * 1024 * 10);
+    ///
+    /// let error = Cache::new_with_named_priority(1024 * 1024 * 10, "fastest").unwrap_err();
+    /// assert_eq!(error, UnknownPriorityFunction("fastest".to_string()));
+    /// ```
+    pub fn new_with_named_priority(size_limit: usize, name: &str) -> Result<Cache, UnknownPriorityFunction> {
+        let priority_function = priority_function_by_name(name)?;
+        #[allow(deprecated)]
+        let mut cache: Cache = Cache::new(size_limit);
+        cache.priority_function = priority_function;
+        Ok(cache)
     }
 
-
-    /// If a file has changed on disk, the cache will not automatically know that a change has occurred.
-    /// Calling this function will check if the file exists, read the new file into memory,
-    /// replace the old file, and update the priority score to reflect the new size of the file.
+    /// Creates a new Cache whose size limit is a fraction of the system's available memory.
+    ///
+    /// The available memory is read when the cache is created, so the size limit will be smaller
+    /// if the system is already under memory pressure.
+    /// The size limit can be re-evaluated later using `fit_to_memory()`.
+    ///
+    /// Reading the system's memory is only supported on Linux.
+    /// On other platforms, an error will be returned.
     ///
     /// # Arguments
     ///
-    /// * `path` - A path that represents the path of the file in the filesystem, and key to
-    /// the file in the cache.
-    /// The path will be used to find the new file in the filesystem and to find the old file to replace in
-    /// the cache.
+    /// * `fraction` - The fraction of the available memory that the cache is allowed to hold.
+    /// * `priority_function` - The function that is used to calculate the priority score of files.
     ///
-    /// # Return
+    /// # Panics
     ///
-    /// The CachedFile will indicate NotFound if the file isn't already in the cache or if it can't
-    /// be found in the filesystem.
-    /// It will otherwise return a CachedFile::InMemory variant.
-    pub fn refresh<P: AsRef<Path>>(&self, path: P) -> CachedFile {
-
-        let mut is_ok_to_refresh: bool = false;
-
-        // Check if the file exists in the cache
-        if self.contains_key(&path.as_ref().to_path_buf()) {
-            // See if the new file exists.
-            let path_string: String = match path.as_ref().to_str() {
-                Some(s) => String::from(s),
-                None => return CachedFile::NotFound,
-            };
-            if let Ok(metadata) = fs::metadata(path_string.as_str()) {
-                if metadata.is_file() {
-                    // If the entry for the old file exists
-                    if self.file_map.find(&path.as_ref().to_path_buf()).is_some() {
-                        is_ok_to_refresh = true;
-                    }
-                }
-            };
-        }
-
-        if is_ok_to_refresh {
-            if let Ok(new_file) = InMemoryFile::open(path.as_ref().to_path_buf()) {
-                debug!("Refreshing file: {:?}", path.as_ref());
-                {
-                    self.file_map.remove(&path.as_ref().to_path_buf());
-                    self.file_map.insert(path.as_ref().to_path_buf(), new_file);
-                }
-                self.update_stats(&path);
-
-                return self.get_from_cache(path)
-            }
-        }
-
-        CachedFile::NotFound
+    /// This function will panic if the fraction is not between 0.0 (exclusive) and 1.0 (inclusive).
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use rocket_file_cache::{Cache, default_priority_function};
+    ///
+    /// if let Ok(cache) = Cache::new_fraction_of_memory(0.1, default_priority_function) {
+    ///     assert!(cache.size_limit() > 0);
+    /// }
+    /// ```
+    pub fn new_fraction_of_memory(fraction: f64, priority_function: fn(usize, usize) -> usize) -> io::Result<Cache> {
+        #[allow(deprecated)]
+        let mut cache: Cache = Cache::new(0);
+        cache.priority_function = priority_function;
+        cache.fit_to_memory(fraction)?;
+        Ok(cache)
     }
 
-    /// Removes the file from the cache.
-    /// This will not reset the access count, so the next time the file is accessed, it will be added to the cache again.
-    /// The access count will have to be reset separately using `alter_access_count()`.
-    ///
-    /// # Arguments
+    /// Iterates over the files in the cache and the paths they are stored under, without copying either.
+    /// Only caches that hold their files in the default backend can be iterated over this way.
     ///
-    /// * `path` - A path that acts as a key to look up the file that should be removed from the cache.
+    /// The bytes of each file can be shared cheaply by cloning the `Arc` returned by `InMemoryFile::bytes()`.
+    /// Parts of the cache are locked while they are iterated over, so files can't be stored in or
+    /// removed from those parts until the iterator is dropped. The iterator shouldn't be held for long,
+    /// and the cache shouldn't be modified while iterating over it.
     ///
     /// # Example
     ///
     /// ```
     /// use rocket_file_cache::Cache;
-    /// use std::path::PathBuf;
     ///
-    /// let mut cache = Cache::new(1024 * 1024 * 10);
-    /// let pathbuf = PathBuf::new();
-    /// cache.remove(&pathbuf);
-    /// assert!(cache.contains_key(&pathbuf) == false);
+    /// let cache = Cache::new(1024 * 1024 * 30);
+    /// let largest: usize = cache.iter().map(|(_, file)| file.stats.size).max().unwrap_or(0);
+    /// assert_eq!(largest, 0);
     /// ```
-    pub fn remove<P: AsRef<Path>>(&self, path: P) -> bool {
-        if let Some(_) = self.file_map.remove(&path.as_ref().to_path_buf()) {
-            true
-        } else {
-            false
-        }
+    pub fn iter<'a>(&'a self) -> impl Iterator<Item = (&'a PathBuf, &'a InMemoryFile)> + 'a {
+        self.file_map.iter()
     }
 
-    /// Returns a boolean indicating if the cache has an entry corresponding to the given key.
+    /// Creates a fairing that manages the cache as Rocket's state, and mounts a route at the mount path
+    /// that serves the files under the root directory from the cache.
+    ///
+    /// This replaces managing the cache and writing a route that gets files from it.
+    /// Other routes can still get files from the cache using `State<Cache>`.
     ///
     /// # Arguments
     ///
-    /// * `path` - A path that is used as a key to look up the file.
+    /// * `mount_path` - The path the file-serving route is mounted at, such as `/static`.
+    /// * `root` - The directory that requested paths are relative to.
     ///
     /// # Example
     ///
     /// ```
+    /// # extern crate rocket;
+    /// # extern crate rocket_file_cache;
+    ///
+    /// # fn main() {
     /// use rocket_file_cache::Cache;
-    /// use std::path::PathBuf;
     ///
-    /// let mut cache = Cache::new(1024 * 1024 * 20);
-    /// let pathbuf: PathBuf = PathBuf::new();
-    /// cache.get(&pathbuf);
-    /// assert!(cache.contains_key(&pathbuf) == false);
+    /// let rocket = rocket::ignite().attach(Cache::default().fairing("/", "www/"));
+    /// # }
     /// ```
-    pub fn contains_key<P: AsRef<Path>>(&self, path: P) -> bool {
-        self.file_map.find(&path.as_ref().to_path_buf()).is_some()
+    #[cfg(feature = "rocket")]
+    pub fn fairing<P: AsRef<Path>>(self, mount_path: &str, root: P) -> CacheFairing {
+        CacheFairing::new(self, mount_path, root)
     }
 
-    /// Alters the access count value of one file in the access_count_map.
-    /// # Arguments
+    /// Creates a fairing that manages the cache as Rocket's state, without mounting a route.
     ///
-    /// * `path` - The key to look up the file.
-    /// * `alter_count_function` - A function that determines how to alter the access_count for the file.
+    /// This is useful when the application's own routes decide which files to get from the cache,
+    /// using `State<Cache>`.
     ///
     /// # Example
     ///
     /// ```
+    /// # extern crate rocket;
+    /// # extern crate rocket_file_cache;
+    ///
+    /// # fn main() {
     /// use rocket_file_cache::Cache;
-    /// use std::path::PathBuf;
     ///
-    /// let mut cache = Cache::new(1024 * 1024 * 10);
-    /// let pathbuf = PathBuf::new();
-    /// cache.get(&pathbuf); // Add a file to the cache
-    /// cache.remove(&pathbuf); // Removing the file will not reset its access count.
-    /// cache.alter_access_count(&pathbuf, | x | { 0 }); // Set the access count to 0.
+    /// let rocket = rocket::ignite().attach(Cache::default().state_fairing());
+    /// # }
     /// ```
-    ///
-    pub fn alter_access_count<P: AsRef<Path>>(&self, path: P, alter_count_function: fn(&usize) -> usize) -> bool {
-        let new_count: usize;
-        {
-            match self.access_count_map.find(&path.as_ref().to_path_buf()) {
-                Some(access_count_entry) => {
-                    new_count = alter_count_function(&access_count_entry.get());
-                }
-                None => return false, // Can't update a file that isn't in the cache.
-            }
-        }
-        {
-            self.access_count_map.insert(
-                path.as_ref().to_path_buf(),
-                new_count,
-            );
-        }
-        self.update_stats(&path);
-        return true;
+    #[cfg(feature = "rocket")]
+    pub fn state_fairing(self) -> CacheFairing {
+        CacheFairing::without_route(self)
     }
+}
 
-    /// Alters the access count value of every file in the access_count_map.
-    /// This is useful for manually aging-out entries in the cache.
+impl<B: CacheBackend> Cache<B> {
+
+    /// Creates a new Cache that holds its files in the given backend, with the given size limit,
+    /// no limits on individual file size, and the default priority function.
+    /// These settings can be set by using `CacheBuilder::build_with_backend()` instead.
     ///
     /// # Arguments
     ///
-    /// * `alter_count_function` - A function that determines how to alter the access_count for the file.
+    /// * `size_limit` - The number of bytes that the Cache is allowed to hold at a given time.
+    /// * `backend` - The store that will hold the cached files.
     ///
     /// # Example
     ///
     /// ```
-    /// use rocket_file_cache::Cache;
+    /// # extern crate concurrent_hashmap;
+    /// # extern crate rocket_file_cache;
+    ///
+    /// # fn main() {
+    /// use concurrent_hashmap::ConcHashMap;
+    /// use rocket_file_cache::{Cache, InMemoryFile};
     /// use std::path::PathBuf;
     ///
-    /// let mut cache = Cache::new(1024 * 1024 * 10);
-    /// let pathbuf = PathBuf::new();
-    /// let other_pathbuf = PathBuf::new();
-    /// cache.get(&pathbuf);
-    /// cache.get(&other_pathbuf);
-    /// // Reduce all access counts by half,
-    /// // allowing newer files to enter the cache more easily.
-    /// cache.alter_all_access_counts(| x | { x / 2 });
+    /// let backend: ConcHashMap<PathBuf, InMemoryFile> = ConcHashMap::new();
+    /// let cache: Cache<ConcHashMap<PathBuf, InMemoryFile>> = Cache::with_backend(1024 * 1024 * 30, backend);
+    /// # }
     /// ```
-    ///
-    pub fn alter_all_access_counts(&self, alter_count_function: fn(&usize) -> usize) {
-        let all_counts: Vec<PathBuf>;
-        {
-            all_counts = self.access_count_map
-                .iter()
-                .map(|x: (&PathBuf, &usize)| x.0.clone())
-                .collect();
-        }
-        for pathbuf in all_counts {
-            self.alter_access_count(&pathbuf, alter_count_function);
+    pub fn with_backend(size_limit: usize, backend: B) -> Cache<B> {
+        let stored_bytes: usize = backend.size_bytes();
+        Cache {
+            size_limit: AtomicUsize::new(size_limit),
+            min_file_size: 0,
+            max_file_size: usize::MAX,
+            max_single_file_fraction: None,
+            priority_function: default_priority_function,
+            accesses_per_refresh: None,
+            ttl: None,
+            max_concurrent_reads: None,
+            age_out: None,
+            high_watermark: None,
+            low_watermark: None,
+            reserve_fraction: 0.0,
+            max_ages: HashMap::new(),
+            default_max_age: None,
+            #[cfg(feature = "rocket")]
+            default_content_type: None,
+            #[cfg(feature = "rocket")]
+            headers: vec![],
+            #[cfg(feature = "rocket")]
+            compressible_types: default_compressible_types(),
+            #[cfg(feature = "rocket")]
+            max_range_multiple: 1,
+            zstd_level: None,
+            compression_dictionary: None,
+            extension_weights: HashMap::new(),
+            on_evict: None,
+            passthrough: false,
+            eviction_policy: EvictionPolicy::LowestPriority,
+            on_checksum_mismatch: ChecksumMismatch::Abort,
+            log_evictions: false,
+            should_cache: None,
+            release_page_cache: false,
+            negative_cache_ttl: None,
+            index_file: None,
+            case_insensitive: false,
+            file_map: backend,
+            stored_bytes: AtomicUsize::new(stored_bytes),
+            access_count_map: ConcHashMap::<PathBuf, usize, RandomState>::new(),
+            last_access_map: ConcHashMap::<PathBuf, Instant, RandomState>::new(),
+            evictions: AtomicU64::new(0),
+            eviction_waste_bytes: AtomicU64::new(0),
+            expected_checksums: ConcHashMap::<PathBuf, u64, RandomState>::new(),
+            pinned: ConcHashMap::<PathBuf, (), RandomState>::new(),
+            missing: ConcHashMap::<PathBuf, Instant, RandomState>::new(),
+            missing_inserts: AtomicUsize::new(0),
+            case_folded_paths: ConcHashMap::<PathBuf, PathBuf, RandomState>::new(),
+            #[cfg(feature = "rocket")]
+            path_headers: ConcHashMap::<PathBuf, Vec<Header<'static>>, RandomState>::new(),
+            #[cfg(feature = "rocket")]
+            language_variants: ConcHashMap::<PathBuf, Vec<(String, PathBuf)>, RandomState>::new(),
+            #[cfg(feature = "rocket")]
+            zstd_sources: ConcHashMap::<PathBuf, String, RandomState>::new(),
+            files_read: AtomicU64::new(0),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+            in_flight: Mutex::new(HashMap::new()),
+            background_inserts: Mutex::new(None),
+            background_refreshes: Mutex::new(None),
+            random_state: Mutex::new(Self::clock_seed()),
+            response_timer: ResponseTimer::new(),
+            read_limiter: ReadLimiter::new(),
+            archive: None,
         }
+    }
 
+    /// Sets the size limit of the cache to a fraction of the system's currently available memory.
+    ///
+    /// This can be called periodically to shrink the cache when the system is under memory pressure,
+    /// and to grow it again once memory is freed.
+    /// Files aren't removed when the size limit shrinks, but the cache will remove files to get back
+    /// under the size limit when new files are stored.
+    ///
+    /// Reading the system's memory is only supported on Linux.
+    /// On other platforms, an error will be returned, and the size limit won't change.
+    ///
+    /// # Arguments
+    ///
+    /// * `fraction` - The fraction of the available memory that the cache is allowed to hold.
+    ///
+    /// # Return
+    ///
+    /// The new size limit.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if the fraction is not between 0.0 (exclusive) and 1.0 (inclusive).
+    pub fn fit_to_memory(&self, fraction: f64) -> io::Result<usize> {
+        if fraction <= 0.0 || fraction > 1.0 {
+            panic!("Incorrectly configured memory fraction. Values must be between 0.0 and 1.0.");
+        }
+        let memory: SystemMemory = SystemMemory::read()?;
+        let size_limit: usize = memory.size_limit(fraction);
+        self.set_size_limit(size_limit);
+        debug!("Set the size limit of the cache to {} bytes, {} of the {} available bytes of memory.", size_limit, fraction, memory.available);
+        Ok(size_limit)
     }
 
-    /// Gets the sum of the sizes of the files that are stored in the cache.
+    /// Spawns a thread that sets the size limit of the cache to a fraction of the system's available memory
+    /// every interval, using `fit_to_memory()`.
+    ///
+    /// The thread only holds a weak reference to the cache, so it stops once the cache is dropped.
+    /// It also stops if the system's memory can't be read, which is the case on platforms other than Linux.
+    ///
+    /// # Arguments
+    ///
+    /// * `cache` - The cache whose size limit is set.
+    /// * `fraction` - The fraction of the available memory that the cache is allowed to hold.
+    /// * `interval` - The time between checks of the available memory.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if the fraction is not between 0.0 (exclusive) and 1.0 (inclusive).
     ///
     /// # Example
     ///
     /// ```
     /// use rocket_file_cache::Cache;
+    /// use std::sync::Arc;
+    /// use std::time::Duration;
     ///
-    /// let cache = Cache::new(1024 * 1024 * 30);
-    /// assert!(cache.used_bytes() == 0);
+    /// let cache = Arc::new(Cache::new(1024 * 1024 * 10));
+    /// Cache::fit_to_memory_periodically(&cache, 0.1, Duration::from_secs(60));
     /// ```
-    pub fn used_bytes(&self) -> usize {
-        self.file_map.iter().fold(
-            0usize,
-            |size, x| size + x.1.stats.size,
-        )
+    pub fn fit_to_memory_periodically(cache: &Arc<Cache<B>>, fraction: f64, interval: Duration) -> JoinHandle<()>
+        where B: 'static
+    {
+        if fraction <= 0.0 || fraction > 1.0 {
+            panic!("Incorrectly configured memory fraction. Values must be between 0.0 and 1.0.");
+        }
+        let cache: Weak<Cache<B>> = Arc::downgrade(cache);
+        thread::spawn(move || loop {
+            thread::sleep(interval);
+            match cache.upgrade() {
+                Some(cache) => {
+                    if let Err(e) = cache.fit_to_memory(fraction) {
+                        warn!("Stopped fitting the cache to memory, because the system's memory couldn't be read: {}", e);
+                        return;
+                    }
+                }
+                None => return,
+            }
+        })
     }
 
-    /// Gets the size of the file from the file's metadata.
-    /// This avoids having to read the file into memory in order to get the file size.
-    fn get_file_size_from_metadata<P: AsRef<Path>>(path: P) -> Result<usize, CacheError> {
-        let path_string: String = match path.as_ref().to_str() {
-            Some(s) => String::from(s),
-            None => return Err(CacheError::InvalidPath),
-        };
-        let metadata: Metadata = match fs::metadata(path_string.as_str()) {
-            Ok(m) => m,
-            Err(_) => return Err(CacheError::InvalidMetadata),
-        };
-        let size: usize = metadata.len() as usize;
-        Ok(size)
+    /// Gets a seed for the random number generator from the system clock.
+    pub(crate) fn clock_seed() -> u64 {
+        match SystemTime::now().duration_since(UNIX_EPOCH) {
+            Ok(duration) => duration.as_secs() ^ (duration.subsec_nanos() as u64) << 32,
+            Err(_) => 0,
+        }
     }
 
+    fn record_access_and_possibly_age_out(&self){
 
-    /// Attempt to store a given file in the the cache.
-    /// Storing will fail if the current files have more access attempts than the file being added.
-    /// If the provided file has more more access attempts than one of the files in the cache,
-    /// but the cache is full, a file will have to be removed from the cache to make room
-    /// for the new file.
+    }
+
+    /// Either gets the file from the cache if it exists there, gets it from the filesystem and
+    /// tries to cache it, or fails to find the file.
     ///
-    /// If the insertion works, the cache will update the priority score for the file being inserted.
-    /// The cached priority score requires the file in question to exist in the file map, so it will
-    /// have a size to use when calculating.
+    /// If the file is in the cache, the CachedFile that is returned holds a copy of it,
+    /// so the file can be removed from or replaced in the cache while the CachedFile is still being used.
     ///
-    /// It will get the size of the file to be inserted.
-    /// If will use this size to check if the file could be inserted.
-    /// If it can be inserted, it reads the file into memory, stores a copy of the in-memory
-    /// file behind a pointer, and constructs a CachedFile to return.
+    /// If the cache is in passthrough mode, the file is always gotten from the filesystem, and is never stored.
     ///
-    /// If the file can't be added, it will open a NamedFile and construct a CachedFile from that,
-    /// and return it.
-    /// This means that it doesn't need to read the whole file into memory before reading through it
-    /// again to set the response body.
-    /// The lack of the need to read the whole file twice keeps performance of cache misses on par
-    /// with just normally reading the file without a cache.
+    /// # Arguments
     ///
+    /// * `path` - A path that represents the path of the file in the filesystem. The path
+    /// also acts as a key for the file in the cache.
+    /// The path will be used to find a cached file in the cache or find a file in the filesystem if
+    /// an entry in the cache doesn't exist.
     ///
-    /// # Arguments
+    /// # Example
     ///
-    /// * `path` - The path of the file to be stored. Acts as a key for the file in the cache. Is used
-    /// look up the location of the file in the filesystem if the file is not in the cache.
+    /// ```
+    /// #![feature(attr_literals)]
+    /// #![feature(custom_attribute)]
+    /// # extern crate rocket;
+    /// # extern crate rocket_file_cache;
+    ///
+    /// # fn main() {
+    /// use rocket_file_cache::{Cache, CachedFile};
+    /// use std::path::{Path, PathBuf};
+    /// use rocket::State;
+    /// use std::sync::Arc;
+    /// use std::sync::atomic::AtomicPtr;
     ///
     ///
-    fn try_insert<P: AsRef<Path>>(&self, path: P) -> CachedFile {
-        let path: PathBuf = path.as_ref().to_path_buf();
-        trace!("Trying to insert file {:?}", path);
+    /// #[get("/<file..>")]
+    /// fn files<'a>(file: PathBuf,  cache: State<'a, Cache> ) -> CachedFile<'a> {
+    ///     let path: PathBuf = Path::new("www/").join(file).to_owned();
+    ///     cache.inner().get(path)
+    /// }
+    /// # }
+    /// ```
+    pub fn get<'a, P: AsRef<Path>>(&'a self, path: P) -> CachedFile<'a> {
+        self.get_with_origin(path).0
+    }
 
-        // If the FS can read metadata for a file, then the file exists, and it should be safe to increment
-        // the access_count and update.
-        let size: usize = match Cache::get_file_size_from_metadata(&path) {
-            Ok(size) => size,
-            Err(_) => return CachedFile::NotFound // Could not open file to read metadata.
-        };
 
-        // Determine how much space can still be used (represented by a negative value) or how much
-        // space needs to be freed in order to make room for the new file
-        let required_space_for_new_file: isize = (self.used_bytes() as isize + size as isize) - self.size_limit as isize;
+    /// Gets the file in the same way as `get()`, unless the request asks for fresh content with a
+    /// `Cache-Control: no-cache` or `Pragma: no-cache` header.
+    ///
+    /// If it does, and the file is in the cache, the file is read from the filesystem again and the
+    /// cached copy is replaced, as if by `refresh()`, before it is served.
+    /// If the file can no longer be found, it is removed from the cache.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// #![feature(attr_literals)]
+    /// #![feature(custom_attribute)]
+    /// # extern crate rocket;
+    /// # extern crate rocket_file_cache;
+    ///
+    /// # fn main() {
+    /// use rocket_file_cache::{Cache, CachedFile};
+    /// use rocket::request::Request;
+    /// use std::path::PathBuf;
+    ///
+    /// fn serve<'a>(cache: &'a Cache, file: PathBuf, request: &Request) -> CachedFile<'a> {
+    ///     cache.get_for_request(PathBuf::from("www/").join(file), request)
+    /// }
+    /// # }
+    /// ```
+    #[cfg(feature = "rocket")]
+    pub fn get_for_request<'a, P: AsRef<Path>>(&'a self, path: P, request: &Request) -> CachedFile<'a> {
+        let path: PathBuf = self.resolve_index_file(self.resolve_case(path));
+        if Self::requests_fresh_content(request) && self.contains_key(&path) {
+            debug!("The request for {:?} asked for fresh content, refreshing it.", path);
+            self.increment_access_count(&path);
+            let refreshed_file: CachedFile = self.refresh(&path);
+            if refreshed_file != CachedFile::NotFound {
+                return refreshed_file;
+            }
+            self.remove(&path);
+        }
+        self.get(path)
+    }
+
+    /// Helper function that checks if the request's Cache-Control or Pragma headers have a `no-cache` directive.
+    #[cfg(feature = "rocket")]
+    fn requests_fresh_content(request: &Request) -> bool {
+        request.headers()
+            .get("Cache-Control")
+            .chain(request.headers().get("Pragma"))
+            .flat_map(|value| value.split(','))
+            .any(|directive| directive.trim().eq_ignore_ascii_case("no-cache"))
+    }
 
+    /// Gets content from the cache, or generates it and stores it in the cache if it isn't there.
+    ///
+    /// This allows content that doesn't exist in the filesystem, such as rendered templates, to be
+    /// cached, so that the generator only needs to run when the content isn't in the cache.
+    /// If room must be made for the content, it is admitted the same way a file read by `get()` would be,
+    /// so it only replaces files with a lower priority than its own.
+    /// If the cached files are more important, or pinned files prevent enough room from being made,
+    /// the content is served without being stored, and is generated again the next time it is gotten.
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - A path that acts as a key for the content in the cache. It does not need to exist in the filesystem.
+    /// * `generate` - A function that produces the content if it isn't in the cache.
+    ///
+    /// # Return
+    ///
+    /// An error will be returned if the generator fails, or if the generated content does not fit
+    /// within the size constraints of the cache.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use rocket_file_cache::Cache;
+    /// use std::path::PathBuf;
+    ///
+    /// let cache = Cache::new(1024 * 1024 * 10);
+    /// let cached_file = cache.get_or_compute(PathBuf::from("rendered/index.html"), || {
+    ///     Ok(b"<h1>Hello World!</h1>".to_vec())
+    /// });
+    /// assert!(cached_file.is_ok());
+    /// ```
+    pub fn get_or_compute<'a, P, F>(&'a self, key: P, generate: F) -> io::Result<CachedFile<'a>>
+        where P: AsRef<Path>,
+              F: FnOnce() -> io::Result<Vec<u8>>
+    {
+        self.get_or_compute_file(key, || generate().map(InMemoryFile::from_bytes))
+    }
 
-        if size > self.max_file_size || size < self.min_file_size {
-            self.get_file_from_fs(&path)
-        } else if required_space_for_new_file < 0 && size < self.size_limit {
-            self.get_file_from_fs_and_add_to_cache(&path)
-        } else {
-            debug!("Trying to make room for the file");
+    /// Gets content from the cache, or generates it and stores it in the cache with the given Content-Type.
+    ///
+    /// This works like `get_or_compute()`, but the content is served with the given Content-Type,
+    /// instead of the one implied by the key's extension.
+    /// This allows content to be stored under keys without extensions.
+    /// If the content is already in the cache, it keeps the Content-Type it was stored with.
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - A path that acts as a key for the content in the cache. It does not need to exist in the filesystem.
+    /// * `content_type` - The Content-Type the content is served with.
+    /// * `generate` - A function that produces the content if it isn't in the cache.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # extern crate rocket;
+    /// # extern crate rocket_file_cache;
+    /// # fn main() {
+    /// use rocket_file_cache::Cache;
+    /// use rocket::http::ContentType;
+    /// use std::path::PathBuf;
+    ///
+    /// let cache = Cache::new(1024 * 1024 * 10);
+    /// let cached_file = cache.get_or_compute_with_content_type(PathBuf::from("api/status"), ContentType::JSON, || {
+    ///     Ok(b"{\"status\": \"ok\"}".to_vec())
+    /// });
+    /// assert!(cached_file.is_ok());
+    /// # }
+    /// ```
+    #[cfg(feature = "rocket")]
+    pub fn get_or_compute_with_content_type<'a, P, F>(&'a self, key: P, content_type: ContentType, generate: F) -> io::Result<CachedFile<'a>>
+        where P: AsRef<Path>,
+              F: FnOnce() -> io::Result<Vec<u8>>
+    {
+        self.get_or_compute_file(key, || {
+            generate().map(|bytes| {
+                let mut file: InMemoryFile = InMemoryFile::from_bytes(bytes);
+                file.content_type = Some(content_type);
+                file
+            })
+        })
+    }
 
-            // Because the size was gotten from the file's metadata, we know that it exists,
-            // so its fine to increment the account
-            self.increment_access_count(&path);
+    /// Helper function that gets content from the cache, or generates the in-memory file and stores it.
+    fn get_or_compute_file<'a, P, F>(&'a self, key: P, generate: F) -> io::Result<CachedFile<'a>>
+        where P: AsRef<Path>,
+              F: FnOnce() -> io::Result<InMemoryFile>
+    {
+        self.get_or_compute_file_with_origin(key, generate).map(|(cached_file, _)| cached_file)
+    }
 
-            // The access_count should have incremented since the last time this was called, so the priority must be recalculated.
-            // Also, the size generally
-            let new_file_priority: usize;
-            {
-                let new_file_access_count: &usize = match self.access_count_map.find(&path) {
-                    Some(access_count) => &access_count.get(),
-                    None => &1,
-                };
-                new_file_priority = (self.priority_function)(new_file_access_count.clone(), size);
+    /// Gets the content in the same way as `get_or_compute_file()`, also indicating if it was already in the cache.
+    fn get_or_compute_file_with_origin<'a, P, F>(&'a self, key: P, generate: F) -> io::Result<(CachedFile<'a>, Origin)>
+        where P: AsRef<Path>,
+              F: FnOnce() -> io::Result<InMemoryFile>
+    {
+        let key: PathBuf = key.as_ref().to_path_buf();
+        if self.contains_key(&key) {
+            // The content has no file to be refreshed from, so it is gotten directly from the cache.
+            self.increment_access_count(&key);
+            self.update_stats(&key);
+            match self.get_from_cache(&key) {
+                CachedFile::NotFound => trace!("{:?} was removed from the cache while it was being gotten.", key),
+                cached_file => return Ok((cached_file, Origin::Cache)),
             }
+        }
 
+        let file: InMemoryFile = generate()?;
+        let size: usize = file.stats.size;
+        if size > self.max_cached_file_size() || size < self.min_file_size || !self.fits_in_size_limit(size) {
+            return Err(io::Error::new(
+                io::ErrorKind::Other,
+                "The generated content does not fit the size constraints of the cache.",
+            ));
+        }
 
-            match self.make_room_for_new_file(required_space_for_new_file as usize, new_file_priority) {
-                Ok(files_to_be_removed) => {
-                    debug!("Made room for new file");
-                    match InMemoryFile::open(path.as_path()) {
-                        Ok(file) => {
-
-                            // We have read a new file into memory, it is safe to
-                            // remove the old files.
-                            for file_key in files_to_be_removed {
-                                // The file was accessed with this key earlier when sorting priorities, which should make removal safe.
-                                match self.file_map.remove(&file_key) {
-                                    Some(_) => {},
-                                    None => warn!("Likely due to concurrent mutations, a file being removed from the cache was not found because another thread removed it first.")
-                                };
-                            }
-
-                            self.file_map.insert(path.clone(), file);
-                            self.update_stats(&path);
+        self.increment_access_count(&key);
+        let used_bytes: usize = self.used_bytes();
+        let required_space: usize = cmp::min((used_bytes + size).saturating_sub(self.usable_size_limit()), used_bytes);
+        if required_space > 0 {
+            let access_count: usize = self.access_count_map.find(&key).map_or(1, |access_count| *access_count.get());
+            let priority: usize = self.priority_for(&key, access_count, size);
+            match self.make_room_for_new_file(&key, required_space, priority) {
+                Ok(files_to_be_removed) => self.evict_to_make_room(files_to_be_removed, required_space),
+                Err(_) => {
+                    // The cached files are more important, or pinned files take up the space,
+                    // so the content is served without being stored.
+                    debug!("Couldn't make room for generated content for key: {:?}", key);
+                    return Ok((CachedFile::Owned(OwnedInMemoryFile::new(key, file)), Origin::Filesystem));
+                }
+            }
+        }
 
-                            let cache_file_accessor = match self.file_map.find(&path) {
-                                Some(accessor_to_file) => accessor_to_file,
-                                None => {
-                                    // If a concurrent remove operation removes the file before
-                                    // it can be gotten via an accessor lock, recursively try to add
-                                    // the file to the Cache until the lock can be attained.
+        debug!("Storing generated content for key: {:?}", key);
+        self.store_file(key.clone(), file);
+        self.update_stats(&key);
+        Ok((self.get_from_cache(&key), Origin::Filesystem))
+    }
 
-                                    // Because this action takes place after room was made for
-                                    // the new file in the cache, those files will be left out of the cache.
-                                    warn!("Tried to add file to cache, but it was removed before it could be added. Attempting to insert file again.");
-                                    // Because this recursion only occurs under extremely rare
-                                    // circumstances due to concurrent removal of the file being
-                                    // added between the insertion into the map, and locking an
-                                    // accessor, a stack overflow is almost impossible. This would require
-                                    // the file to be removed on every recursive attempt to re-insert it,
-                                    // with the exact same timing required to invalidate the `find()` method,
-                                    // for as many times as it takes to fill up the stack. It's not
-                                    // going to happen.
-                                    return self.try_insert(path);
-                                }
-                            };
+    /// Gets the file in the same way as `get()`, but also indicates if the file was already in the cache.
+    ///
+    /// The origin is determined by how the file was gotten, so it is accurate even if other threads store or remove the file at the same time.
+    /// A file that was read from the filesystem by this call has an origin of `Origin::Filesystem`,
+    /// whether it was stored in the cache, streamed because it couldn't be stored, or refreshed because it was
+    /// due to be refreshed or had outlived the cache's `ttl`. Every file has that origin when `passthrough` is enabled.
+    /// Files that couldn't be found also have that origin.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - A path that represents the path of the file in the filesystem, and key to
+    /// the file in the cache.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use rocket_file_cache::{Cache, CachedFile, Origin};
+    /// use std::path::PathBuf;
+    ///
+    /// let cache = Cache::new(1024 * 1024 * 10);
+    /// let (cached_file, origin): (CachedFile, Origin) = cache.get_with_origin(PathBuf::from("www/test.txt"));
+    /// let x_cache: &str = match origin {
+    ///     Origin::Cache => "HIT",
+    ///     Origin::Filesystem => "MISS",
+    /// };
+    /// ```
+    pub fn get_with_origin<'a, P: AsRef<Path>>(&'a self, path: P) -> (CachedFile<'a>, Origin) {
+        trace!("{:#?}", self);
+        // Missing paths are remembered as they were requested, so they are recognized before the filesystem
+        // is checked to resolve their case or an index file.
+        let requested_path: PathBuf = path.as_ref().to_path_buf();
+        if !self.passthrough && self.archive.is_none() && self.is_known_to_be_missing(&requested_path) {
+            trace!("{:?} was recently found to be missing, not checking the filesystem for it.", requested_path);
+            self.misses.fetch_add(1, Ordering::Relaxed);
+            return (CachedFile::NotFound, Origin::Filesystem);
+        }
 
-                            let named_in_memory_file: NamedInMemoryFile = NamedInMemoryFile::new(
-                                path.clone(),
-                                cache_file_accessor
-                            );
+        let path: PathBuf = self.resolve_case(&requested_path);
+        let path: PathBuf = self.resolve_index_file(path);
+        if self.passthrough {
+            trace!("Passthrough is enabled, getting {:?} from the filesystem.", path);
+            self.misses.fetch_add(1, Ordering::Relaxed);
+            return (self.open_streamed_file(path), Origin::Filesystem);
+        }
 
-                            return CachedFile::from(named_in_memory_file);
-                        }
-                        Err(_) => return CachedFile::NotFound
-                    }
+        if let Some(ref archive) = self.archive {
+            if !archive.contains(&path) {
+                self.misses.fetch_add(1, Ordering::Relaxed);
+                return (CachedFile::NotFound, Origin::Filesystem);
+            }
+            return match self.get_or_compute_file_with_origin(&path, || archive.read(&path).map(InMemoryFile::from_bytes)) {
+                Ok((cached_file, origin)) => {
+                    match origin {
+                        Origin::Cache => self.hits.fetch_add(1, Ordering::Relaxed),
+                        Origin::Filesystem => self.misses.fetch_add(1, Ordering::Relaxed),
+                    };
+                    (cached_file, origin)
                 }
                 Err(_) => {
-                    debug!("The file does not have enough priority or is too large to be accepted into the cache.");
-                    // The new file would not be accepted by the cache, so instead of reading the whole file
-                    // into memory, and then copying it yet again when it is attached to the body of the
-                    // response, use a NamedFile instead.
-                    match NamedFile::open(path.clone()) {
-                        Ok(named_file) => CachedFile::from(named_file),
-                        Err(_) => CachedFile::NotFound,
+                    self.misses.fetch_add(1, Ordering::Relaxed);
+                    (CachedFile::NotFound, Origin::Filesystem)
+                }
+            };
+        }
+
+        // First, try to get the file in the cache that corresponds to the desired path.
+
+        if self.contains_key(&path) {
+            // File is in the cache, increment the count, update the stats attached to the cache entry.
+            self.hits.fetch_add(1, Ordering::Relaxed);
+            self.increment_access_count(&path);
+            self.update_stats(&path);
+
+            if self.ttl_remaining(&path) == Some(Duration::from_secs(0)) {
+                debug!("{:?} has outlived the cache's ttl, refreshing it.", path);
+                let refreshed_file: CachedFile = self.refresh(&path);
+                if refreshed_file == CachedFile::NotFound {
+                    self.remove(&path);
+                }
+                return (refreshed_file, Origin::Filesystem);
+            }
+
+            // See if the file should be refreshed
+            let mut due_for_refresh: bool = false;
+            if let Some(accesses_per_refresh) = self.accesses_per_refresh {
+                // The access count is copied, so its map isn't locked while the file is refreshed.
+                let access_count: Option<usize> = self.access_count_map.find(&path).map(|accesses| *accesses.get());
+                match access_count {
+                    // If the access count is a multiple of the refresh parameter, then refresh the file.
+                    Some(access_count) => due_for_refresh = access_count % accesses_per_refresh == 0,
+                    None => warn!("Cache contains entry for {:?}, but does not tract its access counts.", path)
+                }
+            }
+
+            match self.get_from_cache(&path) {
+                // Another thread removed the file after it was found in the cache, so it must be gotten from the filesystem.
+                CachedFile::NotFound => trace!("{:?} was removed from the cache while it was being gotten.", path),
+                cached_file => {
+                    if !due_for_refresh {
+                        return (cached_file, Origin::Cache);
+                    }
+                    // The cached file is gotten before the refresh is queued,
+                    // so the background refresher can't replace it before it is served.
+                    if self.send_to_background_refresher(&path) {
+                        debug!("Queued a background refresh for {:?}, serving the cached file until it completes.", path);
+                        return (cached_file, Origin::Cache);
+                    }
+                    drop(cached_file);
+                    debug!( "Refreshing entry for path: {:?}", path );
+                    return (self.refresh(&path), Origin::Filesystem)
+                }
+            }
+        } else {
+            self.misses.fetch_add(1, Ordering::Relaxed);
+        }
+
+        let (cached_file, origin): (CachedFile, Origin) = match self.send_to_background_evictor(&path) {
+            Some(cached_file) => (cached_file, Origin::Filesystem),
+            None => self.try_insert_once(&path),
+        };
+        if cached_file == CachedFile::NotFound {
+            self.remember_missing(&requested_path);
+        }
+        (cached_file, origin)
+    }
+
+    /// If a file has changed on disk, the cache will not automatically know that a change has occurred.
+    /// Calling this function will check if the file exists, read the new file into memory,
+    /// replace the old file, and update the priority score to reflect the new size of the file.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - A path that represents the path of the file in the filesystem, and key to
+    /// the file in the cache.
+    /// The path will be used to find the new file in the filesystem and to find the old file to replace in
+    /// the cache.
+    ///
+    /// # Return
+    ///
+    /// The CachedFile will indicate NotFound if the file isn't already in the cache or if it can't
+    /// be found in the filesystem.
+    /// It will otherwise return a CachedFile::InMemory variant.
+    pub fn refresh<P: AsRef<Path>>(&self, path: P) -> CachedFile {
+
+        let mut is_ok_to_refresh: bool = false;
+
+        // Check if the file exists in the cache
+        if self.contains_key(&path.as_ref().to_path_buf()) {
+            // See if the new file exists.
+            let path_string: String = match path.as_ref().to_str() {
+                Some(s) => String::from(s),
+                None => return CachedFile::NotFound,
+            };
+            if let Ok(metadata) = Self::stat(path_string.as_str()) {
+                if metadata.is_file() {
+                    // If the entry for the old file exists
+                    if self.file_map.get(path.as_ref()).is_some() {
+                        is_ok_to_refresh = true;
+                    }
+                }
+            };
+        }
+
+        if is_ok_to_refresh {
+            if let Ok(new_file) = self.read_file(path.as_ref()) {
+                if !self.has_expected_checksum(&path, &new_file) {
+                    return CachedFile::NotFound
+                }
+                debug!("Refreshing file: {:?}", path.as_ref());
+                {
+                    self.remove_stored_file(path.as_ref());
+                    self.store_file(path.as_ref().to_path_buf(), new_file);
+                }
+                self.update_stats(&path);
+
+                return self.get_from_cache(path)
+            }
+        }
+
+        CachedFile::NotFound
+    }
+
+    /// Removes the file from the cache.
+    /// This will not reset the access count, so the next time the file is accessed, it will be added to the cache again.
+    /// The access count will have to be reset separately using `alter_access_count()`.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - A path that acts as a key to look up the file that should be removed from the cache.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use rocket_file_cache::Cache;
+    /// use std::path::PathBuf;
+    ///
+    /// let mut cache = Cache::new(1024 * 1024 * 10);
+    /// let pathbuf = PathBuf::new();
+    /// cache.remove(&pathbuf);
+    /// assert!(cache.contains_key(&pathbuf) == false);
+    /// ```
+    pub fn remove<P: AsRef<Path>>(&self, path: P) -> bool {
+        self.forget_case_folded_path(&path);
+        if let Some(_) = self.remove_stored_file(path.as_ref()) {
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Removes the uncompressed file from the cache, but only if one of its compressed sidecar files is
+    /// also in the cache, which is kept.
+    ///
+    /// Compressed variants are usually much smaller than the files they encode, so this frees most of
+    /// the memory a file uses while `PrecompressedFile` can still serve clients that accept the
+    /// variant's encoding from memory. Only clients that need the uncompressed file will miss,
+    /// in which case it is read from the filesystem and may be cached again.
+    ///
+    /// The removal is counted as an eviction, and the cache's `on_evict` callback is called with the removed file.
+    ///
+    /// The cache does this on its own when it must remove files to make room for others:
+    /// uncompressed files whose compressed variants are cached are removed before any other files.
+    ///
+    /// # Return
+    ///
+    /// True if the uncompressed file was removed.
+    /// False if it wasn't in the cache, or if none of its compressed variants are.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use rocket_file_cache::Cache;
+    ///
+    /// let cache = Cache::new(1024 * 1024);
+    /// cache.offer("www/app.js", vec![0u8; 1024]);
+    /// cache.offer("www/style.css", vec![0u8; 1024]);
+    /// cache.offer("www/app.js.gz", vec![0u8; 64]);
+    ///
+    /// assert!(cache.evict_uncompressed("www/app.js"));
+    /// assert!(cache.contains_key("www/app.js.gz"));
+    /// // There isn't a compressed variant of the stylesheet in the cache, so it is kept.
+    /// assert!(!cache.evict_uncompressed("www/style.css"));
+    /// ```
+    #[cfg(feature = "rocket")]
+    pub fn evict_uncompressed<P: AsRef<Path>>(&self, path: P) -> bool {
+        let path: PathBuf = path.as_ref().to_path_buf();
+        if !self.has_compressed_variant(&path) || !self.contains_key(&path) {
+            return false;
+        }
+        self.evict(&path)
+    }
+
+    /// Helper function that checks if any of the compressed sidecar files of the path are in the cache.
+    #[cfg(feature = "rocket")]
+    fn has_compressed_variant(&self, path: &Path) -> bool {
+        sidecar_paths(path)
+            .iter()
+            .any(|sidecar_path| self.contains_key(sidecar_path))
+    }
+
+    /// Without Rocket, nothing serves compressed sidecar files, so files are never considered to have compressed variants.
+    #[cfg(not(feature = "rocket"))]
+    fn has_compressed_variant(&self, _: &Path) -> bool {
+        false
+    }
+
+    /// Gets the zstd compressed variant of a file in the cache, compressing the file at the cache's `zstd_level`
+    /// if it hasn't been compressed since it was read.
+    ///
+    /// The compressed bytes are stored under the path of the file's `.zst` sidecar file, so they count towards
+    /// the `size_limit` and are evicted like any other file. A compressed variant whose file has been evicted
+    /// is still served, but it is compressed again if the file is read into the cache with different contents.
+    ///
+    /// Returns `None` if the cache doesn't compress files, if neither the file nor its compressed variant are in
+    /// the cache, or if the file has a `.zst` sidecar file in the filesystem, which is served instead.
+    #[cfg(feature = "rocket")]
+    pub(crate) fn get_zstd_compressed<'a>(&'a self, path: &Path) -> Option<CachedFile<'a>> {
+        let level: i32 = self.zstd_level?;
+        let path: PathBuf = path.to_path_buf();
+        let key: PathBuf = sidecar_path(&path, "zst");
+        let compressed_from: Option<String> = self.zstd_sources.find(&key).map(|etag| etag.get().clone());
+        let original: Option<InMemoryFile> = self.file_map.get(&path);
+
+        match (&original, &compressed_from) {
+            (&None, &None) => return None,
+            (&Some(_), &None) if key.is_file() => return None,
+            (&Some(ref original), &Some(ref etag)) if *etag != original.etag => {
+                debug!("{:?} changed since it was compressed, compressing it again.", path);
+                self.remove(&key);
+            }
+            _ => {}
+        }
+
+        let compressed_file: CachedFile<'a> = self.get_or_compute_file(&key, || match original {
+            Some(ref original) => self.zstd_compress(&original.bytes, level).map(InMemoryFile::from_bytes),
+            None => Err(io::Error::new(io::ErrorKind::NotFound, "The file to compress isn't in the cache.")),
+        }).ok()?;
+        if let Some(original) = original {
+            self.zstd_sources.insert(key, original.etag);
+        }
+        Some(compressed_file)
+    }
+
+    /// Helper function that compresses the bytes with zstd at the given level, using the cache's `compression_dictionary` if it has one.
+    #[cfg(feature = "rocket")]
+    fn zstd_compress(&self, bytes: &[u8], level: i32) -> io::Result<Vec<u8>> {
+        match self.compression_dictionary {
+            Some(ref dictionary) => zstd::bulk::Compressor::with_dictionary(level, dictionary)?.compress(bytes),
+            None => zstd::bulk::compress(bytes, level),
+        }
+    }
+
+    /// Modifies the bytes of a cached file in place, without removing it and reading it again.
+    ///
+    /// The file is only modified if no response or other holder shares its bytes, such as an
+    /// `OwnedInMemoryFile` that is still being written.
+    /// The function is given the file's own buffer, so the bytes aren't copied.
+    /// The size, ETag and priority of the file are updated to match its new contents,
+    /// but the file keeps its access count and the time it was read, so modifying it doesn't extend its `ttl`.
+    ///
+    /// Modifying a file can make the cache exceed its size limit, as no files are removed to make room.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The key the file is cached under.
+    /// * `f` - A function that modifies the file's bytes.
+    ///
+    /// # Return
+    ///
+    /// True if the file was modified, false if it isn't cached or its bytes are shared.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use rocket_file_cache::Cache;
+    /// use std::path::PathBuf;
+    ///
+    /// let cache = Cache::new(1024 * 1024 * 10);
+    /// assert!(!cache.with_entry_mut(&PathBuf::from("logs/access.log"), |bytes| bytes.extend_from_slice(b"GET /")));
+    /// ```
+    pub fn with_entry_mut<P: AsRef<Path>, F: FnOnce(&mut Vec<u8>)>(&self, path: P, f: F) -> bool {
+        let path: PathBuf = path.as_ref().to_path_buf();
+        let mut resized: Option<(usize, usize)> = None;
+        let found: bool = self.file_map.update(&path, |file| {
+            let old_size: usize = file.stats.size;
+            match Arc::get_mut(&mut file.bytes) {
+                Some(bytes) => f(bytes),
+                None => {
+                    debug!("The bytes of {:?} are shared, so they can't be modified.", path);
+                    return;
+                }
+            }
+            file.update_size_and_etag();
+            resized = Some((old_size, file.stats.size));
+        });
+        let (old_size, new_size): (usize, usize) = match resized {
+            Some(sizes) if found => sizes,
+            _ => return false,
+        };
+        self.record_resize(old_size, new_size);
+        self.update_stats(&path);
+        true
+    }
+
+    /// Moves a file in the cache to a new key, without reading it from the filesystem again.
+    ///
+    /// This is intended to be used when a file has been renamed in the filesystem.
+    /// The file's access count is moved along with it, and future responses will use the new path
+    /// to determine the file's Content-Type.
+    /// If a file is already cached under the new key, it will be replaced.
+    ///
+    /// # Arguments
+    ///
+    /// * `from` - The key the file is currently cached under.
+    /// * `to` - The new key for the file.
+    ///
+    /// # Return
+    ///
+    /// True if a file was cached under the old key, false otherwise.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use rocket_file_cache::Cache;
+    /// use std::path::PathBuf;
+    ///
+    /// let cache = Cache::new(1024 * 1024 * 10);
+    /// assert!(!cache.rename(&PathBuf::from("www/old.html"), PathBuf::from("www/new.html")));
+    /// ```
+    pub fn rename<P: AsRef<Path>>(&self, from: P, to: PathBuf) -> bool {
+        let from: PathBuf = from.as_ref().to_path_buf();
+        self.forget_case_folded_path(&from);
+        self.forget_case_folded_path(&to);
+        let file: InMemoryFile = match self.remove_stored_file(&from) {
+            Some(file) => file,
+            None => return false,
+        };
+        if let Some(access_count) = self.access_count_map.remove(&from) {
+            self.access_count_map.insert(to.clone(), access_count);
+        }
+        if let Some(last_access) = self.last_access_map.remove(&from) {
+            self.last_access_map.insert(to.clone(), last_access);
+        }
+        self.store_file(to.clone(), file);
+        self.update_stats(&to);
+        true
+    }
+
+    /// Removes every file from the cache whose path starts with the given prefix.
+    ///
+    /// # Arguments
+    ///
+    /// * `prefix` - Files with paths beginning with this path will be removed. Only whole path components are matched.
+    /// * `clear_access_counts` - If true, the access counts of the removed files will be reset as well.
+    ///
+    /// # Return
+    ///
+    /// The number of files that were removed.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use rocket_file_cache::Cache;
+    /// use std::path::Path;
+    ///
+    /// let cache = Cache::new(1024 * 1024 * 10);
+    /// let removed: usize = cache.invalidate_prefix(Path::new("www/users/42/"), true);
+    /// assert_eq!(removed, 0);
+    /// ```
+    pub fn invalidate_prefix<P: AsRef<Path>>(&self, prefix: P, clear_access_counts: bool) -> usize {
+        let removed: usize = self.invalidate_where(|path| path.starts_with(prefix.as_ref()), clear_access_counts);
+        debug!("Invalidated {} files with the prefix: {:?}", removed, prefix.as_ref());
+        removed
+    }
+
+    /// Removes every file from the cache whose path satisfies the given predicate.
+    ///
+    /// Like `remove()`, this will not reset the access counts of the removed files.
+    ///
+    /// # Arguments
+    ///
+    /// * `predicate` - A function that returns true for the paths of files that should be removed.
+    ///
+    /// # Return
+    ///
+    /// The number of files that were removed.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use rocket_file_cache::Cache;
+    /// use std::path::Path;
+    /// use std::ffi::OsStr;
+    ///
+    /// let cache = Cache::new(1024 * 1024 * 10);
+    /// // Remove all stylesheets after a theme rebuild.
+    /// cache.invalidate_matching(|path: &Path| path.extension() == Some(OsStr::new("css")));
+    /// ```
+    pub fn invalidate_matching<F: Fn(&Path) -> bool>(&self, predicate: F) -> usize {
+        let removed: usize = self.invalidate_where(predicate, false);
+        debug!("Invalidated {} files matching a predicate", removed);
+        removed
+    }
+
+    /// Removes every file from the cache that hasn't been gotten within the given idle period.
+    ///
+    /// This frees memory during quiet periods, instead of holding files until they are displaced by new files.
+    /// The removed files count as evictions, and are given to the `on_evict` function if the cache has one.
+    /// Files that have never been gotten, such as preloaded files, are not considered idle, and pinned files are never removed.
+    ///
+    /// # Arguments
+    ///
+    /// * `idle` - The time since a file was last gotten after which it will be removed.
+    ///
+    /// # Return
+    ///
+    /// The number of files that were removed.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use rocket_file_cache::Cache;
+    /// use std::time::Duration;
+    ///
+    /// let cache = Cache::new(1024 * 1024 * 10);
+    /// assert_eq!(cache.evict_idle(Duration::from_secs(60 * 10)), 0);
+    /// ```
+    pub fn evict_idle(&self, idle: Duration) -> usize {
+        let now: Instant = Instant::now();
+        let keys: Vec<PathBuf> = self.file_map
+            .entries()
+            .into_iter()
+            .map(|x| x.0.clone())
+            .filter(|key| !self.is_pinned(key))
+            .filter(|key| match self.last_access(key) {
+                Some(last_access) => now.duration_since(last_access) > idle,
+                None => false,
+            })
+            .collect();
+
+        let evicted: usize = keys.iter().filter(|key| self.evict(key)).count();
+        if self.log_evictions {
+            debug!("Evicted {} files that were idle for longer than {:?}.", evicted, idle);
+        }
+        evicted
+    }
+
+    /// Removes the files that should be removed first, according to the eviction policy, until the cache
+    /// uses no more than the target number of bytes.
+    ///
+    /// This is intended to be called when the system signals that it is low on memory,
+    /// so the cache can give memory back without being cleared entirely.
+    /// Pinned files are never removed, so the cache may remain above the target.
+    /// The size limit is unchanged, so the cache will grow again as files are gotten.
+    ///
+    /// # Arguments
+    ///
+    /// * `target_bytes` - The number of bytes the cache should use at most.
+    ///
+    /// # Return
+    ///
+    /// The number of bytes that were freed.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use rocket_file_cache::Cache;
+    ///
+    /// let cache = Cache::new(1024 * 1024 * 10);
+    /// assert_eq!(cache.trim_to(1024 * 1024), 0);
+    /// ```
+    pub fn trim_to(&self, target_bytes: usize) -> usize {
+        let freed_bytes: usize = self.evict_down_to(target_bytes);
+        if self.log_evictions {
+            debug!("Freed {} bytes to trim the cache to {} bytes.", freed_bytes, target_bytes);
+        }
+        freed_bytes
+    }
+
+    /// Spawns a thread that removes idle files from the cache every interval, using `evict_idle()`.
+    ///
+    /// The thread only holds a weak reference to the cache, so it stops once the cache is dropped.
+    ///
+    /// # Arguments
+    ///
+    /// * `cache` - The cache to remove idle files from.
+    /// * `idle` - The time since a file was last gotten after which it will be removed.
+    /// * `interval` - The time between checks for idle files.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use rocket_file_cache::Cache;
+    /// use std::sync::Arc;
+    /// use std::time::Duration;
+    ///
+    /// let cache = Arc::new(Cache::new(1024 * 1024 * 10));
+    /// Cache::evict_idle_periodically(&cache, Duration::from_secs(60 * 10), Duration::from_secs(60));
+    /// ```
+    pub fn evict_idle_periodically(cache: &Arc<Cache<B>>, idle: Duration, interval: Duration) -> JoinHandle<()>
+        where B: 'static
+    {
+        let cache: Weak<Cache<B>> = Arc::downgrade(cache);
+        thread::spawn(move || loop {
+            thread::sleep(interval);
+            match cache.upgrade() {
+                Some(cache) => {
+                    cache.evict_idle(idle);
+                }
+                None => return,
+            }
+        })
+    }
+
+    /// Starts a thread that stores files in the cache, so requests for files that aren't in the cache
+    /// don't wait for the file to be read into memory or for other files to be evicted.
+    ///
+    /// Once the thread is running, getting a file that isn't in the cache streams it from the filesystem
+    /// and queues its path. The thread reads queued files and stores them, evicting other files if needed,
+    /// the same way `get()` would have.
+    /// At most `queue_size` paths can be waiting to be stored; if the queue is full, the path is dropped
+    /// and the file will be queued again the next time it is gotten.
+    ///
+    /// The thread only holds a weak reference to the cache, and stops once the cache is dropped.
+    /// Starting another background evictor replaces the queue of the previous one, which then stops.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use rocket_file_cache::{Cache, CacheBuilder};
+    /// use std::sync::Arc;
+    ///
+    /// let cache: Arc<Cache> = Arc::new(CacheBuilder::new().size_limit(1024 * 1024 * 10).build().unwrap());
+    /// Cache::spawn_background_evictor(&cache, 64);
+    /// ```
+    pub fn spawn_background_evictor(cache: &Arc<Cache<B>>, queue_size: usize) -> JoinHandle<()>
+        where B: 'static
+    {
+        let (sender, receiver) = mpsc::sync_channel::<PathBuf>(queue_size);
+        match cache.background_inserts.lock() {
+            Ok(mut background_inserts) => *background_inserts = Some(sender),
+            Err(poisoned) => *poisoned.into_inner() = Some(sender),
+        }
+
+        let cache: Weak<Cache<B>> = Arc::downgrade(cache);
+        thread::spawn(move || {
+            for path in receiver.iter() {
+                match cache.upgrade() {
+                    Some(cache) => {
+                        if !cache.contains_key(&path) {
+                            let _ = cache.try_insert_once(&path);
+                        }
+                    }
+                    None => return,
+                }
+            }
+        })
+    }
+
+    /// Starts a thread that refreshes files in the cache, so requests for files that are due to be refreshed
+    /// don't wait for the file to be read again.
+    ///
+    /// Once the thread is running, getting a file whose access count reaches a multiple of `accesses_per_refresh`
+    /// serves the file that is currently cached, and queues its path. The thread then refreshes the file
+    /// using `refresh()`, so later gets serve its new contents.
+    /// At most `queue_size` paths can be waiting to be refreshed; if the queue is full, the file is refreshed
+    /// before it is served, as if there were no background refresher.
+    ///
+    /// The thread only holds a weak reference to the cache, and stops once the cache is dropped.
+    /// Starting another background refresher replaces the queue of the previous one, which then stops.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use rocket_file_cache::{Cache, CacheBuilder};
+    /// use std::sync::Arc;
+    ///
+    /// let cache: Arc<Cache> = Arc::new(CacheBuilder::new().accesses_per_refresh(100).build().unwrap());
+    /// Cache::spawn_background_refresher(&cache, 64);
+    /// ```
+    pub fn spawn_background_refresher(cache: &Arc<Cache<B>>, queue_size: usize) -> JoinHandle<()>
+        where B: 'static
+    {
+        let (sender, receiver) = mpsc::sync_channel::<PathBuf>(queue_size);
+        match cache.background_refreshes.lock() {
+            Ok(mut background_refreshes) => *background_refreshes = Some(sender),
+            Err(poisoned) => *poisoned.into_inner() = Some(sender),
+        }
+
+        let cache: Weak<Cache<B>> = Arc::downgrade(cache);
+        thread::spawn(move || {
+            for path in receiver.iter() {
+                match cache.upgrade() {
+                    Some(cache) => {
+                        let _ = cache.refresh(&path);
+                    }
+                    None => return,
+                }
+            }
+        })
+    }
+
+    /// Queues the path to be refreshed by the background refresher, if one is running.
+    ///
+    /// Returns false if the path wasn't queued, and the file should be refreshed by the caller.
+    fn send_to_background_refresher(&self, path: &PathBuf) -> bool {
+        let background_refreshes = match self.background_refreshes.lock() {
+            Ok(background_refreshes) => background_refreshes,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        match *background_refreshes {
+            Some(ref sender) => sender.try_send(path.clone()).is_ok(),
+            None => false,
+        }
+    }
+
+    /// If a background evictor is running, streams the file from the filesystem and queues its path
+    /// to be stored in the cache by the background evictor.
+    ///
+    /// Returns `None` if there is no background evictor, and the file should be stored by the caller.
+    fn send_to_background_evictor(&self, path: &PathBuf) -> Option<CachedFile<'static>> {
+        let background_inserts = match self.background_inserts.lock() {
+            Ok(background_inserts) => background_inserts,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        let sender: &mpsc::SyncSender<PathBuf> = match *background_inserts {
+            Some(ref sender) => sender,
+            None => return None,
+        };
+
+        let cached_file: CachedFile<'static> = self.open_streamed_file(path);
+        if cached_file == CachedFile::NotFound {
+            return Some(cached_file);
+        }
+        match sender.try_send(path.clone()) {
+            Ok(()) => Some(cached_file),
+            Err(mpsc::TrySendError::Full(_)) => {
+                debug!("The background evictor's queue is full, {:?} will not be stored.", path);
+                // The access still counts towards the file's priority when it is next queued.
+                self.increment_access_count(path);
+                Some(cached_file)
+            }
+            // The background evictor has stopped, so the caller should store the file itself.
+            Err(mpsc::TrySendError::Disconnected(_)) => None,
+        }
+    }
+
+    /// Helper function that removes the files whose paths satisfy the predicate, returning the number removed.
+    fn invalidate_where<F: Fn(&Path) -> bool>(&self, predicate: F, clear_access_counts: bool) -> usize {
+        let keys: Vec<PathBuf> = self.file_map
+            .entries()
+            .into_iter()
+            .map(|x| x.0)
+            .filter(|key| predicate(key.as_path()))
+            .collect();
+
+        let mut removed: usize = 0;
+        for key in keys {
+            if self.remove_stored_file(&key).is_some() {
+                removed += 1;
+            }
+            if clear_access_counts {
+                self.access_count_map.remove(&key);
+                self.last_access_map.remove(&key);
+            }
+        }
+
+        // Files that were matched ignoring case may not be cached, so their matches are checked separately.
+        let folded_paths: Vec<PathBuf> = self.case_folded_paths
+            .iter()
+            .filter(|x| predicate(x.1.as_path()))
+            .map(|x| x.0.clone())
+            .collect();
+        for folded in folded_paths {
+            self.case_folded_paths.remove(&folded);
+        }
+        removed
+    }
+
+    /// Removes every file from the cache, returning them along with their paths.
+    ///
+    /// The files are moved out of the cache, not copied, so they can be handed to another cache
+    /// using `extend()` without reading them from the filesystem again.
+    /// Access counts are not reset, but the stats of each file retain the access count it had.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use rocket_file_cache::Cache;
+    ///
+    /// let old_cache = Cache::new(1024 * 1024 * 10);
+    /// let new_cache = Cache::new(1024 * 1024 * 20);
+    /// new_cache.extend(old_cache.drain());
+    /// assert_eq!(old_cache.used_bytes(), 0);
+    /// ```
+    pub fn drain(&self) -> Vec<(PathBuf, InMemoryFile)> {
+        let keys: Vec<PathBuf> = self.file_map
+            .entries()
+            .into_iter()
+            .map(|x| x.0)
+            .collect();
+        keys.into_iter()
+            .filter_map(|key| {
+                self.remove_stored_file(&key).map(|file| (key, file))
+            })
+            .collect()
+    }
+
+    /// Inserts the provided files into the cache.
+    ///
+    /// This is intended to be used with the files returned from `drain()` when moving files between caches.
+    /// The files bypass the size and priority checks that normally determine if a file can be stored,
+    /// so the caller is responsible for making sure they fit.
+    /// Any file without a recorded access count in this cache will use the access count stored in its stats.
+    ///
+    /// # Arguments
+    ///
+    /// * `files` - The paths and in-memory files to store in the cache.
+    pub fn extend<I: IntoIterator<Item = (PathBuf, InMemoryFile)>>(&self, files: I) {
+        for (path, file) in files {
+            if self.access_count_map.find(&path).is_none() {
+                self.access_count_map.insert(path.clone(), file.stats.access_count);
+            }
+            self.store_file(path.clone(), file);
+            self.update_stats(&path);
+        }
+    }
+
+    /// Writes the contents of the most accessed files in the cache to a single dump,
+    /// so another cache can be warmed with them using `warm_from_dump()`.
+    ///
+    /// This is intended for keeping a standby server's cache warm, by periodically dumping the active
+    /// server's hottest files to shared storage.
+    /// Files are written from most to least accessed, along with their paths.
+    ///
+    /// # Arguments
+    ///
+    /// * `n` - The maximum number of files to write.
+    /// * `writer` - Where the dump is written.
+    ///
+    /// # Return
+    ///
+    /// The number of files that were written.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use rocket_file_cache::Cache;
+    ///
+    /// let cache = Cache::new(1024 * 1024 * 10);
+    /// let mut dump: Vec<u8> = Vec::new();
+    /// cache.dump_hottest(100, &mut dump).unwrap();
+    ///
+    /// let standby = Cache::new(1024 * 1024 * 10);
+    /// standby.warm_from_dump(dump.as_slice()).unwrap();
+    /// ```
+    pub fn dump_hottest<W: Write>(&self, n: usize, mut writer: W) -> io::Result<usize> {
+        let mut files: Vec<(PathBuf, usize, Arc<Vec<u8>>)> = self.file_map
+            .entries()
+            .into_iter()
+            .map(|x| {
+                let access_count: usize = match self.access_count_map.find(&x.0) {
+                    Some(access_count) => access_count.get().clone(),
+                    None => x.1.stats.access_count,
+                };
+                (x.0, access_count, x.1.bytes)
+            })
+            .collect();
+        files.sort_by(|l, r| r.1.cmp(&l.1).then_with(|| l.0.cmp(&r.0)));
+        files.truncate(n);
+
+        dump::write_header(&mut writer)?;
+        for &(ref path, _, ref bytes) in files.iter() {
+            dump::write_entry(&mut writer, path, bytes)?;
+        }
+        writer.flush()?;
+        Ok(files.len())
+    }
+
+    /// Stores the files in a dump written by `dump_hottest()`, without reading them from the filesystem.
+    ///
+    /// Files are stored in the order they were dumped, from most to least accessed, as long as they fit
+    /// within the usable size limit and would have been stored if they were read from the filesystem.
+    /// Files that are already cached are replaced.
+    ///
+    /// # Arguments
+    ///
+    /// * `reader` - The dump to read.
+    ///
+    /// # Return
+    ///
+    /// The number of files that were stored, or an error if the dump couldn't be read.
+    pub fn warm_from_dump<R: Read>(&self, mut reader: R) -> io::Result<usize> {
+        dump::read_header(&mut reader)?;
+        let mut stored: usize = 0;
+        while let Some((path, bytes)) = dump::read_entry(&mut reader)? {
+            let file: InMemoryFile = InMemoryFile::from_bytes(bytes);
+            let size: usize = file.stats.size;
+            if size > self.max_cached_file_size() || size < self.min_file_size || !self.fits_in_size_limit(size)
+                || !self.is_allowed_to_cache(&path, size) || !self.has_expected_checksum(&path, &file) {
+                debug!("{:?} from the dump is not allowed in the cache.", path);
+                continue;
+            }
+            let replaced_size: usize = self.file_map.get(&path).map_or(0, |existing| existing.stats.size);
+            if self.used_bytes().saturating_sub(replaced_size) + size > self.usable_size_limit() {
+                debug!("{:?} from the dump does not fit in the cache.", path);
+                continue;
+            }
+            self.extend(vec![(path, file)]);
+            stored += 1;
+        }
+        Ok(stored)
+    }
+
+    /// Offers content to the cache as if it had been read from the filesystem by a call to `get()`,
+    /// so the content is only stored if `get()` would have stored a file of the same size.
+    ///
+    /// The offer counts as an access of the path, and as a hit or a miss, just like `get()`.
+    /// If the path is already in the cache, the cached content is kept.
+    /// Otherwise, lower priority files are removed to make room for the content, if its priority is high enough.
+    ///
+    /// Nothing is read from the filesystem, so this allows the cache's admission and eviction decisions
+    /// to be tested and benchmarked without the noise of disk reads. Combined with `reseed()` or
+    /// `CacheBuilder::eviction_seed()`, the decisions are the same every time.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path to store the content under.
+    /// * `bytes` - The content.
+    ///
+    /// # Return
+    ///
+    /// True if the content is in the cache once the offer has been made.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use rocket_file_cache::Cache;
+    ///
+    /// let cache = Cache::new(1024);
+    /// assert!(cache.offer("a.txt", vec![0u8; 1024]));
+    /// assert!(cache.offer("a.txt", vec![0u8; 1024]));
+    /// // The new content has been accessed less often than the cached file, so it isn't stored.
+    /// assert!(!cache.offer("b.txt", vec![0u8; 1024]));
+    /// assert!(cache.contains_key("a.txt"));
+    /// ```
+    pub fn offer<P: AsRef<Path>>(&self, path: P, bytes: Vec<u8>) -> bool {
+        let path: PathBuf = path.as_ref().to_path_buf();
+        if self.contains_key(&path) {
+            self.hits.fetch_add(1, Ordering::Relaxed);
+            self.increment_access_count(&path);
+            self.update_stats(&path);
+            return true;
+        }
+        self.misses.fetch_add(1, Ordering::Relaxed);
+        self.increment_access_count(&path);
+
+        let file: InMemoryFile = InMemoryFile::from_bytes(bytes);
+        let size: usize = file.stats.size;
+        if size > self.max_cached_file_size() || size < self.min_file_size || !self.fits_in_size_limit(size) || !self.is_allowed_to_cache(&path, size) {
+            return false;
+        }
+
+        let used_bytes: usize = self.used_bytes();
+        let required_space: usize = cmp::min(used_bytes.saturating_add(size).saturating_sub(self.usable_size_limit()), used_bytes);
+        if required_space > 0 {
+            let access_count: usize = self.access_count_map.find(&path).map_or(1, |access_count| *access_count.get());
+            let priority: usize = self.priority_for(&path, access_count, size);
+            match self.make_room_for_new_file(&path, required_space, priority) {
+                Ok(files_to_be_removed) => self.evict_to_make_room(files_to_be_removed, required_space),
+                Err(_) => return false,
+            }
+        }
+
+        self.store_file(path.clone(), file);
+        self.update_stats(&path);
+        true
+    }
+
+    /// Reseeds the random number generator used by the `EvictionPolicy::WeightedRandom` policy.
+    ///
+    /// After reseeding with the same seed, the same sequence of gets and offers will remove the same files,
+    /// which makes tests and benchmarks of the policy reproducible.
+    pub fn reseed(&self, seed: u64) {
+        let mut state = match self.random_state.lock() {
+            Ok(guard) => guard,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        *state = seed;
+    }
+
+    /// Stores a file in the cache regardless of its priority.
+    ///
+    /// The lowest priority files are removed until the new file fits within the size limit, even if they are,
+    /// in aggregate, more important than the new file.
+    /// If a file is already stored at the path, it is replaced.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path to store the file under.
+    /// * `file` - The in-memory file to store.
+    ///
+    /// # Return
+    ///
+    /// An error if the file is larger than the size limit of the cache, or if it wouldn't fit without
+    /// removing pinned files, in which case no files are removed.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use rocket_file_cache::{Cache, InMemoryFile};
+    /// use std::path::PathBuf;
+    ///
+    /// let cache = Cache::new(1024 * 1024 * 30);
+    /// if let Ok(file) = InMemoryFile::open("www/index.html") {
+    ///     cache.force_store(PathBuf::from("www/index.html"), file).unwrap();
+    ///     assert!(cache.contains_key("www/index.html"));
+    /// }
+    /// ```
+    pub fn force_store(&self, path: PathBuf, file: InMemoryFile) -> Result<(), CacheInvalidationError> {
+        let size: usize = file.stats.size;
+        if !self.fits_in_size_limit(size) {
+            return Err(CacheInvalidationError::FileIsLargerThanSizeLimit);
+        }
+        // The file already stored at the path is replaced, so its space doesn't need to be freed.
+        let replaced_size: usize = self.file_map.get(&path).map_or(0, |existing| existing.stats.size);
+        let required_space: usize = (self.used_bytes().saturating_sub(replaced_size) + size).saturating_sub(self.size_limit());
+        // No combination of files can have a priority greater than usize::MAX, so the priority check never prevents removal.
+        match self.make_room_for_new_file(&path, required_space, usize::MAX) {
+            Ok(files_to_be_removed) => {
+                self.evict_to_make_room(files_to_be_removed, required_space);
+            }
+            Err(_) => {
+                warn!("Not enough unpinned files could be removed to make room for {:?}.", path);
+                return Err(CacheInvalidationError::PinnedFilesLeaveTooLittleSpace);
+            }
+        }
+
+        self.store_file(path.clone(), file);
+        self.update_stats(&path);
+        Ok(())
+    }
+
+    /// Replaces every file in the cache with the provided files, returning the files that were replaced.
+    ///
+    /// This requires exclusive access to the cache, so no request can see the cache while it only holds
+    /// some of the new files. To swap the contents of a cache that is serving requests, hold the cache
+    /// in a `RwLock`, so the write lock can be taken for the duration of the swap.
+    ///
+    /// Like `extend()`, the files bypass the size and priority checks that normally determine if a file can be stored.
+    ///
+    /// # Arguments
+    ///
+    /// * `files` - The paths and in-memory files the cache will hold.
+    /// * `keep_access_counts` - If true, the access counts of every path are kept, otherwise they are
+    /// reset to the access counts stored in the stats of the new files.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use rocket_file_cache::{Cache, InMemoryFile};
+    /// use std::collections::HashMap;
+    /// use std::path::PathBuf;
+    /// use std::sync::RwLock;
+    ///
+    /// let cache = RwLock::new(Cache::new(1024 * 1024 * 30));
+    /// let mut files: HashMap<PathBuf, InMemoryFile> = HashMap::new();
+    /// if let Ok(file) = InMemoryFile::open("www/index.html") {
+    ///     files.insert(PathBuf::from("www/index.html"), file);
+    /// }
+    /// cache.write().unwrap().replace_contents(files, false);
+    /// ```
+    pub fn replace_contents(&mut self, files: HashMap<PathBuf, InMemoryFile>, keep_access_counts: bool) -> Vec<(PathBuf, InMemoryFile)> {
+        let replaced: Vec<(PathBuf, InMemoryFile)> = self.drain();
+        if !keep_access_counts {
+            let paths: Vec<PathBuf> = self.access_count_map.iter().map(|x| x.0.clone()).collect();
+            for path in paths {
+                self.access_count_map.remove(&path);
+                self.last_access_map.remove(&path);
+            }
+        }
+        self.extend(files);
+        replaced
+    }
+
+    /// Reads the files in a directory, and its subdirectories, into the cache.
+    ///
+    /// Files are stored from highest to lowest priority, as determined by the priority function using
+    /// their access counts and sizes.
+    /// Files that don't fit the size constraints of the cache, or the space the cache has left, are skipped,
+    /// so preloading will never remove other files from the cache.
+    ///
+    /// # Arguments
+    ///
+    /// * `dir` - The directory containing the files to preload.
+    ///
+    /// # Return
+    ///
+    /// The number of files that were stored, or an error if the directory couldn't be read.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use rocket_file_cache::Cache;
+    ///
+    /// let cache = Cache::new(1024 * 1024 * 10);
+    /// let _ = cache.preload_dir("www/");
+    /// ```
+    pub fn preload_dir<P: AsRef<Path>>(&self, dir: P) -> io::Result<usize> {
+        let mut stored: usize = 0;
+        for path in self.plan_preload(dir)? {
+            if let Ok(file) = self.read_file(&path) {
+                if self.store_preloaded_file(path, file) {
+                    stored += 1;
+                }
+            }
+        }
+        Ok(stored)
+    }
+
+    /// Reads the files in a directory, and its subdirectories, into the cache using multiple threads.
+    ///
+    /// The same files are stored as would be by `preload_dir()`, but they are read from the filesystem
+    /// concurrently by the given number of threads.
+    /// Only files that will fit in the cache are read, so the memory used while preloading is bounded by the cache's size limit.
+    /// Once read, the files are stored from highest to lowest priority by the calling thread.
+    ///
+    /// # Arguments
+    ///
+    /// * `dir` - The directory containing the files to preload.
+    /// * `threads` - The number of threads used to read the files.
+    ///
+    /// # Return
+    ///
+    /// The number of files that were stored, or an error if the directory couldn't be read.
+    pub fn preload_dir_parallel<P: AsRef<Path>>(&self, dir: P, threads: usize) -> io::Result<usize> {
+        let paths: Vec<PathBuf> = self.plan_preload(dir)?;
+        let threads: usize = cmp::min(cmp::max(threads, 1), paths.len());
+
+        // The paths are popped off of the end of the queue, so the highest priority files should be read first.
+        let queue: Arc<Mutex<Vec<PathBuf>>> = Arc::new(Mutex::new(paths.iter().rev().cloned().collect()));
+        let (sender, receiver) = mpsc::channel::<(PathBuf, io::Result<InMemoryFile>)>();
+        let release_page_cache: bool = self.release_page_cache;
+        let handles: Vec<JoinHandle<()>> = (0..threads)
+            .map(|_| {
+                let queue = queue.clone();
+                let sender = sender.clone();
+                thread::spawn(move || loop {
+                    let path: PathBuf = match queue.lock() {
+                        Ok(mut queue) => match queue.pop() {
+                            Some(path) => path,
+                            None => return,
+                        },
+                        Err(_) => return,
+                    };
+                    let file: io::Result<InMemoryFile> = Self::open_file(&path, release_page_cache);
+                    if sender.send((path, file)).is_err() {
+                        return;
+                    }
+                })
+            })
+            .collect();
+        drop(sender); // Only the reading threads should keep the channel open.
+
+        let mut files: HashMap<PathBuf, InMemoryFile> = HashMap::new();
+        for (path, file) in receiver {
+            self.files_read.fetch_add(1, Ordering::Relaxed);
+            if let Ok(file) = file {
+                files.insert(path, file);
+            }
+        }
+        for handle in handles {
+            let _ = handle.join();
+        }
+
+        let mut stored: usize = 0;
+        for path in paths {
+            if let Some(file) = files.remove(&path) {
+                if self.store_preloaded_file(path, file) {
+                    stored += 1;
+                }
+            }
+        }
+        Ok(stored)
+    }
+
+    /// Returns a boolean indicating if the cache has an entry corresponding to the given key.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - A path that is used as a key to look up the file.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use rocket_file_cache::Cache;
+    /// use std::path::PathBuf;
+    ///
+    /// let mut cache = Cache::new(1024 * 1024 * 20);
+    /// let pathbuf: PathBuf = PathBuf::new();
+    /// cache.get(&pathbuf);
+    /// assert!(cache.contains_key(&pathbuf) == false);
+    /// ```
+    pub fn contains_key<P: AsRef<Path>>(&self, path: P) -> bool {
+        self.file_map.get(path.as_ref()).is_some()
+    }
+
+    /// Alters the access count value of one file in the access_count_map.
+    /// # Arguments
+    ///
+    /// * `path` - The key to look up the file.
+    /// * `alter_count_function` - A function that determines how to alter the access_count for the file.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use rocket_file_cache::Cache;
+    /// use std::path::PathBuf;
+    ///
+    /// let mut cache = Cache::new(1024 * 1024 * 10);
+    /// let pathbuf = PathBuf::new();
+    /// cache.get(&pathbuf); // Add a file to the cache
+    /// cache.remove(&pathbuf); // Removing the file will not reset its access count.
+    /// cache.alter_access_count(&pathbuf, | x | { 0 }); // Set the access count to 0.
+    /// ```
+    ///
+    pub fn alter_access_count<P: AsRef<Path>>(&self, path: P, alter_count_function: fn(&usize) -> usize) -> bool {
+        let new_count: usize;
+        {
+            match self.access_count_map.find(&path.as_ref().to_path_buf()) {
+                Some(access_count_entry) => {
+                    new_count = alter_count_function(&access_count_entry.get());
+                }
+                None => return false, // Can't update a file that isn't in the cache.
+            }
+        }
+        {
+            self.access_count_map.insert(
+                path.as_ref().to_path_buf(),
+                new_count,
+            );
+        }
+        self.update_stats(&path);
+        return true;
+    }
+
+    /// Increases the access count of a file by the given number of times without getting the file.
+    ///
+    /// This can be used to raise the priority of a file that is expected to be requested often,
+    /// so it will be favored when it is stored, or survive the removal of other files if it is already cached.
+    /// The file does not need to be in the cache.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The key to look up the file.
+    /// * `times` - The number of accesses to add to the file's access count.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use rocket_file_cache::Cache;
+    /// use std::path::PathBuf;
+    ///
+    /// let cache = Cache::new(1024 * 1024 * 10);
+    /// cache.touch(&PathBuf::from("www/index.html"), 100);
+    /// ```
+    pub fn touch<P: AsRef<Path>>(&self, path: P, times: usize) {
+        self.access_count_map.upsert(
+            path.as_ref().to_path_buf(),
+            times,
+            &|access_count| {
+                *access_count = access_count.saturating_add(times);
+            },
+        );
+        // Updating the stats of a file that isn't in the cache would add an empty entry for it.
+        if self.contains_key(&path) {
+            self.update_stats(&path);
+        }
+    }
+
+    /// Alters the access count value of every file in the access_count_map.
+    /// This is useful for manually aging-out entries in the cache.
+    ///
+    /// # Arguments
+    ///
+    /// * `alter_count_function` - A function that determines how to alter the access_count for the file.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use rocket_file_cache::Cache;
+    /// use std::path::PathBuf;
+    ///
+    /// let mut cache = Cache::new(1024 * 1024 * 10);
+    /// let pathbuf = PathBuf::new();
+    /// let other_pathbuf = PathBuf::new();
+    /// cache.get(&pathbuf);
+    /// cache.get(&other_pathbuf);
+    /// // Reduce all access counts by half,
+    /// // allowing newer files to enter the cache more easily.
+    /// cache.alter_all_access_counts(| x | { x / 2 });
+    /// ```
+    ///
+    pub fn alter_all_access_counts(&self, alter_count_function: fn(&usize) -> usize) {
+        let all_counts: Vec<PathBuf>;
+        {
+            all_counts = self.access_count_map
+                .iter()
+                .map(|x: (&PathBuf, &usize)| x.0.clone())
+                .collect();
+        }
+        for pathbuf in all_counts {
+            self.alter_access_count(&pathbuf, alter_count_function);
+        }
+
+    }
+
+    /// Gets the sum of the sizes of the files that are stored in the cache.
+    ///
+    /// The compressed variants of files served by `PrecompressedFile` are stored in the cache as
+    /// separate sidecar files, so their sizes are included alongside the sizes of the uncompressed files.
+    /// This is the same number of bytes that is compared against the `size_limit` when deciding if files must be evicted,
+    /// so the size limit caps the memory used by both representations of a file.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use rocket_file_cache::Cache;
+    ///
+    /// let cache = Cache::new(1024 * 1024 * 30);
+    /// assert!(cache.used_bytes() == 0);
+    /// ```
+    pub fn used_bytes(&self) -> usize {
+        self.stored_bytes.load(Ordering::SeqCst)
+    }
+
+    /// Estimates the number of bytes the cache uses beyond the contents of its files.
+    ///
+    /// This includes the keys and entries of the maps that hold the files and their access counts,
+    /// the reference counts held alongside each file's bytes, and each file's ETag.
+    /// Unused capacity in the maps can't be measured, so the real overhead will be somewhat larger.
+    /// Adding this to `used_bytes()` approximates how much memory the cache occupies.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use rocket_file_cache::Cache;
+    /// let cache = Cache::new(1024 * 1024 * 30);
+    /// assert_eq!(cache.memory_overhead_estimate(), 0);
+    /// ```
+    pub fn memory_overhead_estimate(&self) -> usize {
+        use std::mem::size_of;
+
+        let file_overhead: usize = self.file_map.entries().into_iter().fold(0usize, |overhead, (path, file)| {
+            overhead
+                + size_of::<PathBuf>() + path.as_os_str().len()
+                + size_of::<InMemoryFile>() + file.etag.capacity()
+                + 2 * size_of::<usize>() // The strong and weak counts of the Arc holding the bytes.
+        });
+        let access_count_overhead: usize = self.access_count_map.iter().fold(0usize, |overhead, (path, _)| {
+            overhead + size_of::<PathBuf>() + path.as_os_str().len() + size_of::<usize>()
+        });
+        file_overhead + access_count_overhead
+    }
+
+    /// Registers the checksum that a file must have in order to be stored in the cache.
+    ///
+    /// When the file is read from the filesystem, the `checksum()` of its bytes will be compared against
+    /// the expected checksum.
+    /// If they don't match, the file is assumed to be corrupt, it will not be stored in the cache,
+    /// and `get()` will return `CachedFile::NotFound` for it.
+    ///
+    /// Files that are streamed from the filesystem instead of being stored are returned as
+    /// `CachedFile::Verified`, which calculates the checksum as the file is streamed.
+    /// What happens when a streamed file doesn't match is determined by the cache's `on_checksum_mismatch`.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - A path that is used as a key to look up the file.
+    /// * `expected_checksum` - The FNV-1a checksum the file's contents are expected to have.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use rocket_file_cache::{Cache, checksum};
+    /// use std::path::PathBuf;
+    ///
+    /// let cache = Cache::new(1024 * 1024 * 30);
+    /// cache.set_expected_checksum(PathBuf::from("www/test.txt"), checksum(b"Hello World!"));
+    /// ```
+    pub fn set_expected_checksum<P: AsRef<Path>>(&self, path: P, expected_checksum: u64) {
+        self.expected_checksums.insert(path.as_ref().to_path_buf(), expected_checksum);
+    }
+
+    /// Sets a multiplier that is applied to the priority scores of files with the given extension.
+    ///
+    /// This allows files of some types to be preferred over others with the same access counts and sizes,
+    /// without writing a custom priority function.
+    /// Files whose extensions don't have a weight use their priority scores unchanged.
+    ///
+    /// # Arguments
+    ///
+    /// * `extension` - The extension of the files, without a leading period. Extensions are matched case-insensitively.
+    /// * `weight` - The multiplier applied to the priority scores of the files.
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if the weight is negative.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use rocket_file_cache::Cache;
+    ///
+    /// let mut cache = Cache::new(1024 * 1024 * 30);
+    /// cache.set_extension_weight("png", 2.0); // Prefer images...
+    /// cache.set_extension_weight("log", 0.5); // ...over logs.
+    /// ```
+    pub fn set_extension_weight(&mut self, extension: &str, weight: f64) {
+        if weight < 0.0 {
+            panic!("Incorrectly configured extension weight. Values must not be negative.");
+        }
+        self.extension_weights.insert(extension.trim_start_matches('.').to_lowercase(), weight);
+    }
+
+    /// Pins the file at the given path, so it will never be removed to make room for other files,
+    /// regardless of its priority.
+    ///
+    /// The file isn't read into the cache when it is pinned, but once it is stored, it will stay in the cache
+    /// until it is unpinned or explicitly removed.
+    /// Pinned files still count towards the `size_limit`, so pinning too many files will keep other files out of the cache.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - A path that is used as a key to look up the file.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use rocket_file_cache::Cache;
+    /// use std::path::PathBuf;
+    ///
+    /// let cache = Cache::new(1024 * 1024 * 30);
+    /// cache.pin(PathBuf::from("www/login.html"));
+    /// assert!(cache.is_pinned(PathBuf::from("www/login.html")));
+    /// ```
+    pub fn pin<P: AsRef<Path>>(&self, path: P) {
+        self.pinned.insert(path.as_ref().to_path_buf(), ());
+    }
+
+    /// Unpins the file at the given path, so it can be removed to make room for other files again.
+    ///
+    /// Returns true if the file was pinned.
+    pub fn unpin<P: AsRef<Path>>(&self, path: P) -> bool {
+        self.pinned.remove(&path.as_ref().to_path_buf()).is_some()
+    }
+
+    /// Determines if the file at the given path is pinned.
+    pub fn is_pinned<P: AsRef<Path>>(&self, path: P) -> bool {
+        self.pinned.find(&path.as_ref().to_path_buf()).is_some()
+    }
+
+    /// Registers headers that will be set on the responses of the file at the given path.
+    ///
+    /// These headers are applied after the headers the cache sets itself, so they will replace
+    /// headers with the same name, including the Content-Type implied by the file's extension.
+    /// They also replace any of the cache's `headers` with the same name.
+    /// Registering headers for a path replaces the headers previously registered for it.
+    ///
+    /// Headers are only set when the file is served from the cache.
+    /// Files that are streamed from the filesystem are served by Rocket's `NamedFile`, which doesn't set them.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - A path that is used as a key to look up the file.
+    /// * `headers` - The headers to set on the file's responses.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # extern crate rocket;
+    /// # extern crate rocket_file_cache;
+    /// # fn main() {
+    /// use rocket_file_cache::Cache;
+    /// use rocket::http::Header;
+    ///
+    /// let cache = Cache::new(1024 * 1024 * 30);
+    /// cache.set_headers("www/fonts/font.woff2", vec![Header::new("Access-Control-Allow-Origin", "*")]);
+    /// # }
+    /// ```
+    #[cfg(feature = "rocket")]
+    pub fn set_headers<P: AsRef<Path>>(&self, path: P, headers: Vec<Header<'static>>) {
+        self.path_headers.insert(path.as_ref().to_path_buf(), headers);
+    }
+
+    /// Registers a file as the variant of a logical path in the given language,
+    /// so `LocalizedFile` can serve the variant that best matches a request's Accept-Language header.
+    ///
+    /// Variants are chosen in the order they were registered when the client prefers them equally,
+    /// and the first variant is served to clients that accept none of them.
+    /// Registering a variant for a language that already has one replaces its path, but keeps its place in the order.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The logical path that `LocalizedFile` is opened with.
+    /// * `language` - The language tag of the variant, such as `en` or `fr-CA`.
+    /// * `variant` - The path of the file that holds the variant.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use rocket_file_cache::Cache;
+    ///
+    /// let cache = Cache::new(1024 * 1024 * 30);
+    /// cache.add_language_variant("www/index.html", "en", "www/index.en.html");
+    /// cache.add_language_variant("www/index.html", "fr", "www/index.fr.html");
+    /// ```
+    #[cfg(feature = "rocket")]
+    pub fn add_language_variant<P: AsRef<Path>, Q: AsRef<Path>>(&self, path: P, language: &str, variant: Q) {
+        let variant: (String, PathBuf) = (language.to_string(), variant.as_ref().to_path_buf());
+        self.language_variants.upsert(
+            path.as_ref().to_path_buf(),
+            vec![variant.clone()],
+            &|variants| {
+                match variants.iter().position(|existing| existing.0.eq_ignore_ascii_case(&variant.0)) {
+                    Some(index) => variants[index] = variant.clone(),
+                    None => variants.push(variant.clone()),
+                }
+            },
+        );
+    }
+
+    /// Gets how long a cached file has until it outlives the cache's `ttl`, after which it will be
+    /// refreshed the next time it is gotten.
+    ///
+    /// Returns `None` if the file isn't in the cache, or if the cache doesn't have a `ttl`.
+    /// Files that have already outlived the `ttl` have zero time remaining.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - A path that is used as a key to look up the file.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use rocket_file_cache::Cache;
+    /// use std::path::PathBuf;
+    ///
+    /// let cache = Cache::new(1024 * 1024 * 30);
+    /// assert_eq!(cache.ttl_remaining(&PathBuf::from("www/index.html")), None);
+    /// ```
+    pub fn ttl_remaining<P: AsRef<Path>>(&self, path: P) -> Option<Duration> {
+        let ttl: Duration = match self.ttl {
+            Some(ttl) => ttl,
+            None => return None,
+        };
+        self.file_map
+            .get(path.as_ref())
+            .map(|file| {
+                let age: Duration = file.read_at.elapsed();
+                if age >= ttl { Duration::from_secs(0) } else { ttl - age }
+            })
+    }
+
+    /// Gets the number of times a file has been accessed, whether or not it is in the cache.
+    ///
+    /// This is the access count the cache's priority function is given when deciding which files to store or remove.
+    /// Returns 0 if the file has never been accessed.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - A path that is used as a key to look up the access count.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use rocket_file_cache::Cache;
+    /// use std::path::PathBuf;
+    ///
+    /// let cache = Cache::new(1024 * 1024 * 30);
+    /// cache.touch(&PathBuf::from("www/index.html"), 3);
+    /// assert_eq!(cache.access_count(&PathBuf::from("www/index.html")), 3);
+    /// ```
+    pub fn access_count<P: AsRef<Path>>(&self, path: P) -> usize {
+        match self.access_count_map.find(&path.as_ref().to_path_buf()) {
+            Some(access_count) => access_count.get().clone(),
+            None => 0,
+        }
+    }
+
+    /// Gets the priority score of a file in the cache, calculated using the cache's priority function
+    /// with the file's size and current access count.
+    ///
+    /// Returns `None` if the file isn't in the cache.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - A path that is used as a key to look up the file.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use rocket_file_cache::Cache;
+    /// use std::path::PathBuf;
+    ///
+    /// let cache = Cache::new(1024 * 1024 * 30);
+    /// assert_eq!(cache.priority_of(&PathBuf::new()), None);
+    /// ```
+    pub fn priority_of<P: AsRef<Path>>(&self, path: P) -> Option<usize> {
+        let size: usize = match self.file_map.get(path.as_ref()) {
+            Some(file) => file.stats.size,
+            None => return None,
+        };
+        let access_count: usize = match self.access_count_map.find(&path.as_ref().to_path_buf()) {
+            Some(access_count) => access_count.get().clone(),
+            None => 0,
+        };
+        Some(self.priority_for(&path, access_count, size))
+    }
+
+    /// Gets the time at which a file was last gotten from the cache.
+    ///
+    /// Like access counts, the last access is recorded for files that aren't currently in the cache.
+    /// Returns `None` if the file has never been gotten.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - A path that is used as a key to look up the file.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use rocket_file_cache::Cache;
+    /// use std::path::PathBuf;
+    ///
+    /// let cache = Cache::new(1024 * 1024 * 30);
+    /// assert_eq!(cache.last_access(&PathBuf::from("www/index.html")), None);
+    /// ```
+    pub fn last_access<P: AsRef<Path>>(&self, path: P) -> Option<Instant> {
+        self.last_access_map
+            .find(&path.as_ref().to_path_buf())
+            .map(|last_access| last_access.get().clone())
+    }
+
+    /// Determines how many bytes would have to be removed from the cache in order to store a file
+    /// of the given size, without storing it or removing anything.
+    ///
+    /// The priority of the file is calculated using its current access count, plus the access that would store it.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path of the file, used to look up its access count.
+    /// * `size` - The size of the file in bytes.
+    ///
+    /// # Return
+    ///
+    /// The number of bytes of files that would be removed, or `None` if the file could not be stored.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use rocket_file_cache::Cache;
+    /// use std::path::PathBuf;
+    ///
+    /// let cache = Cache::new(1024 * 1024 * 30);
+    /// assert_eq!(cache.eviction_cost(&PathBuf::from("www/test.txt"), 1024), Some(0));
+    /// ```
+    pub fn eviction_cost<P: AsRef<Path>>(&self, path: P, size: usize) -> Option<usize> {
+        if size > self.max_cached_file_size() || size < self.min_file_size || size > self.size_limit() {
+            return None;
+        }
+
+        let used_bytes: usize = self.used_bytes();
+        let required_space_for_new_file: isize = (used_bytes as isize + size as isize) - self.usable_size_limit() as isize;
+        if required_space_for_new_file < 0 && size < self.size_limit() {
+            return Some(0);
+        }
+
+        let access_count: usize = match self.access_count_map.find(&path.as_ref().to_path_buf()) {
+            Some(access_count) => access_count.get().saturating_add(1),
+            None => 1,
+        };
+        let new_file_priority: usize = self.priority_for(&path, access_count, size);
+        let required_space: usize = cmp::min(cmp::max(required_space_for_new_file, 0) as usize, used_bytes);
+
+        match self.make_room_for_new_file(&path, required_space, new_file_priority) {
+            Ok(files_to_be_removed) => {
+                Some(files_to_be_removed.iter().fold(0usize, |freed, key| {
+                    match self.file_map.get(key) {
+                        Some(file) => freed + file.stats.size,
+                        None => freed,
+                    }
+                }))
+            }
+            Err(_) => None,
+        }
+    }
+
+    /// Determines if a file of the given size would be stored in the cache the next time it is gotten,
+    /// without storing it or removing anything.
+    ///
+    /// This can be used to avoid reading a file from a slow filesystem when it won't be cached anyway.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path of the file, used to look up its access count.
+    /// * `size` - The size of the file in bytes.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use rocket_file_cache::Cache;
+    /// use std::path::PathBuf;
+    ///
+    /// let cache = Cache::new(1024 * 1024 * 30);
+    /// assert!(cache.would_store(&PathBuf::from("www/test.txt"), 1024));
+    /// assert!(!cache.would_store(&PathBuf::from("www/large.iso"), 1024 * 1024 * 40));
+    /// ```
+    pub fn would_store<P: AsRef<Path>>(&self, path: P, size: usize) -> bool {
+        !self.passthrough && self.is_allowed_to_cache(&path, size) && self.eviction_cost(path, size).is_some()
+    }
+
+    /// Gets the paths of the most accessed files, along with their access counts, sorted from most to least accessed.
+    ///
+    /// Access counts are kept for files that aren't currently in the cache, so these files may not be cached.
+    ///
+    /// # Arguments
+    ///
+    /// * `n` - The maximum number of files to return.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use rocket_file_cache::Cache;
+    /// use std::path::PathBuf;
+    ///
+    /// let cache = Cache::new(1024 * 1024 * 30);
+    /// cache.touch(&PathBuf::from("www/index.html"), 100);
+    /// assert_eq!(cache.top_accessed(10), vec![(PathBuf::from("www/index.html"), 100)]);
+    /// ```
+    pub fn top_accessed(&self, n: usize) -> Vec<(PathBuf, usize)> {
+        let mut access_counts: Vec<(PathBuf, usize)> = self.access_count_map
+            .iter()
+            .map(|x| (x.0.clone(), x.1.clone()))
+            .collect();
+        access_counts.sort_by(|l, r| r.1.cmp(&l.1).then_with(|| l.0.cmp(&r.0)));
+        access_counts.truncate(n);
+        access_counts
+    }
+
+    /// Gets the number of distinct paths that have been gotten from the cache, whether or not they are cached.
+    ///
+    /// This approximates the size of the working set over the cache's lifetime, and together with the
+    /// number of evictions, shows how well the cache covers it.
+    /// Paths whose access counts are cleared, such as by `invalidate_prefix()`, are no longer counted.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use rocket_file_cache::Cache;
+    /// use std::path::PathBuf;
+    ///
+    /// let cache = Cache::new(1024 * 1024 * 30);
+    /// cache.touch(&PathBuf::from("www/index.html"), 1);
+    /// assert_eq!(cache.distinct_files_seen(), 1);
+    /// ```
+    pub fn distinct_files_seen(&self) -> usize {
+        self.access_count_map.iter().count()
+    }
+
+    /// Gets the paths and sizes of the largest and smallest files in the cache.
+    ///
+    /// This can be used to spot a single large file that is keeping many other files out of the cache.
+    ///
+    /// # Return
+    ///
+    /// A tuple containing the path and size of the largest file, followed by the path and size of
+    /// the smallest file, or `None` if the cache is empty.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use rocket_file_cache::Cache;
+    ///
+    /// let cache = Cache::new(1024 * 1024 * 30);
+    /// assert_eq!(cache.size_extremes(), None);
+    /// ```
+    pub fn size_extremes(&self) -> Option<(PathBuf, usize, PathBuf, usize)> {
+        self.file_map.entries().into_iter().fold(None, |extremes, (path, file)| {
+            let size: usize = file.stats.size;
+            match extremes {
+                None => Some((path.clone(), size, path.clone(), size)),
+                Some((largest_path, largest_size, smallest_path, smallest_size)) => {
+                    let (largest_path, largest_size) = if size > largest_size {
+                        (path.clone(), size)
+                    } else {
+                        (largest_path, largest_size)
+                    };
+                    let (smallest_path, smallest_size) = if size < smallest_size {
+                        (path.clone(), size)
+                    } else {
+                        (smallest_path, smallest_size)
+                    };
+                    Some((largest_path, largest_size, smallest_path, smallest_size))
+                }
+            }
+        })
+    }
+
+    /// Gets a snapshot of statistics about the cache.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use rocket_file_cache::Cache;
+    ///
+    /// let cache = Cache::new(1024 * 1024 * 30);
+    /// assert_eq!(cache.stats().evictions, 0);
+    /// ```
+    pub fn stats(&self) -> CacheStats {
+        let (file_count, used_bytes): (usize, usize) = self.file_map.entries().into_iter().fold(
+            (0usize, 0usize),
+            |(count, size), x| (count + 1, size + x.1.stats.size),
+        );
+        let (responses_built, average_response_time): (u64, Duration) = self.response_timer.average();
+        CacheStats {
+            file_count,
+            used_bytes,
+            evictions: self.evictions.load(Ordering::Relaxed),
+            eviction_waste_bytes: self.eviction_waste_bytes.load(Ordering::Relaxed),
+            files_read: self.files_read.load(Ordering::Relaxed),
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            responses_built,
+            average_response_time,
+        }
+    }
+
+    /// Gets the fraction of calls to `get()` that found the file already in the cache.
+    ///
+    /// Returns 0.0 if no files have been gotten yet.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use rocket_file_cache::Cache;
+    ///
+    /// let cache = Cache::new(1024 * 1024 * 30);
+    /// assert_eq!(cache.hit_ratio(), 0.0);
+    /// ```
+    pub fn hit_ratio(&self) -> f64 {
+        let hits: u64 = self.hits.load(Ordering::Relaxed);
+        let misses: u64 = self.misses.load(Ordering::Relaxed);
+        if hits + misses == 0 {
+            0.0
+        } else {
+            hits as f64 / (hits + misses) as f64
+        }
+    }
+
+    /// Gets the cache's statistics in the Prometheus text exposition format, so they can be served
+    /// to a Prometheus scraper without using a client library.
+    ///
+    /// Each metric's name starts with the prefix, followed by an underscore.
+    /// The hits, misses, and evictions are counters, while the bytes used, size limit, and number of
+    /// files are gauges.
+    ///
+    /// # Arguments
+    ///
+    /// * `prefix` - The prefix of the metric names, such as `file_cache`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use rocket_file_cache::Cache;
+    ///
+    /// let cache = Cache::new(1024 * 1024 * 30);
+    /// let metrics: String = cache.prometheus_metrics("file_cache");
+    /// assert!(metrics.contains("file_cache_hits_total 0\n"));
+    /// assert!(metrics.contains("file_cache_size_limit_bytes 31457280\n"));
+    /// ```
+    pub fn prometheus_metrics(&self, prefix: &str) -> String {
+        let stats: CacheStats = self.stats();
+        let metrics: [(&str, &str, &str, u64); 6] = [
+            ("hits_total", "counter", "The number of times a file was found in the cache.", stats.hits),
+            ("misses_total", "counter", "The number of times a file was not found in the cache.", stats.misses),
+            ("evictions_total", "counter", "The number of files removed to make room for other files.", stats.evictions),
+            ("used_bytes", "gauge", "The sum of the sizes of the files in the cache.", stats.used_bytes as u64),
+            ("size_limit_bytes", "gauge", "The number of bytes the cache is allowed to hold.", self.size_limit() as u64),
+            ("files", "gauge", "The number of files in the cache.", stats.file_count as u64),
+        ];
+
+        let mut text: String = String::new();
+        for &(name, metric_type, help, value) in metrics.iter() {
+            text.push_str(&format!("# HELP {}_{} {}\n", prefix, name, help));
+            text.push_str(&format!("# TYPE {}_{} {}\n", prefix, name, metric_type));
+            text.push_str(&format!("{}_{} {}\n", prefix, name, value));
+        }
+        text
+    }
+
+    /// Gets a snapshot of the paths, sizes, access counts, and priorities of the files in the cache,
+    /// along with the cache's statistics.
+    ///
+    /// The contents of the files are not included.
+    /// The files may change while the snapshot is being taken, so it is not guaranteed to be consistent
+    /// with the statistics when the cache is in use.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use rocket_file_cache::Cache;
+    ///
+    /// let cache = Cache::new(1024 * 1024 * 30);
+    /// let snapshot = cache.snapshot();
+    /// assert!(snapshot.files.is_empty());
+    /// assert_eq!(snapshot.size_limit, 1024 * 1024 * 30);
+    /// ```
+    pub fn snapshot(&self) -> CacheSnapshot {
+        let mut files: Vec<FileSnapshot> = self.file_map
+            .entries()
+            .into_iter()
+            .map(|x| {
+                let access_count: usize = match self.access_count_map.find(&x.0) {
+                    Some(access_count) => access_count.get().clone(),
+                    None => x.1.stats.access_count,
+                };
+                FileSnapshot {
+                    path: x.0.clone(),
+                    size: x.1.stats.size,
+                    access_count,
+                    priority: self.priority_for(x.0, access_count, x.1.stats.size),
+                }
+            })
+            .collect();
+        files.sort_by(|l, r| l.path.cmp(&r.path));
+        CacheSnapshot {
+            size_limit: self.size_limit(),
+            files,
+            stats: self.stats(),
+        }
+    }
+
+    /// Gets the number of bytes that the cache is allowed to hold.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use rocket_file_cache::Cache;
+    ///
+    /// let cache = Cache::new(1024 * 1024 * 30);
+    /// let fill_percentage: f64 = cache.used_bytes() as f64 / cache.size_limit() as f64 * 100.0;
+    /// assert_eq!(fill_percentage, 0.0);
+    /// ```
+    pub fn size_limit(&self) -> usize {
+        self.size_limit.load(Ordering::Relaxed)
+    }
+
+    /// Sets the number of bytes that the cache is allowed to hold.
+    ///
+    /// Files aren't removed when the size limit shrinks, but the cache will remove files to get back
+    /// under the size limit when new files are stored.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use rocket_file_cache::{Cache, CacheBuilder};
+    /// use std::sync::Arc;
+    ///
+    /// let cache: Arc<Cache> = Arc::new(CacheBuilder::new().size_limit(1024 * 1024 * 10).build().unwrap());
+    /// cache.set_size_limit(1024 * 1024 * 5);
+    /// assert_eq!(cache.size_limit(), 1024 * 1024 * 5);
+    /// ```
+    pub fn set_size_limit(&self, size_limit: usize) {
+        self.size_limit.store(size_limit, Ordering::Relaxed);
+    }
+
+    /// Gets the number of bytes that can be used before new files will require other files to be removed.
+    ///
+    /// This is the size limit minus the fraction of it that is reserved as free space.
+    fn usable_size_limit(&self) -> usize {
+        let reserved: usize = (self.size_limit() as f64 * self.reserve_fraction) as usize;
+        self.size_limit().saturating_sub(reserved)
+    }
+
+    /// Gets the largest number of bytes a file can have in order to be stored in the cache,
+    /// accounting for both the `max_file_size` and the `max_single_file_fraction`.
+    fn max_cached_file_size(&self) -> usize {
+        match self.max_single_file_fraction {
+            Some(fraction) => cmp::min(self.max_file_size, (self.size_limit() as f64 * fraction) as usize),
+            None => self.max_file_size,
+        }
+    }
+
+    /// Gets the size of the file from the file's metadata.
+    /// This avoids having to read the file into memory in order to get the file size.
+    fn get_file_size_from_metadata<P: AsRef<Path>>(path: P) -> Result<usize, CacheError> {
+        let path_string: String = match path.as_ref().to_str() {
+            Some(s) => String::from(s),
+            None => return Err(CacheError::InvalidPath),
+        };
+        let metadata: Metadata = match Self::stat(path_string.as_str()) {
+            Ok(m) => m,
+            Err(_) => return Err(CacheError::InvalidMetadata),
+        };
+        let size: usize = metadata.len() as usize;
+        Ok(size)
+    }
+
+
+    /// Attempt to store a given file in the cache, making sure that concurrent attempts to store the
+    /// same file will only read it from the filesystem once.
+    ///
+    /// The first attempt to store the file holds a lock for its path while it tries to insert the file.
+    /// Other attempts wait for that lock, and if the file was stored in the meantime, get it from the
+    /// cache instead of reading it again.
+    ///
+    /// The file's origin is `Origin::Cache` if it was stored by a concurrent attempt.
+    fn try_insert_once<P: AsRef<Path>>(&self, path: P) -> (CachedFile, Origin) {
+        let path: PathBuf = path.as_ref().to_path_buf();
+
+        let path_lock: Arc<Mutex<()>> = self.lock_in_flight()
+            .entry(path.clone())
+            .or_insert_with(|| Arc::new(Mutex::new(())))
+            .clone();
+
+        let (cached_file, origin): (CachedFile, Origin) = {
+            let _path_guard: MutexGuard<()> = match path_lock.lock() {
+                Ok(guard) => guard,
+                Err(poisoned) => poisoned.into_inner(),
+            };
+            if self.contains_key(&path) {
+                trace!("File {:?} was added to the cache by a concurrent request.", path);
+                self.increment_access_count(&path);
+                self.update_stats(&path);
+                (self.get_from_cache(&path), Origin::Cache)
+            } else {
+                (self.try_insert(&path), Origin::Filesystem)
+            }
+        };
+
+        // If no other attempts are waiting on the path's lock, it is no longer needed.
+        // Locks are only cloned while the in-flight map is locked, so the count can't increase here.
+        let mut in_flight = self.lock_in_flight();
+        if Arc::strong_count(&path_lock) == 2 {
+            in_flight.remove(&path);
+        }
+
+        (cached_file, origin)
+    }
+
+    /// Helper function that locks the map of paths of files that are being added to the cache.
+    fn lock_in_flight(&self) -> MutexGuard<HashMap<PathBuf, Arc<Mutex<()>>>> {
+        match self.in_flight.lock() {
+            Ok(guard) => guard,
+            Err(poisoned) => poisoned.into_inner(),
+        }
+    }
+
+    /// Helper function that reads a file from the filesystem into memory, counting the read.
+    ///
+    /// If the cache has a `max_concurrent_reads`, this waits until fewer than that many files are being read.
+    fn read_file<P: AsRef<Path>>(&self, path: P) -> io::Result<InMemoryFile> {
+        let _permit: ReadPermit = self.read_limiter.acquire(self.max_concurrent_reads);
+        self.files_read.fetch_add(1, Ordering::Relaxed);
+        Self::open_file(path, self.release_page_cache)
+    }
+
+    /// Helper function that reads a file into memory, releasing its pages from the operating system's page cache if requested.
+    fn open_file<P: AsRef<Path>>(path: P, release_page_cache: bool) -> io::Result<InMemoryFile> {
+        if release_page_cache {
+            InMemoryFile::open_and_release_page_cache(path)
+        } else {
+            InMemoryFile::open(path)
+        }
+    }
+
+    /// Determines which files in a directory, and its subdirectories, should be preloaded into the cache.
+    ///
+    /// The paths are ordered from highest to lowest priority, and only include files that aren't
+    /// already cached and that fit in the space the cache has left.
+    /// Nothing is preloaded if the cache is in passthrough mode.
+    fn plan_preload<P: AsRef<Path>>(&self, dir: P) -> io::Result<Vec<PathBuf>> {
+        if self.passthrough {
+            return Ok(vec![]);
+        }
+        let mut files: Vec<(PathBuf, usize, usize)> = Self::files_in_dir(dir)?
+            .into_iter()
+            .filter(|path| !self.contains_key(path))
+            .filter_map(|path| {
+                Self::get_file_size_from_metadata(&path).ok().map(|size| (path, size))
+            })
+            .filter(|&(ref path, size)| {
+                size <= self.max_cached_file_size() && size >= self.min_file_size && self.is_allowed_to_cache(path, size)
+            })
+            .map(|(path, size)| {
+                let access_count: usize = match self.access_count_map.find(&path) {
+                    Some(access_count) => access_count.get().clone(),
+                    None => 1,
+                };
+                let priority: usize = self.priority_for(&path, access_count, size);
+                (path, size, priority)
+            })
+            .collect();
+        files.sort_by(|l, r| r.2.cmp(&l.2));
+
+        let mut remaining_space: usize = self.usable_size_limit().saturating_sub(self.used_bytes());
+        Ok(files
+            .into_iter()
+            .filter(|&(_, size, _)| if size <= remaining_space {
+                remaining_space -= size;
+                true
+            } else {
+                false
+            })
+            .map(|(path, _, _)| path)
+            .collect())
+    }
+
+    /// Helper function that gets the paths of all files in a directory and its subdirectories.
+    fn files_in_dir<P: AsRef<Path>>(dir: P) -> io::Result<Vec<PathBuf>> {
+        let mut files: Vec<PathBuf> = vec![];
+        for entry in fs::read_dir(dir)? {
+            let path: PathBuf = entry?.path();
+            if path.is_dir() {
+                files.extend(Self::files_in_dir(&path)?);
+            } else {
+                files.push(path);
+            }
+        }
+        Ok(files)
+    }
+
+    /// Helper function that gets the path of the index file if the path is a directory and the cache has an `index_file`.
+    ///
+    /// Paths that are already cached can't be directories, so the filesystem is only checked for paths that aren't cached.
+    fn resolve_index_file<P: AsRef<Path>>(&self, path: P) -> PathBuf {
+        let path: PathBuf = path.as_ref().to_path_buf();
+        match self.index_file {
+            Some(ref index_file) if !self.contains_key(&path) && Self::stat(&path).map_or(false, |metadata| metadata.is_dir()) => {
+                path.join(index_file)
+            }
+            _ => path,
+        }
+    }
+
+    /// Helper function that gets the path of the file on disk that matches the path ignoring case,
+    /// if the cache is case insensitive.
+    ///
+    /// Matches are remembered by their lowercased path, so the filesystem is only searched the first time
+    /// each casing of a path is gotten. If no file matches, the path is returned unchanged.
+    /// A remembered match that is neither cached nor on disk anymore, because the file was renamed or deleted,
+    /// is forgotten and the filesystem is searched again.
+    fn resolve_case<P: AsRef<Path>>(&self, path: P) -> PathBuf {
+        let path: PathBuf = path.as_ref().to_path_buf();
+        if !self.case_insensitive {
+            return path;
+        }
+        let folded: PathBuf = Self::fold_case(&path);
+        if let Some(resolved) = self.case_folded_paths.find(&folded).map(|resolved| resolved.get().clone()) {
+            if self.contains_key(&resolved) || Self::stat(&resolved).is_ok() {
+                return resolved;
+            }
+            debug!("{:?} no longer exists, searching for {:?} ignoring case again.", resolved, path);
+            self.case_folded_paths.remove(&folded);
+        }
+
+        match Self::find_ignoring_case(&path) {
+            Some(resolved) => {
+                self.case_folded_paths.insert(folded, resolved.clone());
+                resolved
+            }
+            None => path,
+        }
+    }
+
+    /// Helper function that forgets the case insensitive match for the path, if there is one,
+    /// so the filesystem is searched again the next time any casing of the path is gotten.
+    fn forget_case_folded_path<P: AsRef<Path>>(&self, path: P) {
+        if self.case_insensitive {
+            self.case_folded_paths.remove(&Self::fold_case(path.as_ref()));
+        }
+    }
+
+    /// Gets the key that the case insensitive matches for every casing of the path are remembered under.
+    fn fold_case(path: &Path) -> PathBuf {
+        PathBuf::from(path.to_string_lossy().to_lowercase())
+    }
+
+    /// Finds the path on disk that matches every component of the path, ignoring case.
+    /// Components that exist with the given casing are used as they are, otherwise their directory is searched.
+    fn find_ignoring_case(path: &Path) -> Option<PathBuf> {
+        let mut resolved: PathBuf = PathBuf::new();
+        for component in path.components() {
+            let candidate: PathBuf = resolved.join(component.as_os_str());
+            if Self::stat(&candidate).is_ok() {
+                resolved = candidate;
+                continue;
+            }
+            let name: String = component.as_os_str().to_string_lossy().to_lowercase();
+            let dir: &Path = if resolved.as_os_str().is_empty() { Path::new(".") } else { resolved.as_path() };
+            let matched: PathBuf = fs::read_dir(dir)
+                .ok()?
+                .filter_map(|entry| entry.ok())
+                .map(|entry| entry.file_name())
+                .find(|file_name| file_name.to_string_lossy().to_lowercase() == name)
+                .map(|file_name| resolved.join(file_name))?;
+            resolved = matched;
+        }
+        Some(resolved)
+    }
+
+    /// Helper function that reads the metadata of the file at the path.
+    ///
+    /// Every check of the filesystem made to find a file goes through this, so tests can count them.
+    fn stat<P: AsRef<Path>>(path: P) -> io::Result<Metadata> {
+        #[cfg(test)]
+        STAT_CALLS.with(|calls| calls.set(calls.get() + 1));
+        fs::metadata(path)
+    }
+
+    /// Helper function that checks if the path was found to be missing within the `negative_cache_ttl`.
+    ///
+    /// Paths that were found to be missing longer ago than that are forgotten, so the filesystem will be checked again.
+    fn is_known_to_be_missing<P: AsRef<Path>>(&self, path: P) -> bool {
+        let ttl: Duration = match self.negative_cache_ttl {
+            Some(ttl) => ttl,
+            None => return false,
+        };
+        let path: PathBuf = path.as_ref().to_path_buf();
+        let missing_since: Instant = match self.missing.find(&path) {
+            Some(missing_since) => missing_since.get().clone(),
+            None => return false,
+        };
+        if missing_since.elapsed() < ttl {
+            true
+        } else {
+            self.missing.remove(&path);
+            false
+        }
+    }
+
+    /// Helper function that remembers that the path couldn't be found, if the cache has a `negative_cache_ttl`.
+    ///
+    /// Expired paths are forgotten when they are next looked up. So that probing for many different
+    /// missing paths won't grow the set of missing paths without bound, every `MISSING_PATHS_PER_SWEEP`
+    /// remembered paths, the paths whose time to live has passed are swept out as well.
+    fn remember_missing<P: AsRef<Path>>(&self, path: P) {
+        let ttl: Duration = match self.negative_cache_ttl {
+            Some(ttl) => ttl,
+            None => return,
+        };
+        self.missing.insert(path.as_ref().to_path_buf(), Instant::now());
+        if self.missing_inserts.fetch_add(1, Ordering::Relaxed) % MISSING_PATHS_PER_SWEEP == MISSING_PATHS_PER_SWEEP - 1 {
+            self.sweep_missing(ttl);
+        }
+    }
+
+    /// Helper function that forgets the missing paths whose time to live has passed.
+    fn sweep_missing(&self, ttl: Duration) {
+        let expired: Vec<PathBuf> = self.missing
+            .iter()
+            .filter(|x| x.1.elapsed() >= ttl)
+            .map(|x| x.0.clone())
+            .collect();
+        for path in expired {
+            self.missing.remove(&path);
+        }
+    }
+
+    /// Helper function that checks if the cache's `should_cache` function allows the file to be stored,
+    /// and that the priority function doesn't veto storing it.
+    ///
+    /// A priority of 0 means that the file should never be cached, so the priority is calculated
+    /// as if the file were being accessed, and the file is rejected if it is 0.
+    fn is_allowed_to_cache<P: AsRef<Path>>(&self, path: P, size: usize) -> bool {
+        let access_count: usize = match self.access_count_map.find(path.as_ref()) {
+            Some(access_count) => access_count.get().saturating_add(1),
+            None => 1,
+        };
+        if self.priority_for(&path, access_count, size) == 0 {
+            trace!("The priority function vetoed caching {:?}.", path.as_ref());
+            return false;
+        }
+        match self.should_cache {
+            Some(ref should_cache) => should_cache(path.as_ref(), size),
+            None => true,
+        }
+    }
+
+    /// Helper function that checks if a file of the given size could ever be stored within the size limit.
+    ///
+    /// A size limit of 0 means that nothing is stored, not even empty files.
+    fn fits_in_size_limit(&self, size: usize) -> bool {
+        self.size_limit() > 0 && size <= self.size_limit()
+    }
+
+    /// Helper function that stores a preloaded file if it still fits in the cache.
+    ///
+    /// Other files may have been stored while the file was being read, so the space is checked again before inserting it.
+    fn store_preloaded_file(&self, path: PathBuf, file: InMemoryFile) -> bool {
+        if self.contains_key(&path) || !self.has_expected_checksum(&path, &file) || !self.fits_in_size_limit(file.stats.size) {
+            return false;
+        }
+        if self.used_bytes() + file.stats.size > self.usable_size_limit() {
+            debug!("Skipped preloading {:?}, because the cache no longer has room for it.", path);
+            return false;
+        }
+        self.store_file(path.clone(), file);
+        self.update_stats(&path);
+        true
+    }
+
+    /// Attempt to store a given file in the the cache.
+    /// Storing will fail if the current files have more access attempts than the file being added.
+    /// If the provided file has more more access attempts than one of the files in the cache,
+    /// but the cache is full, a file will have to be removed from the cache to make room
+    /// for the new file.
+    ///
+    /// If the insertion works, the cache will update the priority score for the file being inserted.
+    /// The cached priority score requires the file in question to exist in the file map, so it will
+    /// have a size to use when calculating.
+    ///
+    /// It will get the size of the file to be inserted.
+    /// If will use this size to check if the file could be inserted.
+    /// If it can be inserted, it reads the file into memory, stores a copy of the in-memory
+    /// file behind a pointer, and constructs a CachedFile to return.
+    ///
+    /// If the file can't be added, it will open a NamedFile and construct a CachedFile from that,
+    /// and return it.
+    /// This means that it doesn't need to read the whole file into memory before reading through it
+    /// again to set the response body.
+    /// The lack of the need to read the whole file twice keeps performance of cache misses on par
+    /// with just normally reading the file without a cache.
+    ///
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path of the file to be stored. Acts as a key for the file in the cache. Is used
+    /// look up the location of the file in the filesystem if the file is not in the cache.
+    ///
+    /// # Return
+    ///
+    /// If the file was stored, the returned CachedFile is an `InMemory` variant holding the entry
+    /// that was just inserted, so it can be served without looking it up in the cache again.
+    /// Otherwise, a `FileSystem` variant is returned, or `NotFound` if the file doesn't exist.
+    fn try_insert<P: AsRef<Path>>(&self, path: P) -> CachedFile {
+        let path: PathBuf = path.as_ref().to_path_buf();
+        trace!("Trying to insert file {:?}", path);
+
+        // If the cache has grown past its high watermark, free up space in one pass so this and
+        // subsequent inserts don't have to search for files to remove.
+        self.evict_to_low_watermark();
+
+        // If the FS can read metadata for a file, then the file exists, and it should be safe to increment
+        // the access_count and update.
+        let size: usize = match Self::get_file_size_from_metadata(&path) {
+            Ok(size) => size,
+            Err(_) => return CachedFile::NotFound // Could not open file to read metadata.
+        };
+
+        // Determine how much space needs to be freed in order to make room for the new file while keeping the reserve free.
+        // If the file already fits, no space needs to be freed.
+        let used_bytes: usize = self.used_bytes();
+        let required_space_for_new_file: usize = used_bytes.saturating_add(size).saturating_sub(self.usable_size_limit());
+
+
+        // Files that could never fit, or that shouldn't be cached, are streamed from the filesystem instead of being read into memory.
+        if size > self.max_cached_file_size() || size < self.min_file_size || !self.fits_in_size_limit(size) || !self.is_allowed_to_cache(&path, size) {
+            self.get_file_from_fs(&path)
+        } else if required_space_for_new_file == 0 {
+            self.get_file_from_fs_and_add_to_cache(&path)
+        } else {
+            if self.log_evictions {
+                debug!("Trying to make room for the file {:?}", path);
+            }
+
+            // Because the size was gotten from the file's metadata, we know that it exists,
+            // so its fine to increment the account
+            self.increment_access_count(&path);
+
+            // The access_count should have incremented since the last time this was called, so the priority must be recalculated.
+            // Also, the size generally
+            let new_file_priority: usize;
+            {
+                let new_file_access_count: &usize = match self.access_count_map.find(&path) {
+                    Some(access_count) => &access_count.get(),
+                    None => &1,
+                };
+                new_file_priority = self.priority_for(&path, new_file_access_count.clone(), size);
+            }
+
+
+            // Files larger than the reserve allows can still be stored if everything else is removed,
+            // as long as they fit within the size limit.
+            let required_space: usize = cmp::min(required_space_for_new_file, used_bytes);
+
+            match self.make_room_for_new_file(&path, required_space, new_file_priority) {
+                Ok(files_to_be_removed) => {
+                    if self.log_evictions {
+                        debug!("Made room for new file {:?}", path);
+                    }
+                    match self.read_file(path.as_path()) {
+                        Ok(ref file) if !self.has_expected_checksum(&path, file) => return CachedFile::NotFound,
+                        Ok(file) => {
+
+                            // We have read a new file into memory, it is safe to
+                            // remove the old files.
+                            // They were accessed with these keys earlier when sorting priorities, which should make removal safe.
+                            self.evict_to_make_room(files_to_be_removed, required_space);
+
+                            self.store_file(path.clone(), file);
+                            self.update_stats(&path);
+
+                            let cached_file: InMemoryFile = match self.file_map.get(&path) {
+                                Some(cached_file) => cached_file,
+                                None => {
+                                    // If a concurrent remove operation removes the file before
+                                    // it can be gotten from the cache, recursively try to add
+                                    // the file to the Cache until it can be gotten.
+
+                                    // Because this action takes place after room was made for
+                                    // the new file in the cache, those files will be left out of the cache.
+                                    warn!("Tried to add file to cache, but it was removed before it could be added. Attempting to insert file again.");
+                                    // Because this recursion only occurs under extremely rare
+                                    // circumstances due to concurrent removal of the file being
+                                    // added between the insertion into the map, and getting it
+                                    // again, a stack overflow is almost impossible. This would require
+                                    // the file to be removed on every recursive attempt to re-insert it,
+                                    // with the exact same timing required to invalidate the `get()` method,
+                                    // for as many times as it takes to fill up the stack. It's not
+                                    // going to happen.
+                                    return self.try_insert(path);
+                                }
+                            };
+
+                            let named_in_memory_file: NamedInMemoryFile = self.named_in_memory_file(
+                                path.clone(),
+                                cached_file
+                            );
+
+                            return CachedFile::from(named_in_memory_file);
+                        }
+                        Err(_) => return CachedFile::NotFound
+                    }
+                }
+                Err(_) => {
+                    if self.log_evictions {
+                        debug!("The file {:?} does not have enough priority or is too large to be accepted into the cache.", path);
+                    }
+                    // The new file would not be accepted by the cache, so instead of reading the whole file
+                    // into memory, and then copying it yet again when it is attached to the body of the
+                    // response, stream it from the filesystem instead.
+                    self.open_streamed_file(&path)
+                }
+            }
+        }
+    }
+
+    /// Gets a file from the filesystem and converts it to a CachedFile.
+    ///
+    /// This should be used when the cache knows that the new file won't make it into the cache.
+    fn get_file_from_fs< P: AsRef<Path>>(&self, path: P) -> CachedFile{
+        debug!("File does not fit size constraints of the cache.");
+        match self.open_streamed_file(&path) {
+            CachedFile::NotFound => CachedFile::NotFound,
+            streamed_file => {
+                self.increment_access_count(path);
+                streamed_file
+            }
+        }
+    }
+
+    /// Opens a file that will be streamed from the filesystem.
+    ///
+    /// If the file has an expected checksum, the checksum will be verified as the file is streamed.
+    fn open_streamed_file<P: AsRef<Path>>(&self, path: P) -> CachedFile<'static> {
+        let named_file: NamedFile = match NamedFile::open(path.as_ref()) {
+            Ok(named_file) => named_file,
+            Err(_) => return CachedFile::NotFound,
+        };
+        let max_age: Option<usize> = self.max_age_for(&path);
+        match self.expected_checksums.find(&path.as_ref().to_path_buf()) {
+            Some(expected_checksum) => CachedFile::from(VerifiedFile {
+                file: named_file,
+                expected_checksum: *expected_checksum.get(),
+                on_mismatch: self.on_checksum_mismatch,
+                max_age,
+                #[cfg(feature = "rocket")]
+                status: None,
+            }),
+            None => CachedFile::from(StreamedFile {
+                file: named_file,
+                max_age,
+                #[cfg(feature = "rocket")]
+                status: None,
+            }),
+        }
+    }
+
+    /// Reads a file from the filesystem into memory and stores it in the cache.
+    ///
+    /// This is the slowest operation the cache can perform, slower than just getting the file.
+    /// It should only be used when the cache decides to store the file.
+    fn get_file_from_fs_and_add_to_cache<P: AsRef<Path>>(&self, path: P) -> CachedFile {
+        debug!("Cache has room for the file.");
+        match self.read_file(&path) {
+            Ok(ref file) if !self.has_expected_checksum(&path, file) => return CachedFile::NotFound,
+            Ok(file) => {
+                self.store_file(path.as_ref().to_path_buf(), file);
+
+                self.increment_access_count(&path);
+                self.update_stats(&path);
+
+                let in_memory_file: InMemoryFile = match self.file_map.get(path.as_ref()) {
+                    Some(in_memory_file) => in_memory_file,
+                    None => {
+                        // If for whatever reason, a concurrent remove operation removes the file
+                        // before it can be gotten from the cache, recursively try to add
+                        // the file to the Cache until it can be gotten.
+                        warn!("Tried to add file to cache, but it was removed before it could be added. Attempting to get file again.");
+                        // Because this recursion only occurs under extremely rare circumstances
+                        // due to a concurrent removal of the file being added between the insertion
+                        // into the map, and getting it again, a stack overflow is almost impossible.
+                        return self.get_file_from_fs_and_add_to_cache(path);
+                    }
+                };
+
+                let cached_file: NamedInMemoryFile = self.named_in_memory_file(
+                    path.as_ref().to_path_buf(),
+                    in_memory_file
+                );
+
+                return CachedFile::from(cached_file);
+            }
+            Err(_) => return CachedFile::NotFound,
+        }
+    }
+
+
+
+    /// Determine which files should be removed to make room for a file with a size: required_space.
+    ///
+    /// The lowest priority files whose aggregate priority does not exceed the new file's priority
+    /// are candidates for removal.
+    /// Of those, the files that free the required space while wasting the least space are chosen,
+    /// so a large file won't be removed when a few small files would suffice.
+    ///
+    /// If this returns an OK, it contains the keys of the files that should be removed from the file_map.
+    /// If this returns an Err, then either not enough space could be freed, or the priority of
+    /// files that would need to be freed to make room for the new file is greater than the
+    /// new file's priority.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path of the new file. A file already stored at the path is being replaced,
+    /// so removing it wouldn't make room, and it is never chosen.
+    /// * `required_space` - A `usize` representing the number of bytes that must be freed to make room for a new file.
+    /// * `new_file_priority` - A `usize` representing the priority of the new file to be added. If the priority of the files possibly being removed
+    /// is greater than this value, then the files won't be removed.
+    fn make_room_for_new_file<P: AsRef<Path>>(&self, path: P, required_space: usize, new_file_priority: usize) -> Result<Vec<PathBuf>, CacheError> {
+        if required_space == 0 {
+            return Ok(vec![]);
+        }
+
+        let mut possibly_freed_space: usize = 0;
+        let mut priority_score_to_free: usize = 0;
+        let mut candidates: Vec<(PathBuf, FileStats)> = vec![];
+
+        let mut stats: Vec<(PathBuf, FileStats)> = self.eviction_order();
+        stats.retain(|file| file.0 != path.as_ref());
+        let file_count: usize = stats.len();
+        // pop the files with the lowest priority off of the vector
+        while let Some(lowest) = stats.pop() {
+            // Check if total priority to free is greater than the new file's priority,
+            // If it is, then don't consider this file, as it and the other candidates are, in
+            // aggregate, more important than the new file.
+            priority_score_to_free = priority_score_to_free.saturating_add(lowest.1.priority);
+            if priority_score_to_free > new_file_priority {
+                break;
+            }
+            possibly_freed_space += lowest.1.size;
+            candidates.push(lowest);
+        }
+
+        if possibly_freed_space < required_space {
+            if self.log_evictions {
+                debug!("Only {} of the required {} bytes could be freed without exceeding a priority of {}.", possibly_freed_space, required_space, new_file_priority);
+            }
+            if candidates.len() == file_count {
+                return Err(CacheError::NoMoreFilesToRemove);
+            } else {
+                return Err(CacheError::NewPriorityIsNotHighEnough);
+            }
+        }
+
+        // Uncompressed files whose compressed variants are cached can still be served to most clients from memory,
+        // so they are removed instead of other candidates when they free enough space on their own.
+        let uncompressed: Vec<(PathBuf, FileStats)> = candidates
+            .iter()
+            .filter(|candidate| self.has_compressed_variant(&candidate.0))
+            .cloned()
+            .collect();
+        if uncompressed.iter().map(|candidate| candidate.1.size).sum::<usize>() >= required_space {
+            return Ok(Self::least_wasteful_files(uncompressed, required_space));
+        }
+
+        Ok(Self::least_wasteful_files(candidates, required_space))
+    }
+
+    /// Selects files from the candidates that free at least the required space, while freeing as
+    /// little additional space as possible.
+    ///
+    /// The smallest single file that frees enough space on its own is compared against a
+    /// combination of the smallest files, and whichever frees less space is chosen.
+    /// The candidates must be able to free the required space in aggregate.
+    fn least_wasteful_files(mut candidates: Vec<(PathBuf, FileStats)>, required_space: usize) -> Vec<PathBuf> {
+        candidates.sort_by(|l, r| l.1.size.cmp(&r.1.size));
+
+        // Accumulate the smallest files until enough space would be freed.
+        let mut combination: Vec<&(PathBuf, FileStats)> = vec![];
+        let mut combined_size: usize = 0;
+        for candidate in candidates.iter() {
+            if combined_size >= required_space {
+                break;
+            }
+            combined_size += candidate.1.size;
+            combination.push(candidate);
+        }
+
+        // Going from largest to smallest, drop any files from the combination that aren't needed to free the required space.
+        let mut index: usize = combination.len();
+        while index > 0 {
+            index -= 1;
+            let size: usize = combination[index].1.size;
+            if combined_size - size >= required_space {
+                combined_size -= size;
+                combination.remove(index);
+            }
+        }
+
+        match candidates.iter().find(|candidate| candidate.1.size >= required_space) {
+            Some(single) if single.1.size <= combined_size => vec![single.0.clone()],
+            _ => combination.into_iter().map(|candidate| candidate.0.clone()).collect(),
+        }
+    }
+
+    /// Removes files from the cache, in the order determined by the eviction policy, until the number of used bytes is at or below the low watermark.
+    ///
+    /// This only does anything if both watermarks are set and the used bytes exceed the high watermark.
+    fn evict_to_low_watermark(&self) {
+        let (high_watermark, low_watermark): (usize, usize) = match (self.high_watermark, self.low_watermark) {
+            (Some(high), Some(low)) => (high, low),
+            _ => return,
+        };
+
+        let used_bytes: usize = self.used_bytes();
+        if used_bytes <= high_watermark {
+            return;
+        }
+        if self.log_evictions {
+            debug!("Cache usage of {} bytes exceeds the high watermark, evicting down to {} bytes.", used_bytes, low_watermark);
+        }
+        self.evict_down_to(low_watermark);
+    }
+
+    /// Helper function that evicts the files that should be removed first until the cache's usage is
+    /// at or below the target, returning the number of bytes freed.
+    fn evict_down_to(&self, target_bytes: usize) -> usize {
+        let mut used_bytes: usize = self.used_bytes();
+        let mut freed_bytes: usize = 0;
+        let mut stats: Vec<(PathBuf, FileStats)> = self.eviction_order();
+        while used_bytes > target_bytes {
+            // pop the file with the lowest priority off of the vector
+            match stats.pop() {
+                Some((lowest_key, lowest_stats)) => {
+                    if self.evict_uncompressed_or_file(&lowest_key) {
+                        used_bytes = used_bytes.saturating_sub(lowest_stats.size);
+                        freed_bytes += lowest_stats.size;
+                    }
+                }
+                None => break,
+            }
+        }
+        freed_bytes
+    }
+
+    /// Helper function that evicts the file, using `evict_uncompressed()` if it has a compressed variant in the cache.
+    #[cfg(feature = "rocket")]
+    fn evict_uncompressed_or_file(&self, path: &PathBuf) -> bool {
+        self.evict_uncompressed(path) || self.evict(path)
+    }
+
+    #[cfg(not(feature = "rocket"))]
+    fn evict_uncompressed_or_file(&self, path: &PathBuf) -> bool {
+        self.evict(path)
+    }
+
+    /// Helper function that checks if the file has the checksum registered for its path.
+    ///
+    /// Files without an expected checksum are always considered valid.
+    fn has_expected_checksum<P: AsRef<Path>>(&self, path: P, file: &InMemoryFile) -> bool {
+        match self.expected_checksums.find(&path.as_ref().to_path_buf()) {
+            Some(expected_checksum) => {
+                if checksum(&file.bytes) == *expected_checksum.get() {
+                    true
+                } else {
+                    error!("The file at {:?} does not match its expected checksum and will not be cached.", path.as_ref());
+                    false
+                }
+            }
+            None => true,
+        }
+    }
+
+    /// Helper function that stores a file in the file_map, keeping the count of stored bytes up to date.
+    ///
+    /// The size of the file is counted before it is stored, so a concurrent removal of the file
+    /// can't make the count drop below the bytes that are actually stored.
+    fn store_file(&self, path: PathBuf, file: InMemoryFile) -> Option<InMemoryFile> {
+        self.stored_bytes.fetch_add(file.stats.size, Ordering::SeqCst);
+        let replaced_file: Option<InMemoryFile> = self.file_map.insert(path, file);
+        if let Some(ref replaced_file) = replaced_file {
+            self.stored_bytes.fetch_sub(replaced_file.stats.size, Ordering::SeqCst);
+        }
+        replaced_file
+    }
+
+    /// Helper function that removes a file from the file_map, keeping the count of stored bytes up to date.
+    fn remove_stored_file(&self, path: &Path) -> Option<InMemoryFile> {
+        let removed_file: Option<InMemoryFile> = self.file_map.remove(path);
+        if let Some(ref removed_file) = removed_file {
+            self.stored_bytes.fetch_sub(removed_file.stats.size, Ordering::SeqCst);
+        }
+        removed_file
+    }
+
+    /// Helper function that updates the count of stored bytes after the size of a stored file changed.
+    fn record_resize(&self, old_size: usize, new_size: usize) {
+        if new_size > old_size {
+            self.stored_bytes.fetch_add(new_size - old_size, Ordering::SeqCst);
+        } else {
+            self.stored_bytes.fetch_sub(old_size - new_size, Ordering::SeqCst);
+        }
+    }
+
+    /// Helper function that removes a file from the cache to make room for other files, counting the eviction.
+    ///
+    /// If the cache has an `on_evict` function, the removed file is given to it before being dropped.
+    fn evict(&self, path: &PathBuf) -> bool {
+        self.evict_returning_size(path).is_some()
+    }
+
+    /// Evicts the file in the same way as `evict()`, returning the size of the removed file.
+    fn evict_returning_size(&self, path: &PathBuf) -> Option<usize> {
+        match self.remove_stored_file(path) {
+            Some(file) => {
+                self.evictions.fetch_add(1, Ordering::Relaxed);
+                let size: usize = file.stats.size;
+                if self.log_evictions {
+                    debug!("Evicted {:?}, which had a priority of {}.", path, file.stats.priority);
+                }
+                if let Some(ref on_evict) = self.on_evict {
+                    on_evict(path, &file);
+                }
+                Some(size)
+            }
+            None => {
+                warn!("Likely due to concurrent mutations, a file being removed from the cache was not found because another thread removed it first.");
+                None
+            }
+        }
+    }
+
+    /// Evicts the files chosen to make room for a new file, recording how many more bytes were freed than were required.
+    fn evict_to_make_room(&self, files_to_be_removed: Vec<PathBuf>, required_space: usize) {
+        let freed_bytes: usize = files_to_be_removed
+            .iter()
+            .filter_map(|file_key| self.evict_returning_size(file_key))
+            .sum();
+        self.eviction_waste_bytes.fetch_add(freed_bytes.saturating_sub(required_space) as u64, Ordering::Relaxed);
+    }
+
+    ///Helper function that gets the file from the cache if it exists there.
+    fn get_from_cache<P: AsRef<Path>>(&self, path: P) -> CachedFile {
+        match self.file_map.get(path.as_ref()) {
+            Some(in_memory_file) => {
+                trace!("Found file: {:?} in cache.", path.as_ref());
+                CachedFile::from(self.named_in_memory_file(
+                    path.as_ref().to_path_buf(),
+                    in_memory_file,
+                ))
+            }
+            None => CachedFile::NotFound,
+        }
+
+    }
+
+    /// Helper function that wraps a file from the cache with the information needed to respond with it.
+    fn named_in_memory_file<'a>(&'a self, path: PathBuf, file: InMemoryFile) -> NamedInMemoryFile<'a> {
+        let max_age: Option<usize> = self.max_age_for(&path);
+        let mut named_in_memory_file: NamedInMemoryFile = NamedInMemoryFile::new(path, file);
+        named_in_memory_file.max_age = max_age;
+        #[cfg(feature = "rocket")]
+        {
+            named_in_memory_file.default_content_type = self.default_content_type.clone();
+            named_in_memory_file.response_timer = Some(&self.response_timer);
+            named_in_memory_file.headers = self.headers_for(&named_in_memory_file.path);
+            named_in_memory_file.max_range_multiple = self.max_range_multiple;
+        }
+        named_in_memory_file
+    }
+
+    /// Helper function that combines the cache's headers with the headers registered for a file.
+    ///
+    /// Headers registered for the file replace the cache's headers with the same name.
+    #[cfg(feature = "rocket")]
+    fn headers_for<P: AsRef<Path>>(&self, path: P) -> Vec<Header<'static>> {
+        let mut headers: Vec<Header<'static>> = self.headers.clone();
+        if let Some(path_headers) = self.path_headers.find(&path.as_ref().to_path_buf()) {
+            for header in path_headers.get().iter() {
+                headers.retain(|existing| !existing.name().eq_ignore_ascii_case(header.name()));
+                headers.push(header.clone());
+            }
+        }
+        headers
+    }
+
+    /// Helper function that calculates the priority score of a file using the priority function,
+    /// multiplied by the weight of the file's extension, if it has one.
+    fn priority_for<P: AsRef<Path>>(&self, path: P, access_count: usize, size: usize) -> usize {
+        let priority: usize = (self.priority_function)(access_count, size);
+        let weight: f64 = match path.as_ref()
+            .extension()
+            .and_then(|ext| self.extension_weights.get(&ext.to_string_lossy().to_lowercase())) {
+            Some(weight) => *weight,
+            None => return priority,
+        };
+        let weighted_priority: f64 = priority as f64 * weight;
+        if weighted_priority >= usize::MAX as f64 {
+            usize::MAX
+        } else {
+            weighted_priority as usize
+        }
+    }
+
+    /// Helper function that determines the max-age that should be used in the Cache-Control header for a file.
+    ///
+    /// A max-age registered for the file's extension takes precedence over the default max-age.
+    fn max_age_for<P: AsRef<Path>>(&self, path: P) -> Option<usize> {
+        let extension_max_age: Option<usize> = path.as_ref()
+            .extension()
+            .and_then(|ext| self.max_ages.get(&ext.to_string_lossy().to_lowercase()))
+            .cloned();
+        extension_max_age.or(self.default_max_age)
+    }
+
+    /// Helper function for incrementing the access count for a given file name.
+    ///
+    /// This should only be used in cases where the file is known to exist, to avoid bloating the access count map with useless values.
+    fn increment_access_count<P: AsRef<Path>>(&self, path: P) {
+        self.last_access_map.insert(path.as_ref().to_path_buf(), Instant::now());
+        self.access_count_map.upsert(
+            path.as_ref().to_path_buf(),
+            1, // insert 1 if nothing at key. The closure will not execute.
+            &|access_count| {
+                *access_count = match usize::checked_add(access_count.clone(), 1) {
+                    Some(v) => v, // return the incremented value
+                    None => usize::MAX, // If the access count bumps up against the usize max, keep the value the same.
+                }
+            },
+        );
+        if let Some(age_out) = self.age_out {
+            age_out.access_count.fetch_add(1, Ordering::Relaxed);
+            if age_out.access_count.load(Ordering::Relaxed) == age_out.accesses_limit {
+                self.access_count_map.iter().for_each(|x| {
+                    (age_out.age_out_function)(x.1)
+                })
+            }
+        }
+    }
+
+
+    /// Update the stats associated with this file.
+    fn update_stats<P: AsRef<Path>>(&self, path: P) {
+
+        let access_count: usize = match self.access_count_map.find(&path.as_ref().to_path_buf()) {
+            Some(access_count) => access_count.get().clone(),
+            None => 1,
+        };
+
+        let mut resized_from_metadata: Option<usize> = None;
+        self.file_map.update(path.as_ref(), |file_entry| {
+            // If the size is initialized to 0, then try to get the actual size from the filesystem
+            if file_entry.stats.size == 0 {
+                file_entry.stats.size = Self::get_file_size_from_metadata(&path.as_ref().to_path_buf()).unwrap_or(0);
+                resized_from_metadata = Some(file_entry.stats.size);
+            }
+            file_entry.stats.access_count = access_count;
+            file_entry.stats.priority = self.priority_for(&path, file_entry.stats.access_count, file_entry.stats.size); // update the priority score.
+        });
+        if let Some(new_size) = resized_from_metadata {
+            self.record_resize(0, new_size);
+        }
+
+
+    }
+
+
+
+
+
+    /// Gets a vector of tuples containing the Path, priority score, and size in bytes of all items
+    /// in the file_map.
+    ///
+    /// The vector is sorted from highest to lowest priority.
+    /// This allows the assumption that the last element to be popped from the vector will have the
+    /// lowest priority, and therefore is the most eligible candidate for elimination from the
+    /// cache.
+    ///
+    fn sorted_priorities(&self) -> Vec<(PathBuf, FileStats)> {
+
+        // The last access of each file is looked up once, instead of every time it is compared.
+        let mut priorities: Vec<(PathBuf, FileStats, Option<Instant>)> = self.file_map
+            .entries()
+            .into_iter()
+            .map(|x| {
+                let last_access: Option<Instant> = self.last_access(&x.0);
+                (x.0, x.1.stats, last_access)
+            })
+            .collect();
+
+        // Sort the priorities from highest priority to lowest, so when they are pop()ed later,
+        // the last element will have the lowest priority.
+        // Files with equal priorities are ordered so the least recently accessed one is popped first,
+        // and files that have never been accessed are popped before those that have.
+        // Any remaining ties are broken by path, so the order doesn't depend on the iteration order of the map.
+        priorities.sort_by(|l, r| {
+            r.1.priority.cmp(&l.1.priority)
+                .then_with(|| r.2.cmp(&l.2))
+                .then_with(|| r.0.cmp(&l.0))
+        });
+        priorities
+            .into_iter()
+            .map(|(path, stats, _)| (path, stats))
+            .collect()
+    }
+
+    /// Gets the files in the cache in the reverse of the order they should be considered for removal,
+    /// so the first file to consider can be popped off of the end of the vector.
+    /// Pinned files are never considered for removal, so they are left out.
+    ///
+    /// For the weighted random eviction policy, each file gets a random key of `ln(u) * (priority + 1)`,
+    /// where `u` is uniformly distributed between 0 and 1, and the files are ordered by their keys.
+    /// This samples the files without replacement, with each file weighted by the inverse of its priority.
+    fn eviction_order(&self) -> Vec<(PathBuf, FileStats)> {
+        let mut files: Vec<(PathBuf, FileStats)> = match self.eviction_policy {
+            EvictionPolicy::LowestPriority => self.sorted_priorities(),
+            EvictionPolicy::WeightedRandom => {
+                let mut files: Vec<(PathBuf, FileStats)> = self.file_map
+                    .entries()
+                    .into_iter()
+                    .map(|x| (x.0.clone(), x.1.stats.clone()))
+                    .collect();
+                // The iteration order of the file map isn't deterministic, so the files are sorted
+                // before drawing their keys, to make the order reproducible for a given seed.
+                files.sort_by(|l, r| l.0.cmp(&r.0));
+
+                let mut keyed_files: Vec<(f64, (PathBuf, FileStats))> = files
+                    .into_iter()
+                    .map(|file| (self.next_random().ln() * (file.1.priority as f64 + 1.0), file))
+                    .collect();
+                // The keys closest to 0 are sorted to the end, so those files are considered first.
+                keyed_files.sort_by(|l, r| l.0.partial_cmp(&r.0).unwrap_or(cmp::Ordering::Equal));
+                keyed_files.into_iter().map(|x| x.1).collect()
+            }
+        };
+        files.retain(|file| !self.is_pinned(&file.0));
+        // Uncompressed files whose compressed variants are cached are moved to the end, so they are considered first.
+        // The sort is stable, so they keep the order given by the eviction policy.
+        files.sort_by_key(|file| self.has_compressed_variant(&file.0));
+        files
+    }
+
+    /// Gets a random number between 0 and 1 (exclusive) using the xorshift64* generator.
+    fn next_random(&self) -> f64 {
+        let mut state = match self.random_state.lock() {
+            Ok(guard) => guard,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        // The generator would only produce 0 if it was seeded with 0.
+        let mut x: u64 = if *state == 0 { 0x9E37_79B9_7F4A_7C15 } else { *state };
+        x ^= x >> 12;
+        x ^= x << 25;
+        x ^= x >> 27;
+        *state = x;
+        let random: u64 = x.wrapping_mul(0x2545_F491_4F6C_DD1D);
+        // Use the upper 53 bits, which fit exactly in the mantissa of an f64.
+        ((random >> 11) as f64 + 0.5) / (1u64 << 53) as f64
+    }
+}
+
+
+
+#[cfg(all(test, not(feature = "rocket")))]
+mod plain_tests {
+    extern crate tempdir;
+
+    use super::*;
+
+    use self::tempdir::TempDir;
+    use std::fs::File;
+    use std::io::Write;
+
+    #[test]
+    fn cache_works_without_rocket() {
+        let cache: Cache = Cache::new(1024 * 1024);
+        let temp_dir = TempDir::new("plain").unwrap();
+        let small_path = temp_dir.path().join("small.txt");
+        let large_path = temp_dir.path().join("large.txt");
+        File::create(&small_path).unwrap().write_all(b"Hello World!").unwrap();
+        File::create(&large_path).unwrap().write_all(&vec![0u8; 1024 * 1024 * 2]).unwrap();
+
+        match cache.get(&small_path) {
+            CachedFile::InMemory(file) => assert_eq!(file.into_owned().bytes(), b"Hello World!"),
+            _ => panic!("The file should have been stored in the cache."),
+        }
+        assert!(cache.contains_key(&small_path));
+        assert_eq!(cache.used_bytes(), 12);
+
+        match cache.get(&large_path) {
+            CachedFile::FileSystem(named_file) => assert_eq!(named_file.path(), large_path.as_path()),
+            _ => panic!("The file is larger than the cache, so it should have been gotten from the filesystem."),
+        }
+        assert!(!cache.contains_key(&large_path));
+
+        assert!(cache.remove(&small_path));
+        assert_eq!(cache.used_bytes(), 0);
+    }
+}
+
+#[cfg(all(test, feature = "rocket"))]
+mod tests {
+    extern crate test;
+    extern crate tempdir;
+    extern crate rand;
+
+    use super::*;
+
+    use self::tempdir::TempDir;
+    use self::test::Bencher;
+    use self::rand::{StdRng, Rng};
+    use std::io::{Write, BufWriter};
+    use std::fs::File;
+    use rocket::response::NamedFile;
+    use std::io::Read;
+    use in_memory_file::InMemoryFile;
+    use std::mem;
+    use std::thread;
+    use std::sync::{Barrier, Once};
+    use std::cell::{RefCell, Cell};
+    use rocket::local::Client;
+    use rocket::http::{Status, Header};
+    use precompressed_file::PrecompressedFile;
+    use bundle::Bundle;
+    use localized_file::LocalizedFile;
+    use priority_function::{normal_priority_function, access_priority_function, small_files_access_priority_function};
+    use log::{Log, Record, LevelFilter};
+    use log::Metadata as LogMetadata;
+    use rocket::response::Responder;
+    use zip::{ZipWriter, CompressionMethod};
+    use zip::write::FileOptions;
+
+    const MEG1: usize = 1024 * 1024;
+    const MEG2: usize = MEG1 * 2;
+    const MEG5: usize = MEG1 * 5;
+    const MEG10: usize = MEG1 * 10;
+
+    const DIR_TEST: &'static str = "test1";
+    const FILE_MEG1: &'static str = "meg1.txt";
+    const FILE_MEG2: &'static str = "meg2.txt";
+    const FILE_MEG5: &'static str = "meg5.txt";
+    const FILE_MEG10: &'static str = "meg10.txt";
+
+    // Helper function that creates test files in a directory that is cleaned up after the test runs.
+    fn create_test_file(temp_dir: &TempDir, size: usize, name: &str) -> PathBuf {
+        let path = temp_dir.path().join(name);
+        let tmp_file = File::create(path.clone()).unwrap();
+        let mut rand_data: Vec<u8> = vec![0u8; size];
+        StdRng::new().unwrap().fill_bytes(rand_data.as_mut());
+        let mut buffer = BufWriter::new(tmp_file);
+        buffer.write(&rand_data).unwrap();
+        path
+    }
+
+
+    // Standardize the way a file is used in these tests.
+    impl<'a> CachedFile<'a> {
+        fn dummy_write(self) {
+            match self {
+                CachedFile::InMemory(cached_file) => {
+                    let mut v: Vec<u8> = Vec::new();
+                    let _ = cached_file.file.bytes.as_slice().read_to_end(&mut v).unwrap();
+                },
+                CachedFile::FileSystem(mut streamed_file) => {
+                    let mut v: Vec<u8> = Vec::new();
+                    let _ = streamed_file.read_to_end(&mut v).unwrap();
+                }
+                CachedFile::Verified(verified_file) => {
+                    let mut v: Vec<u8> = Vec::new();
+                    let _ = verified_file.file.take_file().read_to_end(&mut v).unwrap();
+                }
+                CachedFile::Owned(owned_file) => {
+                    let mut v: Vec<u8> = Vec::new();
+                    let _ = owned_file.bytes().read_to_end(&mut v).unwrap();
+                }
+                CachedFile::NotFound => {
+                    panic!("tried to write using a non-existent file")
+                }
+            }
+        }
+
+        fn get_in_memory_file(self) -> NamedInMemoryFile<'a> {
+            match self {
+                CachedFile::InMemory(n) => n,
+                _ => panic!("tried to get cached file for named file"),
+
+            }
+        }
+
+        fn get_named_file(self) -> NamedFile {
+            match self {
+                CachedFile::FileSystem(n) => n.file,
+                _ =>  panic!("tried to get cached file for named file"),
+            }
+        }
+    }
+
+    #[bench]
+    fn cache_get_10mb(b: &mut Bencher) {
+        let cache: Cache = Cache::new(MEG1 * 20); //Cache can hold 20Mb
+        let temp_dir = TempDir::new(DIR_TEST).unwrap();
+        let path_10m = create_test_file(&temp_dir, MEG10, FILE_MEG10);
+        cache.get(&path_10m); // add the 10 mb file to the cache
+
+        b.iter(|| {
+            let cached_file = cache.get(&path_10m);
+            cached_file.dummy_write()
+        });
+    }
+
+    /// Gets different files from 8 threads at once, so contention on the cache's locks shows up as a slowdown.
+    #[bench]
+    fn concurrent_get_different_files_8_threads(b: &mut Bencher) {
+        let cache: Arc<Cache> = Arc::new(Cache::new(MEG10));
+        let temp_dir = TempDir::new(DIR_TEST).unwrap();
+        let paths: Vec<PathBuf> = (0..8)
+            .map(|i| create_test_file(&temp_dir, 1024, format!("{}_1kib.txt", i).as_str()))
+            .collect();
+        for path in paths.iter() {
+            cache.get(path); // add the files to the cache
+        }
+
+        b.iter(|| {
+            let handles: Vec<thread::JoinHandle<()>> = paths
+                .iter()
+                .map(|path| {
+                    let cache = cache.clone();
+                    let path = path.clone();
+                    thread::spawn(move || {
+                        for _ in 0..1000 {
+                            cache.get(&path).dummy_write();
+                        }
+                    })
+                })
+                .collect();
+            for handle in handles {
+                handle.join().unwrap();
+            }
+        });
+    }
+
+    #[bench]
+    fn offer_with_eviction_1kb(b: &mut Bencher) {
+        let cache: Cache = Cache::new(1024 * 100);
+        cache.reseed(42);
+        let mut i: usize = 0;
+        b.iter(|| {
+            i += 1;
+            cache.touch(format!("{}.txt", i), i % 8);
+            cache.offer(format!("{}.txt", i), vec![0u8; 1024])
+        });
+    }
+
+    #[bench]
+    fn cache_miss_10mb(b: &mut Bencher) {
+        let cache: Cache = Cache::new(0);
+        let temp_dir = TempDir::new(DIR_TEST).unwrap();
+        let path_10m = create_test_file(&temp_dir, MEG10, FILE_MEG10);
+
+        b.iter(|| {
+            let cached_file = cache.get(&path_10m);
+            cached_file.dummy_write()
+        });
+    }
+
+    #[bench]
+    fn named_file_read_10mb(b: &mut Bencher) {
+        let temp_dir = TempDir::new(DIR_TEST).unwrap();
+        let path_10m = create_test_file(&temp_dir, MEG10, FILE_MEG10);
+        b.iter(|| {
+            let named_file = CachedFile::from(NamedFile::open(&path_10m).unwrap());
+            named_file.dummy_write()
+        });
+    }
+
+    #[bench]
+    fn cache_get_1mb(b: &mut Bencher) {
+        let cache: Cache = Cache::new(MEG1 * 20); //Cache can hold 20Mb
+        let temp_dir = TempDir::new(DIR_TEST).unwrap();
+        let path_1m = create_test_file(&temp_dir, MEG1, FILE_MEG1);
+        cache.get(&path_1m); // add the 10 mb file to the cache
+
+        b.iter(|| {
+            let cached_file = cache.get(&path_1m);
+            cached_file.dummy_write()
+        });
+    }
+
+    #[bench]
+    fn cache_miss_1mb(b: &mut Bencher) {
+        let cache: Cache = Cache::new(0);
+        let temp_dir = TempDir::new(DIR_TEST).unwrap();
+        let path_1m = create_test_file(&temp_dir, MEG1, FILE_MEG1);
+
+        b.iter(|| {
+            let cached_file = cache.get(&path_1m);
+            cached_file.dummy_write()
+        });
+    }
+
+    #[bench]
+    fn named_file_read_1mb(b: &mut Bencher) {
+        let temp_dir = TempDir::new(DIR_TEST).unwrap();
+        let path_1m = create_test_file(&temp_dir, MEG1, FILE_MEG1);
+
+        b.iter(|| {
+            let named_file = CachedFile::from(NamedFile::open(&path_1m).unwrap());
+            named_file.dummy_write()
+        });
+    }
+
+
+
+    #[bench]
+    fn cache_get_5mb(b: &mut Bencher) {
+        let cache: Cache = Cache::new(MEG1 * 20); //Cache can hold 20Mb
+        let temp_dir = TempDir::new(DIR_TEST).unwrap();
+        let path_5m = create_test_file(&temp_dir, MEG5, FILE_MEG5);
+        cache.get(&path_5m); // add the 10 mb file to the cache
+
+        b.iter(|| {
+            let cached_file = cache.get(&path_5m);
+            cached_file.dummy_write()
+        });
+    }
+
+    #[bench]
+    fn cache_miss_5mb(b: &mut Bencher) {
+        let cache: Cache = Cache::new(0);
+        let temp_dir = TempDir::new(DIR_TEST).unwrap();
+        let path_5m = create_test_file(&temp_dir, MEG5, FILE_MEG5);
+
+        b.iter(|| {
+            let cached_file = cache.get(&path_5m);
+            cached_file.dummy_write()
+        });
+    }
+
+    #[bench]
+    fn named_file_read_5mb(b: &mut Bencher) {
+        let temp_dir = TempDir::new(DIR_TEST).unwrap();
+        let path_5m = create_test_file(&temp_dir, MEG5, FILE_MEG5);
+
+        b.iter(|| {
+            let named_file = CachedFile::from(NamedFile::open(&path_5m).unwrap());
+            named_file.dummy_write()
+        });
+    }
+
+
+
+    // Constant time access regardless of size.
+    #[bench]
+    fn cache_get_1mb_from_1000_entry_cache(b: &mut Bencher) {
+        let temp_dir = TempDir::new(DIR_TEST).unwrap();
+        let path_1m = create_test_file(&temp_dir, MEG1, FILE_MEG1);
+        let cache: Cache = Cache::new(MEG1 * 3); //Cache can hold 3Mb
+        cache.get(&path_1m); // add the file to the cache
+
+        // Add 1024 1kib files to the cache.
+        for i in 0..1024 {
+            let path = create_test_file(&temp_dir, 1024, format!("{}_1kib.txt", i).as_str());
+            cache.get(&path);
+        }
+        // make sure that the file has a high priority.
+        cache.alter_all_access_counts(|x| x + 1 * 100000);
+
+        assert_eq!(cache.used_bytes(), MEG1 * 2);
+
+        let named_file = CachedFile::from(NamedFile::open(&path_1m).unwrap());
+
+        b.iter(|| {
+            let cached_file = cache.get(&path_1m);
+            assert!(mem::discriminant(&cached_file) != mem::discriminant(&named_file));
+            cached_file.dummy_write()
+        });
+    }
+
+    // There is a penalty for missing the cache.
+    #[bench]
+    fn cache_miss_1mb_from_1000_entry_cache(b: &mut Bencher) {
+        let temp_dir = TempDir::new(DIR_TEST).unwrap();
+        let path_1m = create_test_file(&temp_dir, MEG1, FILE_MEG1);
+        let cache: Cache = Cache::new(MEG1); //Cache can hold 1Mb
+
+        // Add 1024 1kib files to the cache.
+        for i in 0..1024 {
+            let path = create_test_file(&temp_dir, 1024, format!("{}_1kib.txt", i).as_str());
+            cache.get(&path);
+        }
+        // make sure that the file has a high priority.
+        cache.alter_all_access_counts(|x| x + 1 * 100_000_000_000_000_000);
+        let named_file = CachedFile::from(NamedFile::open(&path_1m).unwrap());
+
+        b.iter(|| {
+            let cached_file = cache.get(&path_1m);
+            assert!(mem::discriminant(&cached_file) == mem::discriminant(&named_file)); // get() in this case should only return files in the FS
+            cached_file.dummy_write()
+        });
+    }
+
+    // This is pretty much a worst-case scenario, where every file would try to be removed to make room for the new file.
+    // There is a penalty for missing the cache.
+    #[bench]
+    fn cache_miss_5mb_from_1000_entry_cache(b: &mut Bencher) {
+        let temp_dir = TempDir::new(DIR_TEST).unwrap();
+        let path_5m = create_test_file(&temp_dir, MEG5, FILE_MEG1);
+        let cache: Cache = Cache::new(MEG5); //Cache can hold 1Mb
+
+        // Add 1024 5kib files to the cache.
+        for i in 0..1024 {
+            let path = create_test_file(&temp_dir, 1024 * 5, format!("{}_5kib.txt", i).as_str());
+            cache.get(&path);
+        }
+        // make sure that the file has a high priority.
+        cache.alter_all_access_counts(|x| x + 1 * 100_000_000_000_000_000);
+        let named_file = CachedFile::from(NamedFile::open(&path_5m).unwrap());
+
+        b.iter(|| {
+            let cached_file: CachedFile = cache.get(&path_5m);
+            // Mimic what is done when the response body is set.
+            assert!(mem::discriminant(&cached_file) == mem::discriminant(&named_file));  // get() in this case should only return files in the FS
+            cached_file.dummy_write()
+        });
+    }
+
+
+    #[bench]
+    fn in_memory_file_read_10mb(b: &mut Bencher) {
+        let temp_dir = TempDir::new(DIR_TEST).unwrap();
+        let path_10m = create_test_file(&temp_dir, MEG10, FILE_MEG10);
+
+        b.iter(|| {
+            let in_memory_file = Arc::new(InMemoryFile::open(path_10m.clone()).unwrap());
+            let file: *const InMemoryFile = Arc::into_raw(in_memory_file);
+            unsafe {
+                let _ = (*file).bytes.clone();
+                let _ = Arc::from_raw(file);
+            }
+        });
+    }
+
+
+    #[test]
+    fn in_memory_file_open_odd_size() {
+        let temp_dir = TempDir::new(DIR_TEST).unwrap();
+        let size: usize = MEG1 + 12345;
+        let path = create_test_file(&temp_dir, size, "odd_size.txt");
+
+        let imf: InMemoryFile = InMemoryFile::open(path.clone()).unwrap();
+
+        let mut file_vec: Vec<u8> = Vec::new();
+        let _ = NamedFile::open(path).unwrap().read_to_end(&mut file_vec);
+        assert_eq!(imf.stats.size, size);
+        assert_eq!(imf.bytes.as_ref(), file_vec.as_slice());
+    }
+
+    #[test]
+    fn in_memory_file_open_with_capacity() {
+        let temp_dir = TempDir::new(DIR_TEST).unwrap();
+        let path_10m = create_test_file(&temp_dir, MEG10, FILE_MEG10);
+
+        let imf: InMemoryFile = InMemoryFile::open_with_capacity(path_10m.clone(), 1024 * 64).unwrap();
+
+        let mut file_vec: Vec<u8> = Vec::new();
+        let _ = NamedFile::open(&path_10m).unwrap().read_to_end(&mut file_vec);
+        assert_eq!(imf.stats.size, MEG10);
+        assert_eq!(imf.bytes.as_ref(), file_vec.as_slice());
+        // The buffer was allocated from the file's length, so it wasn't grown while the file was read.
+        assert_eq!(imf.bytes.capacity(), MEG10 + 1);
+
+        // Chunks that don't divide the file, or that are larger than it, read the same bytes.
+        for &capacity in &[1000, MEG10 * 2] {
+            let imf_chunked: InMemoryFile = InMemoryFile::open_with_capacity(&path_10m, capacity).unwrap();
+            assert_eq!(imf_chunked, imf);
+        }
+    }
+
+    #[test]
+    fn in_memory_file_clone_shares_bytes() {
+        let temp_dir = TempDir::new(DIR_TEST).unwrap();
+        let path_1m = create_test_file(&temp_dir, MEG1, FILE_MEG1);
+
+        let imf: InMemoryFile = InMemoryFile::open(path_1m).unwrap();
+        let clone: InMemoryFile = imf.clone();
+
+        assert_eq!(imf.bytes.as_ptr(), clone.bytes.as_ptr());
+        assert!(Arc::ptr_eq(&imf.bytes, &clone.bytes));
+    }
+
+    #[test]
+    fn file_exceeds_size_limit() {
+        let cache: Cache = Cache::new(MEG1 * 8); // Cache can hold only 8Mb
+        let temp_dir = TempDir::new(DIR_TEST).unwrap();
+        let path_10m = create_test_file(&temp_dir, MEG10, FILE_MEG10);
+
+        let named_file = NamedFile::open(path_10m.clone()).unwrap();
+
+        // expect the cache to get the item from the FS.
+        assert_eq!(cache.try_insert(path_10m), CachedFile::from(named_file));
+    }
+
+
+    #[test]
+    fn file_replaces_other_file() {
+        let temp_dir = TempDir::new(DIR_TEST).unwrap();
+
+        let path_1m = create_test_file(&temp_dir, MEG1, FILE_MEG1);
+        let path_5m = create_test_file(&temp_dir, MEG5, FILE_MEG5);
+
+        let named_file_1m = NamedFile::open(path_1m.clone()).unwrap();
+        let named_file_1m_2 = NamedFile::open(path_1m.clone()).unwrap();
+
+
+        let mut imf_5m = InMemoryFile::open(path_5m.clone()).unwrap();
+        let mut imf_1m = InMemoryFile::open(path_1m.clone()).unwrap();
+
+        // set expected stats for 5m
+        imf_5m.stats.access_count = 1;
+        imf_5m.stats.priority = 2289;
+
+
+        let cache: Cache = Cache::new(5500000); //Cache can hold only 5.5Mib
+
+        println!("0:\n{:#?}", cache);
+
+        assert_eq!(
+            cache
+                .try_insert(path_5m.clone())
+                .get_in_memory_file()
+                .file,
+            imf_5m
+        );
+        println!("1:\n{:#?}", cache);
+        assert_eq!(
+            cache.try_insert(path_1m.clone()),
+            CachedFile::from(named_file_1m)
+        );
+        println!("2:\n{:#?}", cache);
+        assert_eq!(
+            cache.try_insert(path_1m.clone()),
+            CachedFile::from(named_file_1m_2)
+        );
+        println!("3:\n{:#?}", cache);
+
+        // set the expected stats for 1m
+        imf_1m.stats.access_count = 3;
+        imf_1m.stats.priority = 3072;
+
+        assert_eq!(
+            cache
+                .try_insert(path_1m.clone())
+                .get_in_memory_file()
+                .file,
+            imf_1m
+        );
+        println!("4:\n{:#?}", cache);
+    }
+
+
+
+
+    #[test]
+    fn new_file_replaces_lowest_priority_file() {
+        let temp_dir = TempDir::new(DIR_TEST).unwrap();
+        let path_1m = create_test_file(&temp_dir, MEG1, FILE_MEG1);
+        let path_2m = create_test_file(&temp_dir, MEG2, FILE_MEG2);
+        let path_5m = create_test_file(&temp_dir, MEG5, FILE_MEG5);
+
+
+        #[allow(unused_variables)]
+        let named_file_1m = NamedFile::open(path_1m.clone()).unwrap();
+
+        let cache: Cache = Cache::new(MEG1 * 7 + 2000); // cache can hold a little more than 7MB
+
+        println!("1:\n{:#?}", cache);
+        let mut imf_5m: InMemoryFile = InMemoryFile::open(path_5m.clone()).unwrap();
+        imf_5m.stats.priority = 2289;
+        imf_5m.stats.access_count = 1;
+
+        assert_eq!(
+            cache.get(&path_5m)
+               .get_in_memory_file()
+               .file,
+            imf_5m
+        );
+
+        println!("2:\n{:#?}", cache);
+        let mut imf_2m: InMemoryFile = InMemoryFile::open(path_2m.clone()).unwrap();
+        imf_2m.stats.priority = 1448;
+        imf_2m.stats.access_count = 1;
+        assert_eq!(
+            cache.get(&path_2m)
+                .get_in_memory_file()
+                .file,
+            imf_2m
+        );
+
+
+        println!("3:\n{:#?}", cache);
+        let mut named_1m = NamedFile::open(path_1m.clone()).unwrap();
+        let mut v: Vec<u8> = Vec::new();
+        let _ = cache
+            .get(&path_1m)
+            .get_named_file()
+            .read_to_end(&mut v)
+            .unwrap();
+
+        let mut file_vec: Vec<u8> = Vec::new();
+        let _ = named_1m.read_to_end(&mut file_vec);
+        assert_eq!(
+            v,
+            file_vec
+        );
+
+
+        println!("4:\n{:#?}", cache);
+        let mut imf_1m: InMemoryFile = InMemoryFile::open(path_1m.clone()).unwrap();
+        imf_1m.stats.priority = 2048; // This priority is higher than the in memory file - 2m's 1448, and therefore will replace it now
+        imf_1m.stats.access_count = 1;
+
+        // The cache will now accept the 1 meg file because (sqrt(2)_size * 1_access) for the old
+        // file is less than (sqrt(1)_size * 2_access) for the new file.
+        assert_eq!(
+            cache.get(&path_1m)
+                .get_in_memory_file()
+                .file
+                .bytes,
+            imf_1m.bytes
+        );
+        println!("5:\n{:#?}", cache);
+
+
+        if let CachedFile::NotFound = cache.get_from_cache(&path_1m) {
+            panic!("Expected 1m file to be in the cache");
+        }
+
+        // Check if the 5m file is still in the cache
+        if let CachedFile::NotFound = cache.get_from_cache(&path_5m) {
+            panic!("Expected 5m file to be in the cache");
+        }
+
+        //
+        if let CachedFile::InMemory(_) = cache.get_from_cache(&path_2m) {
+            panic!("Expected 2m file to not be in the cache");
+        }
+
+        drop(cache);
+    }
+
+
+
+
+    #[test]
+    fn remove_file() {
+        let cache: Cache = Cache::new(MEG1 * 10);
+        let temp_dir = TempDir::new(DIR_TEST).unwrap();
+        let path_5m = create_test_file(&temp_dir, MEG5, FILE_MEG5);
+
+        let mut imf: InMemoryFile = InMemoryFile::open(path_5m.clone()).unwrap();
+
+        // Set the expected values for the stats in IMF.
+        imf.stats.priority = 2289;
+        imf.stats.access_count = 1;
+
+        // expect the cache to get the item from the FS.
+        assert_eq!(
+            cache
+                .get(&path_5m)
+                .get_in_memory_file()
+                .file,
+            imf
+        );
+
+        cache.remove(&path_5m);
+
+        assert_eq!(cache.contains_key(&path_5m.clone()), false);
+    }
+
+    #[test]
+    fn file_exceeding_max_file_size_is_streamed() {
+        let mut cache: Cache = Cache::new(MEG10);
+        cache.max_file_size = MEG2;
+        let temp_dir = TempDir::new(DIR_TEST).unwrap();
+        let path_5m = create_test_file(&temp_dir, MEG5, FILE_MEG5);
+
+        let named_file = NamedFile::open(path_5m.clone()).unwrap();
+
+        assert_eq!(cache.get(&path_5m), CachedFile::from(named_file));
+        assert_eq!(cache.contains_key(&path_5m), false);
+        assert_eq!(cache.used_bytes(), 0);
+    }
+
+    #[test]
+    fn file_larger_than_cache_is_streamed_without_eviction() {
+        let cache: Cache = Cache::new(MEG2);
+        let temp_dir = TempDir::new(DIR_TEST).unwrap();
+        let path_1m = create_test_file(&temp_dir, MEG1, FILE_MEG1);
+        let path_5m = create_test_file(&temp_dir, MEG5, FILE_MEG5);
+        cache.get(&path_1m);
+        assert!(cache.contains_key(&path_1m));
+
+        // However often the file is requested, it can never fit, so it doesn't replace the cached file.
+        for _ in 0..10 {
+            match cache.get(&path_5m) {
+                CachedFile::FileSystem(_) => {}
+                _ => panic!("The file should have been streamed from the filesystem."),
+            }
+        }
+        assert!(!cache.contains_key(&path_5m));
+        assert!(cache.contains_key(&path_1m));
+        assert_eq!(cache.used_bytes(), MEG1);
+        assert_eq!(cache.stats().evictions, 0);
+    }
+
+    #[test]
+    fn try_insert_returns_stored_file() {
+        let cache: Cache = Cache::new(MEG10);
+        let temp_dir = TempDir::new(DIR_TEST).unwrap();
+        let path_1m = create_test_file(&temp_dir, MEG1, FILE_MEG1);
+
+        let mut file_vec: Vec<u8> = Vec::new();
+        let _ = NamedFile::open(path_1m.clone()).unwrap().read_to_end(&mut file_vec);
+
+        assert_eq!(
+            cache
+                .try_insert(path_1m.clone())
+                .get_in_memory_file()
+                .file
+                .bytes
+                .as_ref(),
+            file_vec.as_slice()
+        );
+        assert!(cache.contains_key(&path_1m));
+    }
+
+    #[test]
+    fn crossing_high_watermark_evicts_to_low_watermark() {
+        let temp_dir = TempDir::new(DIR_TEST).unwrap();
+        let mut cache: Cache = Cache::new(MEG10);
+        cache.high_watermark = Some(MEG1 * 6);
+        cache.low_watermark = Some(MEG1 * 3);
+
+        for i in 0..7 {
+            let path = create_test_file(&temp_dir, MEG1, format!("{}_1mib.txt", i).as_str());
+            cache.get(&path);
+        }
+        assert_eq!(cache.used_bytes(), MEG1 * 7);
+        assert_eq!(cache.stats().evictions, 0);
+
+        // Getting a file that isn't cached evicts down to the low watermark before the file is stored.
+        let path = create_test_file(&temp_dir, MEG1, FILE_MEG1);
+        cache.get(&path);
+        assert_eq!(cache.used_bytes(), MEG1 * 4);
+        assert_eq!(cache.stats().evictions, 4);
+        assert!(cache.contains_key(&path));
+
+        // Inserting a new file after eviction won't need to remove anything else.
+        let path = create_test_file(&temp_dir, MEG1, FILE_MEG2);
+        cache.get(&path);
+        assert_eq!(cache.used_bytes(), MEG1 * 5);
+        assert_eq!(cache.stats().evictions, 4);
+        assert!(cache.contains_key(&path));
+    }
+
+    #[test]
+    fn evictions_are_counted() {
+        let temp_dir = TempDir::new(DIR_TEST).unwrap();
+        let cache: Cache = Cache::new(MEG1 * 3);
+
+        for i in 0..3 {
+            let path = create_test_file(&temp_dir, MEG1, format!("{}_1mib.txt", i).as_str());
+            cache.get(&path);
+        }
+        assert_eq!(cache.stats().evictions, 0);
+
+        // With the access counts of the cached files reset, a 2MB file will displace two of the 1MB files.
+        let path_2m = create_test_file(&temp_dir, MEG2, FILE_MEG2);
+        cache.alter_all_access_counts(|_| 0);
+        cache.get(&path_2m);
+
+        let stats = cache.stats();
+        assert!(cache.contains_key(&path_2m));
+        assert_eq!(stats.evictions, 2);
+        assert_eq!(stats.file_count, 2);
+        assert_eq!(stats.used_bytes, MEG1 * 3);
+    }
+
+    #[test]
+    fn priority_of_cached_file() {
+        let cache: Cache = Cache::new(MEG10);
+        let temp_dir = TempDir::new(DIR_TEST).unwrap();
+        let path_5m = create_test_file(&temp_dir, MEG5, FILE_MEG5);
+
+        assert_eq!(cache.priority_of(&path_5m), None);
+
+        cache.get(&path_5m);
+        cache.get(&path_5m);
+
+        assert_eq!(cache.priority_of(&path_5m), Some(default_priority_function(2, MEG5)));
+    }
+
+    #[test]
+    fn file_with_mismatched_checksum_is_refused() {
+        let cache: Cache = Cache::new(MEG10);
+        let temp_dir = TempDir::new(DIR_TEST).unwrap();
+        let path_1m = create_test_file(&temp_dir, MEG1, FILE_MEG1);
+        let path_2m = create_test_file(&temp_dir, MEG2, FILE_MEG2);
+
+        let imf_2m: InMemoryFile = InMemoryFile::open(path_2m.clone()).unwrap();
+        cache.set_expected_checksum(&path_1m, checksum(b"not the file's contents"));
+        cache.set_expected_checksum(&path_2m, checksum(&imf_2m.bytes));
+
+        assert_eq!(cache.get(&path_1m), CachedFile::NotFound);
+        assert_eq!(cache.contains_key(&path_1m), false);
+
+        cache.get(&path_2m);
+        assert!(cache.contains_key(&path_2m));
+    }
+
+    #[test]
+    fn concurrent_readers_record_every_access() {
+        let cache: Arc<Cache> = Arc::new(Cache::new(MEG10));
+        let temp_dir = TempDir::new(DIR_TEST).unwrap();
+        let path_1m = create_test_file(&temp_dir, MEG1, FILE_MEG1);
+        cache.get(&path_1m);
+
+        let handles: Vec<thread::JoinHandle<()>> = (0..8)
+            .map(|_| {
+                let cache = cache.clone();
+                let path = path_1m.clone();
+                thread::spawn(move || {
+                    for _ in 0..100 {
+                        cache.get(&path).dummy_write();
+                    }
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        assert_eq!(cache.priority_of(&path_1m), Some(default_priority_function(801, MEG1)));
+    }
+
+    #[test]
+    fn drain_into_another_cache() {
+        let old_cache: Cache = Cache::new(MEG10);
+        let new_cache: Cache = Cache::new(MEG10);
+        let temp_dir = TempDir::new(DIR_TEST).unwrap();
+        let path_1m = create_test_file(&temp_dir, MEG1, FILE_MEG1);
+        let path_2m = create_test_file(&temp_dir, MEG2, FILE_MEG2);
+        old_cache.get(&path_1m);
+        old_cache.get(&path_2m);
+        old_cache.get(&path_2m);
+
+        new_cache.extend(old_cache.drain());
+
+        assert_eq!(old_cache.used_bytes(), 0);
+        assert_eq!(new_cache.used_bytes(), MEG1 + MEG2);
+        assert_eq!(new_cache.priority_of(&path_2m), Some(default_priority_function(2, MEG2)));
+
+        let imf_1m: InMemoryFile = InMemoryFile::open(path_1m.clone()).unwrap();
+        assert_eq!(
+            new_cache
+                .get(&path_1m)
+                .get_in_memory_file()
+                .file
+                .bytes,
+            imf_1m.bytes
+        );
+    }
+
+    #[test]
+    fn reserve_is_kept_free() {
+        let temp_dir = TempDir::new(DIR_TEST).unwrap();
+        let mut cache: Cache = Cache::new(MEG10);
+        cache.reserve_fraction = 0.2;
+
+        // Files are inserted without removing any others until the reserve is reached.
+        for i in 0..8 {
+            let path = create_test_file(&temp_dir, MEG1, format!("{}_1mib.txt", i).as_str());
+            cache.get(&path);
+        }
+        assert_eq!(cache.used_bytes(), MEG1 * 8);
+        assert_eq!(cache.stats().evictions, 0);
+
+        // The next file must replace one of the others to keep the reserve free.
+        let path_1m = create_test_file(&temp_dir, MEG1, FILE_MEG1);
+        cache.get(&path_1m);
+        assert!(cache.contains_key(&path_1m));
+        assert_eq!(cache.used_bytes(), MEG1 * 8);
+        assert_eq!(cache.stats().evictions, 1);
+    }
+
+    #[test]
+    fn default_cache() {
+        let default_cache: Cache = Cache::default();
+        let cache: Cache = Cache::new(DEFAULT_SIZE_LIMIT);
+
+        assert_eq!(default_cache.size_limit(), cache.size_limit());
+        assert_eq!(default_cache.min_file_size, cache.min_file_size);
+        assert_eq!(default_cache.max_file_size, cache.max_file_size);
+        assert_eq!(default_cache.priority_function as usize, cache.priority_function as usize);
+        assert_eq!(default_cache.stats(), cache.stats());
+    }
+
+    #[test]
+    fn invalidate_prefix_only_removes_matching_files() {
+        let cache: Cache = Cache::new(MEG10);
+        let temp_dir = TempDir::new(DIR_TEST).unwrap();
+        fs::create_dir(temp_dir.path().join("a")).unwrap();
+        fs::create_dir(temp_dir.path().join("b")).unwrap();
+        let path_a_1m = create_test_file(&temp_dir, MEG1, "a/meg1.txt");
+        let path_a_2m = create_test_file(&temp_dir, MEG2, "a/meg2.txt");
+        let path_b_1m = create_test_file(&temp_dir, MEG1, "b/meg1.txt");
+        cache.get(&path_a_1m);
+        cache.get(&path_a_2m);
+        cache.get(&path_b_1m);
+
+        assert_eq!(cache.invalidate_prefix(temp_dir.path().join("a"), true), 2);
+
+        assert_eq!(cache.contains_key(&path_a_1m), false);
+        assert_eq!(cache.contains_key(&path_a_2m), false);
+        assert!(cache.contains_key(&path_b_1m));
+        assert!(cache.access_count_map.find(&path_a_1m).is_none());
+        assert!(cache.access_count_map.find(&path_b_1m).is_some());
+    }
+
+    #[test]
+    fn invalidate_matching_txt_files() {
+        let cache: Cache = Cache::new(MEG10);
+        let temp_dir = TempDir::new(DIR_TEST).unwrap();
+        let path_1m = create_test_file(&temp_dir, MEG1, FILE_MEG1);
+        let path_2m = create_test_file(&temp_dir, MEG2, FILE_MEG2);
+        let path_css = create_test_file(&temp_dir, MEG1, "style.css");
+        cache.get(&path_1m);
+        cache.get(&path_2m);
+        cache.get(&path_css);
+
+        let removed: usize = cache.invalidate_matching(|path| {
+            path.extension().map_or(false, |ext| ext == "txt")
+        });
+
+        assert_eq!(removed, 2);
+        assert_eq!(cache.contains_key(&path_1m), false);
+        assert_eq!(cache.contains_key(&path_2m), false);
+        assert!(cache.contains_key(&path_css));
+    }
+
+    #[test]
+    fn serve_with_custom_status() {
+        let cache: Cache = Cache::new(MEG10);
+        let temp_dir = TempDir::new(DIR_TEST).unwrap();
+        let path_1m = create_test_file(&temp_dir, MEG1, FILE_MEG1);
+        let client = Client::new(rocket::ignite()).unwrap();
+        let request = client.get("/");
+
+        let response = cache
+            .get(&path_1m)
+            .with_status(Status::ServiceUnavailable)
+            .respond_to(request.inner())
+            .unwrap();
+        assert_eq!(response.status(), Status::ServiceUnavailable);
+
+        // Files that aren't stored in the cache are streamed with the status as well.
+        let no_space: Cache = Cache::new(0);
+        let streamed_file: CachedFile = no_space.get(&path_1m).with_status(Status::ServiceUnavailable);
+        if let CachedFile::FileSystem(_) = streamed_file {
+        } else {
+            panic!("The file should have been streamed from the filesystem.")
+        }
+        let response = streamed_file.respond_to(request.inner()).unwrap();
+        assert_eq!(response.status(), Status::ServiceUnavailable);
+
+        let missing_path = temp_dir.path().join("missing.txt");
+        let error = cache
+            .get(&missing_path)
+            .with_status(Status::ServiceUnavailable)
+            .respond_to(request.inner())
+            .unwrap_err();
+        assert_eq!(error, Status::NotFound);
+    }
+
+    #[test]
+    fn custom_status_ignores_range() {
+        let cache: Cache = Cache::new(MEG10);
+        let temp_dir = TempDir::new(DIR_TEST).unwrap();
+        let path_1m = create_test_file(&temp_dir, MEG1, FILE_MEG1);
+        let client = Client::new(rocket::ignite()).unwrap();
+        let request = client.get("/").header(Header::new("Range", "bytes=0-99"));
+
+        let mut response = cache
+            .get(&path_1m)
+            .with_status(Status::ServiceUnavailable)
+            .respond_to(request.inner())
+            .unwrap();
+        assert_eq!(response.status(), Status::ServiceUnavailable);
+        assert_eq!(response.headers().get_one("Content-Range"), None);
+        assert_eq!(response.body_bytes().unwrap().len(), MEG1);
+
+        let request = client.get("/").header(Header::new("Range", "bytes=5000000-"));
+        let mut response = CachedFile::from_bytes(PathBuf::from("maintenance.html"), vec![0u8; 100])
+            .with_status(Status::ServiceUnavailable)
+            .respond_to(request.inner())
+            .unwrap();
+        assert_eq!(response.status(), Status::ServiceUnavailable);
+        assert_eq!(response.body_bytes().unwrap().len(), 100);
+    }
+
+    #[test]
+    fn etag_is_computed_once() {
+        let cache: Cache = Cache::new(MEG10);
+        let temp_dir = TempDir::new(DIR_TEST).unwrap();
+        let path_1m = create_test_file(&temp_dir, MEG1, FILE_MEG1);
+        let client = Client::new(rocket::ignite()).unwrap();
+        let request = client.get("/");
+
+        let etag: String = String::from(cache.get(&path_1m).get_in_memory_file().file.etag());
+        assert_eq!(etag, format!("\"{:016x}\"", checksum(&InMemoryFile::open(&path_1m).unwrap().bytes)));
+
+        // Altering the bytes in the cache won't change the ETag, because it isn't recalculated when responding.
+        let mut altered_bytes: Vec<u8> = InMemoryFile::open(&path_1m).unwrap().bytes.to_vec();
+        altered_bytes[0] ^= 0xFF;
+        cache.file_map.find_mut(&path_1m).unwrap().get().bytes = Arc::new(altered_bytes);
+
+        let first = cache.get(&path_1m).respond_to(request.inner()).unwrap();
+        let second = cache.get(&path_1m).respond_to(request.inner()).unwrap();
+        assert_eq!(first.headers().get_one("ETag"), Some(etag.as_str()));
+        assert_eq!(second.headers().get_one("ETag"), Some(etag.as_str()));
+    }
+
+    #[test]
+    fn small_files_are_removed_instead_of_large_file() {
+        let temp_dir = TempDir::new(DIR_TEST).unwrap();
+        let cache: Cache = Cache::new(MEG1 * 8);
+        let path_5m = create_test_file(&temp_dir, MEG5, FILE_MEG5);
+        cache.get(&path_5m);
+        for i in 0..3 {
+            let path = create_test_file(&temp_dir, MEG1, format!("{}_1mib.txt", i).as_str());
+            cache.get(&path);
+        }
+        // The 5MB file now has the lowest priority, but removing it would free far more space than is needed.
+        cache.alter_access_count(&path_5m, |_| 0);
+        assert_eq!(cache.used_bytes(), MEG1 * 8);
+
+        let path_1m = create_test_file(&temp_dir, MEG1, FILE_MEG1);
+        cache.get(&path_1m);
+
+        assert!(cache.contains_key(&path_1m));
+        assert!(cache.contains_key(&path_5m));
+        assert_eq!(cache.used_bytes(), MEG1 * 8);
+        assert_eq!(cache.stats().evictions, 1);
+    }
+
+    #[test]
+    fn accept_ranges_header_is_set() {
+        let cache: Cache = Cache::new(MEG10);
+        let temp_dir = TempDir::new(DIR_TEST).unwrap();
+        let path_1m = create_test_file(&temp_dir, MEG1, FILE_MEG1);
+        let client = Client::new(rocket::ignite()).unwrap();
+        let request = client.get("/");
+
+        let response = cache.get(&path_1m).respond_to(request.inner()).unwrap();
+        assert_eq!(response.status(), Status::Ok);
+        assert_eq!(response.headers().get_one("Accept-Ranges"), Some("bytes"));
+    }
+
+    #[test]
+    fn range_responses_share_etag_and_respect_if_range() {
+        let cache: Cache = Cache::new(MEG10);
+        let temp_dir = TempDir::new(DIR_TEST).unwrap();
+        let path_1m = create_test_file(&temp_dir, MEG1, FILE_MEG1);
+        let bytes: Vec<u8> = InMemoryFile::open(&path_1m).unwrap().bytes.to_vec();
+        let client = Client::new(rocket::ignite()).unwrap();
+
+        let request = client.get("/");
+        let full_response = cache.get(&path_1m).respond_to(request.inner()).unwrap();
+        let etag: String = full_response.headers().get_one("ETag").unwrap().to_string();
+
+        let request = client.get("/").header(Header::new("Range", "bytes=100-199"));
+        let mut response = cache.get(&path_1m).respond_to(request.inner()).unwrap();
+        assert_eq!(response.status(), Status::PartialContent);
+        assert_eq!(response.headers().get_one("ETag"), Some(etag.as_str()));
+        assert_eq!(response.headers().get_one("Content-Range").map(String::from), Some(format!("bytes 100-199/{}", MEG1)));
+        assert_eq!(response.body_bytes(), Some(bytes[100..200].to_vec()));
+
+        let request = client.get("/")
+            .header(Header::new("Range", "bytes=-10"))
+            .header(Header::new("If-Range", etag.clone()));
+        let mut response = cache.get(&path_1m).respond_to(request.inner()).unwrap();
+        assert_eq!(response.status(), Status::PartialContent);
+        assert_eq!(response.body_bytes(), Some(bytes[MEG1 - 10..].to_vec()));
+
+        let request = client.get("/")
+            .header(Header::new("Range", "bytes=100-199"))
+            .header(Header::new("If-Range", "\"stale\""));
+        let mut response = cache.get(&path_1m).respond_to(request.inner()).unwrap();
+        assert_eq!(response.status(), Status::Ok);
+        assert_eq!(response.headers().get_one("ETag"), Some(etag.as_str()));
+        assert_eq!(response.body_bytes(), Some(bytes.clone()));
+
+        let request = client.get("/").header(Header::new("Range", format!("bytes={}-", MEG1)));
+        let response = cache.get(&path_1m).respond_to(request.inner()).unwrap();
+        assert_eq!(response.status(), Status::RangeNotSatisfiable);
+    }
+
+    #[test]
+    fn overlapping_ranges_beyond_file_size_are_rejected() {
+        let mut cache: Cache = Cache::new(MEG10);
+        let temp_dir = TempDir::new(DIR_TEST).unwrap();
+        let path_1m = create_test_file(&temp_dir, MEG1, FILE_MEG1);
+        let client = Client::new(rocket::ignite()).unwrap();
+
+        let abusive_range: String = format!("bytes={}", vec!["0-"; 100].join(","));
+        {
+            let request = client.get("/").header(Header::new("Range", abusive_range.clone()));
+            let response = cache.get(&path_1m).respond_to(request.inner()).unwrap();
+            assert_eq!(response.status(), Status::RangeNotSatisfiable);
+            assert_eq!(response.headers().get_one("Content-Range").map(String::from), Some(format!("bytes */{}", MEG1)));
+
+            // Ranges within the limit are served, as the whole file when there are several of them.
+            let request = client.get("/").header(Header::new("Range", "bytes=0-99,200-299"));
+            let response = cache.get(&path_1m).respond_to(request.inner()).unwrap();
+            assert_eq!(response.status(), Status::Ok);
+        }
+
+        cache.max_range_multiple = 100;
+        let request = client.get("/").header(Header::new("Range", abusive_range));
+        let response = cache.get(&path_1m).respond_to(request.inner()).unwrap();
+        assert_eq!(response.status(), Status::Ok);
+    }
+
+    #[test]
+    fn non_ascii_range_header_is_ignored() {
+        let cache: Cache = Cache::new(MEG10);
+        let temp_dir = TempDir::new(DIR_TEST).unwrap();
+        let path_1m = create_test_file(&temp_dir, MEG1, FILE_MEG1);
+        let client = Client::new(rocket::ignite()).unwrap();
+
+        for range in &["bytesé=0-1", "byté=0-1", "é"] {
+            let request = client.get("/").header(Header::new("Range", *range));
+            let mut response = cache.get(&path_1m).respond_to(request.inner()).unwrap();
+            assert_eq!(response.status(), Status::Ok);
+            assert_eq!(response.body_bytes().map(|bytes| bytes.len()), Some(MEG1));
+        }
+    }
+
+    #[test]
+    fn concurrent_misses_read_file_once() {
+        let cache: Arc<Cache> = Arc::new(Cache::new(MEG10));
+        let temp_dir = TempDir::new(DIR_TEST).unwrap();
+        let path_5m = create_test_file(&temp_dir, MEG5, FILE_MEG5);
+        let barrier: Arc<Barrier> = Arc::new(Barrier::new(10));
+
+        let handles: Vec<thread::JoinHandle<()>> = (0..10)
+            .map(|_| {
+                let cache = cache.clone();
+                let barrier = barrier.clone();
+                let path = path_5m.clone();
+                thread::spawn(move || {
+                    barrier.wait();
+                    cache.get(&path).dummy_write();
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        assert_eq!(cache.stats().files_read, 1);
+        assert_eq!(cache.priority_of(&path_5m), Some(default_priority_function(10, MEG5)));
+        assert!(cache.in_flight.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn eviction_cost_matches_store() {
+        let temp_dir = TempDir::new(DIR_TEST).unwrap();
+        let cache: Cache = Cache::new(MEG1 * 3);
+        for i in 0..3 {
+            let path = create_test_file(&temp_dir, MEG1, format!("{}_1mib.txt", i).as_str());
+            cache.get(&path);
+        }
+        let path_2m = create_test_file(&temp_dir, MEG2, FILE_MEG2);
+        let path_5m = create_test_file(&temp_dir, MEG5, FILE_MEG5);
+
+        // The cached files have a higher priority than a new file.
+        assert_eq!(cache.eviction_cost(&path_2m, MEG2), None);
+        // The file is larger than the cache.
+        assert_eq!(cache.eviction_cost(&path_5m, MEG5), None);
+
+        cache.alter_all_access_counts(|_| 0);
+        let predicted_cost: usize = cache.eviction_cost(&path_2m, MEG2).unwrap();
+
+        let used_bytes_before: usize = cache.used_bytes();
+        cache.get(&path_2m);
+        assert!(cache.contains_key(&path_2m));
+        let freed: usize = used_bytes_before + MEG2 - cache.used_bytes();
+
+        assert_eq!(predicted_cost, MEG2);
+        assert_eq!(predicted_cost, freed);
+    }
+
+    #[test]
+    fn max_age_depends_on_extension() {
+        let mut cache: Cache = Cache::new(MEG10);
+        cache.max_ages.insert(String::from("png"), 60 * 60 * 24 * 365);
+        cache.default_max_age = Some(60);
+        let temp_dir = TempDir::new(DIR_TEST).unwrap();
+        let path_html = create_test_file(&temp_dir, 1024, "index.html");
+        let path_png = create_test_file(&temp_dir, 1024, "image.png");
+        let client = Client::new(rocket::ignite()).unwrap();
+        let request = client.get("/");
+
+        let html_response = cache.get(&path_html).respond_to(request.inner()).unwrap();
+        assert_eq!(html_response.headers().get_one("Cache-Control"), Some("max-age=60"));
+
+        let png_response = cache.get(&path_png).respond_to(request.inner()).unwrap();
+        assert_eq!(png_response.headers().get_one("Cache-Control"), Some("max-age=31536000"));
+    }
+
+    #[test]
+    fn max_age_is_set_for_files_streamed_from_the_filesystem() {
+        let mut cache: Cache = Cache::new(MEG1);
+        cache.max_ages.insert(String::from("png"), 60 * 60 * 24 * 365);
+        cache.default_max_age = Some(60);
+        let temp_dir = TempDir::new(DIR_TEST).unwrap();
+        let path_html = create_test_file(&temp_dir, MEG2, "index.html");
+        let path_png = create_test_file(&temp_dir, MEG2, "image.png");
+        cache.set_expected_checksum(&path_png, checksum(&InMemoryFile::open(&path_png).unwrap().bytes));
+        let client = Client::new(rocket::ignite()).unwrap();
+        let request = client.get("/");
+
+        // The files are too large to be cached, so one is streamed, and the other is verified as it is streamed.
+        let html_file = cache.get(&path_html);
+        match html_file {
+            CachedFile::FileSystem(ref streamed_file) => assert_eq!(streamed_file.max_age(), Some(60)),
+            _ => panic!("The file should have been served as the FileSystem variant."),
+        }
+        let html_response = html_file.respond_to(request.inner()).unwrap();
+        assert_eq!(html_response.headers().get_one("Cache-Control"), Some("max-age=60"));
+
+        let png_file = cache.get(&path_png);
+        match png_file {
+            CachedFile::Verified(ref verified_file) => assert_eq!(verified_file.max_age(), Some(31536000)),
+            _ => panic!("The file should have been served as the Verified variant."),
+        }
+        let png_response = png_file.respond_to(request.inner()).unwrap();
+        assert_eq!(png_response.headers().get_one("Cache-Control"), Some("max-age=31536000"));
+    }
+
+    #[test]
+    fn size_limit_getter() {
+        let cache: Cache = Cache::new(MEG5);
+        assert_eq!(cache.size_limit(), MEG5);
+    }
+
+    #[test]
+    fn size_limit_of_shared_cache_can_be_set() {
+        let cache: Arc<Cache> = Arc::new(Cache::new(MEG10));
+        let temp_dir = TempDir::new(DIR_TEST).expect("Couldn't create temp dir");
+        let path_5m: PathBuf = create_test_file(&temp_dir, MEG5, FILE_MEG5);
+
+        cache.set_size_limit(MEG2);
+        assert_eq!(cache.size_limit(), MEG2);
+        cache.get(&path_5m).dummy_write();
+        assert!(!cache.contains_key(&path_5m));
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn shared_cache_is_fit_to_memory_periodically() {
+        let cache: Arc<Cache> = Arc::new(Cache::new(0));
+        let size_limit: usize = cache.fit_to_memory(0.1).unwrap();
+        assert!(size_limit > 0);
+        assert_eq!(cache.size_limit(), size_limit);
+
+        cache.set_size_limit(0);
+        Cache::fit_to_memory_periodically(&cache, 0.1, Duration::from_millis(10));
+        let deadline: Instant = Instant::now() + Duration::from_secs(5);
+        while cache.size_limit() == 0 && Instant::now() < deadline {
+            thread::sleep(Duration::from_millis(10));
+        }
+        assert!(cache.size_limit() > 0);
+    }
+
+    #[test]
+    fn default_content_type_for_unknown_extension() {
+        let mut cache: Cache = Cache::new(MEG10);
+        cache.default_content_type = Some(ContentType::Binary);
+        let temp_dir = TempDir::new(DIR_TEST).unwrap();
+        let path_no_ext = create_test_file(&temp_dir, 1024, "no_extension");
+        let path_txt = create_test_file(&temp_dir, 1024, FILE_MEG1);
+        let client = Client::new(rocket::ignite()).unwrap();
+        let request = client.get("/");
+
+        let response = cache.get(&path_no_ext).respond_to(request.inner()).unwrap();
+        assert_eq!(response.content_type(), Some(ContentType::Binary));
+
+        let response = cache.get(&path_txt).respond_to(request.inner()).unwrap();
+        assert_eq!(response.content_type(), Some(ContentType::Plain));
+    }
+
+    #[test]
+    fn concurrent_access_to_different_files() {
+        let cache: Arc<Cache> = Arc::new(Cache::new(MEG10));
+        let temp_dir = TempDir::new(DIR_TEST).unwrap();
+        let paths: Vec<PathBuf> = (0..8)
+            .map(|i| create_test_file(&temp_dir, 1024, format!("{}_1kib.txt", i).as_str()))
+            .collect();
+
+        let handles: Vec<thread::JoinHandle<()>> = paths
+            .iter()
+            .map(|path| {
+                let cache = cache.clone();
+                let path = path.clone();
+                thread::spawn(move || {
+                    for _ in 0..100 {
+                        cache.get(&path).dummy_write();
                     }
-                }
-            }
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        for path in paths.iter() {
+            assert_eq!(cache.priority_of(path), Some(default_priority_function(100, 1024)));
         }
     }
 
-    /// Gets a file from the filesystem and converts it to a CachedFile.
-    ///
-    /// This should be used when the cache knows that the new file won't make it into the cache.
-    fn get_file_from_fs< P: AsRef<Path>>(&self, path: P) -> CachedFile{
-        debug!("File does not fit size constraints of the cache.");
-        match NamedFile::open(path.as_ref().to_path_buf()) {
-            Ok(named_file) => {
-                self.increment_access_count(path);
-                return CachedFile::from(named_file);
-            }
-            Err(_) => return CachedFile::NotFound
+    #[test]
+    fn serve_gzip_sidecar() {
+        let cache: Cache = Cache::new(MEG10);
+        let temp_dir = TempDir::new(DIR_TEST).unwrap();
+        let path_js = create_test_file(&temp_dir, 2048, "app.js");
+        let path_gz = create_test_file(&temp_dir, 1024, "app.js.gz");
+        let client = Client::new(rocket::ignite()).unwrap();
+
+        let request = client.get("/").header(Header::new("Accept-Encoding", "deflate, gzip;q=1.0"));
+        let mut response = PrecompressedFile::open(&path_js, &cache).respond_to(request.inner()).unwrap();
+        assert_eq!(response.headers().get_one("Content-Encoding"), Some("gzip"));
+        assert_eq!(response.content_type(), Some(ContentType::JavaScript));
+        assert_eq!(response.body_bytes(), Some(InMemoryFile::open(&path_gz).unwrap().bytes.to_vec()));
+        assert!(cache.contains_key(&path_gz));
+
+        let request = client.get("/");
+        let mut response = PrecompressedFile::open(&path_js, &cache).respond_to(request.inner()).unwrap();
+        assert_eq!(response.headers().get_one("Content-Encoding"), None);
+        assert_eq!(response.content_type(), Some(ContentType::JavaScript));
+        assert_eq!(response.body_bytes(), Some(InMemoryFile::open(&path_js).unwrap().bytes.to_vec()));
+    }
+
+    #[test]
+    fn touched_file_is_favored() {
+        let temp_dir = TempDir::new(DIR_TEST).unwrap();
+        let cache: Cache = Cache::new(MEG1 * 3);
+        for i in 0..3 {
+            let path = create_test_file(&temp_dir, MEG1, format!("{}_1mib.txt", i).as_str());
+            cache.get(&path);
         }
+        let path_2m = create_test_file(&temp_dir, MEG2, FILE_MEG2);
+
+        cache.touch(&path_2m, 10);
+        assert_eq!(cache.contains_key(&path_2m), false);
+
+        cache.get(&path_2m);
+        assert!(cache.contains_key(&path_2m));
+        assert_eq!(cache.priority_of(&path_2m), Some(default_priority_function(11, MEG2)));
     }
 
-    /// Reads a file from the filesystem into memory and stores it in the cache.
-    ///
-    /// This is the slowest operation the cache can perform, slower than just getting the file.
-    /// It should only be used when the cache decides to store the file.
-    fn get_file_from_fs_and_add_to_cache<P: AsRef<Path>>(&self, path: P) -> CachedFile {
-        debug!("Cache has room for the file.");
-        match InMemoryFile::open(&path) {
-            Ok(file) => {
-                self.file_map.insert(path.as_ref().to_path_buf(), file);
+    #[test]
+    fn origin_of_cold_and_warm_requests() {
+        let cache: Cache = Cache::new(MEG10);
+        let temp_dir = TempDir::new(DIR_TEST).unwrap();
+        let path_1m = create_test_file(&temp_dir, MEG1, FILE_MEG1);
 
-                self.increment_access_count(&path);
-                self.update_stats(&path);
+        let (cold_file, cold_origin) = cache.get_with_origin(&path_1m);
+        assert_eq!(cold_origin, Origin::Filesystem);
+        drop(cold_file);
 
-                let cache_file_accessor = match self.file_map.find(path.as_ref()) {
-                    Some(accessor_to_file) => accessor_to_file,
-                    None => {
-                        // If for whatever reason, a concurrent remove operation removes the file
-                        // before it can be gotten via an accessor lock, recursively try to add
-                        // the file to the Cache until the lock can be attained.
-                        warn!("Tried to add file to cache, but it was removed before it could be added. Attempting to get file again.");
-                        // Because this recursion only occurs under extremely rare circumstances
-                        // due to a concurrent removal of the file being added between the insertion
-                        // into the map, and locking an accessor, a stack overflow is almost impossible.
-                        return self.get_file_from_fs_and_add_to_cache(path);
-                    }
-                };
+        let (warm_file, warm_origin) = cache.get_with_origin(&path_1m);
+        assert_eq!(warm_origin, Origin::Cache);
+        warm_file.get_in_memory_file();
+    }
 
-                let cached_file: NamedInMemoryFile = NamedInMemoryFile::new(
-                    path.as_ref().to_path_buf(),
-                    cache_file_accessor
-                );
+    #[test]
+    fn origin_of_refreshed_and_passthrough_requests() {
+        let mut cache: Cache = Cache::new(MEG10);
+        let temp_dir = TempDir::new(DIR_TEST).unwrap();
+        let path_1m = create_test_file(&temp_dir, MEG1, FILE_MEG1);
+        cache.get(&path_1m);
+        let files_read: u64 = cache.files_read.load(Ordering::SeqCst);
+
+        // A file that has outlived the ttl is read from the filesystem again, even though it was in the cache.
+        cache.ttl = Some(Duration::from_millis(1));
+        thread::sleep(Duration::from_millis(10));
+        let (_, origin) = cache.get_with_origin(&path_1m);
+        assert_eq!(origin, Origin::Filesystem);
+        assert_eq!(cache.files_read.load(Ordering::SeqCst), files_read + 1);
+        cache.ttl = None;
+
+        // A file that is due to be refreshed is also read from the filesystem.
+        cache.accesses_per_refresh = Some(1);
+        let (_, origin) = cache.get_with_origin(&path_1m);
+        assert_eq!(origin, Origin::Filesystem);
+        cache.accesses_per_refresh = None;
+        let (_, origin) = cache.get_with_origin(&path_1m);
+        assert_eq!(origin, Origin::Cache);
+
+        // Passthrough always reads files from the filesystem, even if they are in the cache.
+        cache.passthrough = true;
+        assert!(cache.contains_key(&path_1m));
+        let (cached_file, origin) = cache.get_with_origin(&path_1m);
+        assert_eq!(origin, Origin::Filesystem);
+        match cached_file {
+            CachedFile::FileSystem(_) => {}
+            _ => panic!("The file should have been streamed from the filesystem."),
+        }
+    }
 
-                return CachedFile::from(cached_file);
-            }
-            Err(_) => return CachedFile::NotFound,
+    #[test]
+    fn serve_preferred_sidecar_encoding() {
+        let cache: Cache = Cache::new(MEG10);
+        let temp_dir = TempDir::new(DIR_TEST).unwrap();
+        let path_json = create_test_file(&temp_dir, 4096, "data.json");
+        let path_gz = create_test_file(&temp_dir, 2048, "data.json.gz");
+        let path_zst = create_test_file(&temp_dir, 1024, "data.json.zst");
+        let client = Client::new(rocket::ignite()).unwrap();
+
+        let request = client.get("/").header(Header::new("Accept-Encoding", "zstd"));
+        let mut response = PrecompressedFile::open(&path_json, &cache).respond_to(request.inner()).unwrap();
+        assert_eq!(response.headers().get_one("Content-Encoding"), Some("zstd"));
+        assert_eq!(response.content_type(), Some(ContentType::JSON));
+        assert_eq!(response.body_bytes(), Some(InMemoryFile::open(&path_zst).unwrap().bytes.to_vec()));
+
+        let request = client.get("/").header(Header::new("Accept-Encoding", "zstd;q=0.1, gzip"));
+        let mut response = PrecompressedFile::open(&path_json, &cache).respond_to(request.inner()).unwrap();
+        assert_eq!(response.headers().get_one("Content-Encoding"), Some("gzip"));
+        assert_eq!(response.body_bytes(), Some(InMemoryFile::open(&path_gz).unwrap().bytes.to_vec()));
+
+        let request = client.get("/").header(Header::new("Accept-Encoding", "zstd;q=0, identity"));
+        let response = PrecompressedFile::open(&path_json, &cache).respond_to(request.inner()).unwrap();
+        assert_eq!(response.headers().get_one("Content-Encoding"), None);
+    }
+
+    #[test]
+    fn cached_files_are_compressed_lazily_with_zstd() {
+        let mut cache: Cache = Cache::new(MEG10);
+        cache.zstd_level = Some(3);
+        let temp_dir = TempDir::new(DIR_TEST).unwrap();
+        let path_json: PathBuf = temp_dir.path().join("data.json");
+        let path_zst: PathBuf = temp_dir.path().join("data.json.zst");
+        let contents: Vec<u8> = "{\"id\": 1, \"name\": \"rocket\"}\n".repeat(1000).into_bytes();
+        File::create(&path_json).unwrap().write_all(&contents).unwrap();
+        let client = Client::new(rocket::ignite()).unwrap();
+        let request = client.get("/").header(Header::new("Accept-Encoding", "gzip;q=0.5, zstd"));
+
+        // The file isn't in the cache yet, so it is served uncompressed and stored.
+        let response = PrecompressedFile::open(&path_json, &cache).respond_to(request.inner()).unwrap();
+        assert_eq!(response.headers().get_one("Content-Encoding"), None);
+        assert!(!cache.contains_key(&path_zst));
+
+        for _ in 0..2 {
+            let mut response = PrecompressedFile::open(&path_json, &cache).respond_to(request.inner()).unwrap();
+            assert_eq!(response.headers().get_one("Content-Encoding"), Some("zstd"));
+            assert_eq!(response.content_type(), Some(ContentType::JSON));
+            let body: Vec<u8> = response.body_bytes().unwrap();
+            assert!(body.len() < contents.len());
+            assert_eq!(zstd::decode_all(&body[..]).unwrap(), contents);
         }
+        assert!(cache.contains_key(&path_zst));
+        assert_eq!(cache.used_bytes(), contents.len() + cache.file_map.find(&path_zst).unwrap().get().stats.size);
+
+        // Once the file is read again with different contents, the compressed variant is replaced.
+        let changed_contents: Vec<u8> = "{\"id\": 2}\n".repeat(1000).into_bytes();
+        File::create(&path_json).unwrap().write_all(&changed_contents).unwrap();
+        cache.refresh(&path_json);
+        let mut response = PrecompressedFile::open(&path_json, &cache).respond_to(request.inner()).unwrap();
+        assert_eq!(response.headers().get_one("Content-Encoding"), Some("zstd"));
+        assert_eq!(zstd::decode_all(&response.body_bytes().unwrap()[..]).unwrap(), changed_contents);
+
+        // Without a level, the cache doesn't compress files.
+        let cache: Cache = Cache::new(MEG10);
+        cache.get(&path_json);
+        let response = PrecompressedFile::open(&path_json, &cache).respond_to(request.inner()).unwrap();
+        assert_eq!(response.headers().get_one("Content-Encoding"), None);
     }
 
+    #[test]
+    fn compression_dictionary_shrinks_similar_files() {
+        let mut cache: Cache = Cache::new(MEG10);
+        cache.zstd_level = Some(3);
+        let fragment = |id: usize| -> Vec<u8> {
+            format!(
+                "{{\"id\": {}, \"kind\": \"thumbnail\", \"width\": 128, \"height\": 128, \"format\": \"webp\", \"tags\": [\"gallery\", \"preview\"]}}",
+                id
+            ).into_bytes()
+        };
+        let dictionary: Vec<u8> = (0..10).flat_map(|id| fragment(id)).collect();
 
+        let files: Vec<Vec<u8>> = (100..110).map(|id| fragment(id)).collect();
+        let without_dictionary: Vec<Vec<u8>> = files.iter().map(|file| cache.zstd_compress(file, 3).unwrap()).collect();
+        cache.compression_dictionary = Some(dictionary.clone());
+        let with_dictionary: Vec<Vec<u8>> = files.iter().map(|file| cache.zstd_compress(file, 3).unwrap()).collect();
 
-    /// Remove the n lowest priority files to make room for a file with a size: required_space.
-    ///
-    /// If this returns an OK, this function has removed the required file space from the file_map.
-    /// If this returns an Err, then either not enough space could be freed, or the priority of
-    /// files that would need to be freed to make room for the new file is greater than the
-    /// new file's priority, and as result no memory was freed.
-    ///
-    /// # Arguments
-    ///
-    /// * `required_space` - A `usize` representing the number of bytes that must be freed to make room for a new file.
-    /// * `new_file_priority` - A `usize` representing the priority of the new file to be added. If the priority of the files possibly being removed
-    /// is greater than this value, then the files won't be removed.
-    fn make_room_for_new_file(&self, required_space: usize, new_file_priority: usize) -> Result<Vec<PathBuf>, CacheError> {
-        let mut possibly_freed_space: usize = 0;
-        let mut priority_score_to_free: usize = 0;
-        let mut file_paths_to_remove: Vec<PathBuf> = vec![];
+        let total = |compressed: &Vec<Vec<u8>>| compressed.iter().map(|bytes| bytes.len()).sum::<usize>();
+        assert!(total(&with_dictionary) < total(&without_dictionary));
 
-        let mut stats: Vec<(PathBuf, FileStats)> = self.sorted_priorities();
-        while possibly_freed_space < required_space {
-            // pop the priority group with the lowest priority off of the vector
-            match stats.pop() {
-                Some(lowest) => {
-                    let (lowest_key, lowest_stats) = lowest;
-
-                    possibly_freed_space += lowest_stats.size;
-                    priority_score_to_free += lowest_stats.priority;
-                    file_paths_to_remove.push(lowest_key.clone());
-
-                    // Check if total priority to free is greater than the new file's priority,
-                    // If it is, then don't free the files, as they in aggregate, are more important
-                    // than the new file.
-                    if priority_score_to_free > new_file_priority {
-                        return Err(CacheError::NewPriorityIsNotHighEnough);
-                    }
-                }
-                None => return Err(CacheError::NoMoreFilesToRemove),
-            };
+        let mut decompressor = zstd::bulk::Decompressor::with_dictionary(&dictionary).unwrap();
+        for (file, compressed) in files.iter().zip(with_dictionary.iter()) {
+            assert!(compressed.len() < without_dictionary[0].len());
+            assert_eq!(&decompressor.decompress(compressed, file.len()).unwrap(), file);
+        }
+    }
+
+    #[test]
+    fn on_evict_is_called_with_evicted_file() {
+        let temp_dir = TempDir::new(DIR_TEST).unwrap();
+        let mut cache: Cache = Cache::new(MEG1 * 3);
+        let evicted_bytes: Arc<AtomicUsize> = Arc::new(AtomicUsize::new(0));
+        let evicted_paths: Arc<Mutex<Vec<PathBuf>>> = Arc::new(Mutex::new(vec![]));
+        {
+            let evicted_bytes = evicted_bytes.clone();
+            let evicted_paths = evicted_paths.clone();
+            cache.on_evict = Some(Box::new(move |path: &PathBuf, file: &InMemoryFile| {
+                evicted_bytes.fetch_add(file.bytes.len(), Ordering::SeqCst);
+                evicted_paths.lock().unwrap().push(path.clone());
+            }));
         }
-        Ok(file_paths_to_remove)
+        let paths: Vec<PathBuf> = (0..3)
+            .map(|i| create_test_file(&temp_dir, MEG1, format!("{}_1mib.txt", i).as_str()))
+            .collect();
+        for path in paths.iter() {
+            cache.get(path);
+        }
+        cache.alter_access_count(&paths[0], |_| 0);
+
+        let path_1m = create_test_file(&temp_dir, MEG1, FILE_MEG1);
+        cache.get(&path_1m);
 
+        assert!(cache.contains_key(&path_1m));
+        assert_eq!(evicted_bytes.load(Ordering::SeqCst), MEG1);
+        assert_eq!(*evicted_paths.lock().unwrap(), vec![paths[0].clone()]);
     }
 
-    ///Helper function that gets the file from the cache if it exists there.
-    fn get_from_cache<P: AsRef<Path>>(&self, path: P) -> CachedFile {
-        match self.file_map.find(&path.as_ref().to_path_buf()) {
-            Some(in_memory_file) => {
-                trace!("Found file: {:?} in cache.", path.as_ref());
-                CachedFile::from(NamedInMemoryFile::new(
-                    path.as_ref().to_path_buf(),
-                    in_memory_file,
-                ))
-            }
-            None => CachedFile::NotFound,
+    #[test]
+    fn generator_only_runs_on_miss() {
+        let cache: Cache = Cache::new(MEG10);
+        let key: PathBuf = PathBuf::from("rendered/index.html");
+        let generated_count: Cell<usize> = Cell::new(0);
+        let generate = || {
+            generated_count.set(generated_count.get() + 1);
+            Ok(b"<h1>Hello World!</h1>".to_vec())
+        };
+
+        for _ in 0..3 {
+            let bytes: Vec<u8> = cache
+                .get_or_compute(&key, &generate)
+                .unwrap()
+                .get_in_memory_file()
+                .file
+                .bytes
+                .to_vec();
+            assert_eq!(bytes, b"<h1>Hello World!</h1>".to_vec());
         }
 
+        assert_eq!(generated_count.get(), 1);
+        assert_eq!(cache.priority_of(&key), Some(default_priority_function(3, 21)));
+
+        let error = cache.get_or_compute(PathBuf::from("rendered/too_large.html"), || Ok(vec![0u8; MEG10 + 1]));
+        assert!(error.is_err());
     }
 
-    /// Helper function for incrementing the access count for a given file name.
-    ///
-    /// This should only be used in cases where the file is known to exist, to avoid bloating the access count map with useless values.
-    fn increment_access_count<P: AsRef<Path>>(&self, path: P) {
-        self.access_count_map.upsert(
-            path.as_ref().to_path_buf(),
-            1, // insert 1 if nothing at key. The closure will not execute.
-            &|access_count| {
-                *access_count = match usize::checked_add(access_count.clone(), 1) {
-                    Some(v) => v, // return the incremented value
-                    None => usize::MAX, // If the access count bumps up against the usize max, keep the value the same.
-                }
-            },
-        );
-        if let Some(age_out) = self.age_out {
-            age_out.access_count.fetch_add(1, Ordering::Relaxed);
-            if age_out.access_count.load(Ordering::Relaxed) == age_out.accesses_limit {
-                self.access_count_map.iter().for_each(|x| {
-                    (age_out.age_out_function)(x.1)
+    #[test]
+    fn generated_content_is_not_stored_when_pinned_files_fill_the_cache() {
+        let cache: Cache = Cache::new(MEG2);
+        for key in &["rendered/a.html", "rendered/b.html"] {
+            cache.pin(key);
+            cache.get_or_compute(key, || Ok(vec![0u8; MEG1])).unwrap();
+        }
+        assert_eq!(cache.used_bytes(), MEG2);
+
+        let generated_count: Cell<usize> = Cell::new(0);
+        for _ in 0..2 {
+            let cached_file = cache
+                .get_or_compute("rendered/c.html", || {
+                    generated_count.set(generated_count.get() + 1);
+                    Ok(vec![1u8; MEG1])
                 })
+                .unwrap();
+            match cached_file {
+                CachedFile::Owned(_) => {}
+                _ => panic!("The generated content should have been served without being stored."),
             }
+            assert_eq!(cached_file.into_bytes().unwrap(), vec![1u8; MEG1]);
         }
-    }
 
+        assert_eq!(generated_count.get(), 2);
+        assert!(!cache.contains_key("rendered/c.html"));
+        assert!(cache.contains_key("rendered/a.html"));
+        assert!(cache.contains_key("rendered/b.html"));
+        assert_eq!(cache.used_bytes(), MEG2);
+        assert_eq!(cache.stats().evictions, 0);
+    }
 
-    /// Update the stats associated with this file.
-    fn update_stats<P: AsRef<Path>>(&self, path: P) {
+    #[test]
+    fn cold_generated_content_does_not_evict_hot_file() {
+        let cache: Cache = Cache::new(MEG2);
+        let temp_dir = TempDir::new(DIR_TEST).expect("Couldn't create temp dir");
+        let path_2m: PathBuf = create_test_file(&temp_dir, MEG2, FILE_MEG2);
+        for _ in 0..5 {
+            cache.get(&path_2m);
+        }
+        assert!(cache.contains_key(&path_2m));
 
-        let access_count: usize = match self.access_count_map.find(&path.as_ref().to_path_buf()) {
-            Some(access_count) => access_count.get().clone(),
-            None => 1,
-        };
+        let cached_file = cache.get_or_compute("rendered/cold.html", || Ok(vec![1u8; MEG1])).unwrap();
+        match cached_file {
+            CachedFile::Owned(_) => {}
+            _ => panic!("The cold generated content should have been served without being stored."),
+        }
+        assert_eq!(cached_file.into_bytes().unwrap(), vec![1u8; MEG1]);
 
-        self.file_map.upsert(
-            // Key
-            path.as_ref().to_path_buf(),
-            // Default Value
-            InMemoryFile {
-                bytes: Vec::new(),
-                stats: FileStats {
-                    size: 0,
-                    access_count: 0,
-                    priority: 0,
-                },
-            },
-            // Update Function
-            &|file_entry| {
-                // If the size is initialized to 0, then try to get the actual size from the filesystem
-                if file_entry.stats.size == 0 {
-                    file_entry.stats.size = Cache::get_file_size_from_metadata(&path.as_ref().to_path_buf()).unwrap_or(0);
-                }
-                file_entry.stats.access_count = access_count;
-                file_entry.stats.priority = (self.priority_function)(file_entry.stats.access_count, file_entry.stats.size); // update the priority score.
-            },
-        );
+        assert!(!cache.contains_key("rendered/cold.html"));
+        assert!(cache.contains_key(&path_2m));
+        assert_eq!(cache.stats().evictions, 0);
+    }
 
+    #[test]
+    fn encoded_variant_has_distinct_etag() {
+        let cache: Cache = Cache::new(MEG10);
+        let temp_dir = TempDir::new(DIR_TEST).unwrap();
+        let path_css = create_test_file(&temp_dir, 2048, "style.css");
+        let path_gz = create_test_file(&temp_dir, 1024, "style.css.gz");
+        let client = Client::new(rocket::ignite()).unwrap();
+
+        let request = client.get("/");
+        let identity_response = PrecompressedFile::open(&path_css, &cache).respond_to(request.inner()).unwrap();
+
+        let request = client.get("/").header(Header::new("Accept-Encoding", "gzip"));
+        let gzip_response = PrecompressedFile::open(&path_css, &cache).respond_to(request.inner()).unwrap();
+
+        let gzip_etag: String = InMemoryFile::open(&path_gz).unwrap().etag().trim_end_matches('"').to_string() + "-gzip\"";
+        assert_eq!(gzip_response.headers().get_one("ETag"), Some(gzip_etag.as_str()));
+        assert_eq!(identity_response.headers().get_one("ETag"), Some(InMemoryFile::open(&path_css).unwrap().etag()));
+        assert_ne!(identity_response.headers().get_one("ETag"), gzip_response.headers().get_one("ETag"));
+        assert_eq!(identity_response.headers().get_one("Vary"), Some("Accept-Encoding"));
+        assert_eq!(gzip_response.headers().get_one("Vary"), Some("Accept-Encoding"));
+    }
 
+    #[test]
+    fn parallel_preload_matches_serial_preload() {
+        let temp_dir = TempDir::new(DIR_TEST).unwrap();
+        create_test_file(&temp_dir, MEG1, "a.txt");
+        create_test_file(&temp_dir, MEG1, "b.txt");
+        create_test_file(&temp_dir, MEG1, "c.txt");
+        create_test_file(&temp_dir, MEG2, "d.txt");
+        fs::create_dir(temp_dir.path().join("nested")).unwrap();
+        create_test_file(&temp_dir, MEG2, "nested/e.txt");
+
+        let serial_cache: Cache = Cache::new(MEG5);
+        let parallel_cache: Cache = Cache::new(MEG5);
+        let serially_stored: usize = serial_cache.preload_dir(temp_dir.path()).unwrap();
+        let parallel_stored: usize = parallel_cache.preload_dir_parallel(temp_dir.path(), 4).unwrap();
+
+        assert_eq!(serially_stored, 3);
+        assert_eq!(parallel_stored, serially_stored);
+        assert_eq!(serial_cache.used_bytes(), MEG5);
+        assert_eq!(parallel_cache.used_bytes(), MEG5);
+        // The larger files have a higher priority, so they are stored first.
+        assert!(parallel_cache.contains_key(temp_dir.path().join("d.txt")));
+        assert!(parallel_cache.contains_key(temp_dir.path().join("nested/e.txt")));
+
+        let mut serial_keys: Vec<PathBuf> = serial_cache.file_map.iter().map(|x| x.0.clone()).collect();
+        let mut parallel_keys: Vec<PathBuf> = parallel_cache.file_map.iter().map(|x| x.0.clone()).collect();
+        serial_keys.sort();
+        parallel_keys.sort();
+        assert_eq!(serial_keys, parallel_keys);
     }
 
+    #[test]
+    fn size_extremes_of_cached_files() {
+        let cache: Cache = Cache::new(MEG10);
+        assert_eq!(cache.size_extremes(), None);
 
+        let temp_dir = TempDir::new(DIR_TEST).unwrap();
+        let path_1m = create_test_file(&temp_dir, MEG1, FILE_MEG1);
+        let path_2m = create_test_file(&temp_dir, MEG2, FILE_MEG2);
+        let path_5m = create_test_file(&temp_dir, MEG5, FILE_MEG5);
+        cache.get(&path_2m);
+        cache.get(&path_5m);
+        cache.get(&path_1m);
 
+        assert_eq!(cache.size_extremes(), Some((path_5m, MEG5, path_1m, MEG1)));
+    }
 
+    #[test]
+    fn passthrough_always_reads_from_filesystem() {
+        let mut cache: Cache = Cache::new(MEG10);
+        cache.passthrough = true;
+        let temp_dir = TempDir::new(DIR_TEST).unwrap();
+        let path = temp_dir.path().join("index.html");
+        let client = Client::new(rocket::ignite()).unwrap();
 
-    /// Gets a vector of tuples containing the Path, priority score, and size in bytes of all items
-    /// in the file_map.
-    ///
-    /// The vector is sorted from highest to lowest priority.
-    /// This allows the assumption that the last element to be popped from the vector will have the
-    /// lowest priority, and therefore is the most eligible candidate for elimination from the
-    /// cache.
-    ///
-    fn sorted_priorities(&self) -> Vec<(PathBuf, FileStats)> {
+        File::create(&path).unwrap().write_all(b"first").unwrap();
+        let cached_file: CachedFile = cache.get(&path);
+        if let CachedFile::FileSystem(_) = cached_file {
+        } else {
+            panic!("The file should have been gotten from the filesystem.")
+        }
+        let request = client.get("/");
+        let mut response = cached_file.respond_to(request.inner()).unwrap();
+        assert_eq!(response.body_bytes(), Some(b"first".to_vec()));
 
-        let mut priorities: Vec<(PathBuf, FileStats)> = self.file_map
-            .iter()
-            .map(|x| (x.0.clone(), x.1.stats.clone()))
+        File::create(&path).unwrap().write_all(b"second").unwrap();
+        let mut response = cache.get(&path).respond_to(request.inner()).unwrap();
+        assert_eq!(response.body_bytes(), Some(b"second".to_vec()));
+
+        assert!(!cache.contains_key(&path));
+        assert_eq!(cache.used_bytes(), 0);
+    }
+
+    #[test]
+    fn weighted_random_eviction_is_reproducible() {
+        let temp_dir = TempDir::new(DIR_TEST).unwrap();
+        let paths: Vec<PathBuf> = (0..5)
+            .map(|i| create_test_file(&temp_dir, MEG1, &format!("{}.txt", i)))
             .collect();
+        let new_path: PathBuf = create_test_file(&temp_dir, MEG1, "new.txt");
+
+        let evicted_files = |seed: u64| -> Vec<PathBuf> {
+            let mut cache: Cache = Cache::new(MEG5);
+            cache.eviction_policy = EvictionPolicy::WeightedRandom;
+            *cache.random_state.lock().unwrap() = seed;
+            for path in paths.iter() {
+                cache.get(path);
+            }
+            cache.touch(&new_path, 10);
+            cache.get(&new_path).dummy_write();
+            assert!(cache.contains_key(&new_path));
+            assert_eq!(cache.used_bytes(), MEG5);
 
-        // Sort the priorities from highest priority to lowest, so when they are pop()ed later,
-        // the last element will have the lowest priority.
-        priorities.sort_by(|l, r| r.1.priority.cmp(&l.1.priority));
-        priorities
+            paths.iter().filter(|path| !cache.contains_key(path)).cloned().collect()
+        };
+
+        // The files all have the same priority, so the seed alone decides which one is evicted.
+        assert_eq!(evicted_files(42), vec![paths[3].clone()]);
+        assert_eq!(evicted_files(42), evicted_files(42));
+        assert_eq!(evicted_files(7), vec![paths[1].clone()]);
+        assert_eq!(evicted_files(7), evicted_files(7));
     }
-}
 
+    #[test]
+    fn weighted_random_eviction_prefers_low_priority_files() {
+        let low_priority_path: PathBuf = PathBuf::from("low.txt");
+        let high_priority_path: PathBuf = PathBuf::from("high.txt");
+        let mut low_priority_evictions: usize = 0;
+        let mut high_priority_evictions: usize = 0;
+
+        for seed in 1..201 {
+            let mut cache: Cache = Cache::new(1024 * 2);
+            cache.eviction_policy = EvictionPolicy::WeightedRandom;
+            cache.reseed(seed);
+            cache.touch(&low_priority_path, 1);
+            cache.offer(&low_priority_path, vec![0u8; 1024]);
+            cache.touch(&high_priority_path, 9);
+            cache.offer(&high_priority_path, vec![1u8; 1024]);
+            cache.touch("new.txt", 100);
+            assert!(cache.offer("new.txt", vec![2u8; 1024]));
+
+            if !cache.contains_key(&low_priority_path) {
+                low_priority_evictions += 1;
+            }
+            if !cache.contains_key(&high_priority_path) {
+                high_priority_evictions += 1;
+            }
+        }
 
+        assert_eq!(low_priority_evictions + high_priority_evictions, 200);
+        assert!(low_priority_evictions > high_priority_evictions * 2);
+    }
 
-#[cfg(test)]
-mod tests {
-    extern crate test;
-    extern crate tempdir;
-    extern crate rand;
+    #[test]
+    fn offered_eviction_is_deterministic() {
+        let evicted_files = |seed: u64| -> Vec<PathBuf> {
+            let mut cache: Cache = Cache::new(MEG5);
+            cache.eviction_policy = EvictionPolicy::WeightedRandom;
+            cache.reseed(seed);
+            let mut evicted: Vec<PathBuf> = Vec::new();
+            for i in 0..20 {
+                let path: PathBuf = PathBuf::from(format!("{}.txt", i));
+                let before: Vec<PathBuf> = cache.file_map.iter().map(|x| x.0.clone()).collect();
+                cache.touch(&path, i % 4 + 1);
+                cache.offer(&path, vec![i as u8; MEG1]);
+                for path in before {
+                    if !cache.contains_key(&path) {
+                        evicted.push(path);
+                    }
+                }
+            }
+            assert_eq!(cache.used_bytes(), MEG5);
+            evicted
+        };
 
-    use super::*;
+        let evicted: Vec<PathBuf> = evicted_files(42);
+        assert!(!evicted.is_empty());
+        assert_eq!(evicted, evicted_files(42));
+        assert_eq!(evicted_files(7), evicted_files(7));
+    }
 
-    use self::tempdir::TempDir;
-    use self::test::Bencher;
-    use self::rand::{StdRng, Rng};
-    use std::io::{Write, BufWriter};
-    use std::fs::File;
-    use rocket::response::NamedFile;
-    use std::io::Read;
-    use in_memory_file::InMemoryFile;
-    use concurrent_hashmap::Accessor;
-    use std::sync::Arc;
-    use std::mem;
+    #[test]
+    fn offer_is_admitted_like_get() {
+        let cache: Cache = Cache::new(MEG2);
+        assert!(cache.offer("a.txt", vec![0u8; MEG1]));
+        assert!(cache.offer("b.txt", vec![0u8; MEG1]));
+        assert!(cache.offer("b.txt", vec![1u8; MEG1]));
+        assert!(cache.offer("a.txt", vec![1u8; MEG1]));
+        assert_eq!(cache.stats().hits, 2);
+        assert_eq!(cache.stats().misses, 2);
+
+        // The new content has been accessed less often than either file, so it doesn't replace them.
+        assert!(!cache.offer("c.txt", vec![0u8; MEG1]));
+        assert!(!cache.contains_key("c.txt"));
+        // Content larger than the cache is never stored.
+        assert!(!cache.offer("d.txt", vec![0u8; MEG5]));
+
+        cache.touch("c.txt", 5);
+        assert!(cache.offer("c.txt", vec![0u8; MEG1]));
+        assert!(cache.contains_key("a.txt"));
+        assert!(!cache.contains_key("b.txt"));
+        assert_eq!(cache.stats().evictions, 1);
+    }
 
-    const MEG1: usize = 1024 * 1024;
-    const MEG2: usize = MEG1 * 2;
-    const MEG5: usize = MEG1 * 5;
-    const MEG10: usize = MEG1 * 10;
+    #[test]
+    fn would_store_agrees_with_get() {
+        fn is_stored(cached_file: CachedFile) -> bool {
+            match cached_file {
+                CachedFile::InMemory(_) => true,
+                _ => false,
+            }
+        }
 
-    const DIR_TEST: &'static str = "test1";
-    const FILE_MEG1: &'static str = "meg1.txt";
-    const FILE_MEG2: &'static str = "meg2.txt";
-    const FILE_MEG5: &'static str = "meg5.txt";
-    const FILE_MEG10: &'static str = "meg10.txt";
+        let cache: Cache = Cache::new(MEG5);
+        let temp_dir = TempDir::new(DIR_TEST).unwrap();
+        let path_10m = create_test_file(&temp_dir, MEG10, FILE_MEG10);
+        let path_5m = create_test_file(&temp_dir, MEG5, FILE_MEG5);
+        let path_2m = create_test_file(&temp_dir, MEG2, FILE_MEG2);
 
-    // Helper function that creates test files in a directory that is cleaned up after the test runs.
-    fn create_test_file(temp_dir: &TempDir, size: usize, name: &str) -> PathBuf {
-        let path = temp_dir.path().join(name);
-        let tmp_file = File::create(path.clone()).unwrap();
-        let mut rand_data: Vec<u8> = vec![0u8; size];
-        StdRng::new().unwrap().fill_bytes(rand_data.as_mut());
-        let mut buffer = BufWriter::new(tmp_file);
-        buffer.write(&rand_data).unwrap();
-        path
+        // The file fits in the empty cache.
+        assert!(cache.would_store(&path_5m, MEG5));
+        assert!(is_stored(cache.get(&path_5m)));
+        cache.get(&path_5m);
+        cache.get(&path_5m);
+
+        // The file is larger than the cache.
+        assert!(!cache.would_store(&path_10m, MEG10));
+        assert!(!is_stored(cache.get(&path_10m)));
+
+        // The file doesn't have a high enough priority to replace the cached file.
+        assert!(!cache.would_store(&path_2m, MEG2));
+        assert!(!is_stored(cache.get(&path_2m)));
+
+        // After enough accesses, the file has a high enough priority.
+        cache.touch(&path_2m, 10);
+        assert!(cache.would_store(&path_2m, MEG2));
+        assert!(is_stored(cache.get(&path_2m)));
     }
 
+    #[test]
+    fn corrupted_streamed_file_is_detected() {
+        let mut cache: Cache = Cache::new(MEG10);
+        cache.max_file_size = MEG1;
+        let temp_dir = TempDir::new(DIR_TEST).unwrap();
+        let path = create_test_file(&temp_dir, MEG2, FILE_MEG2);
+        let client = Client::new(rocket::ignite()).unwrap();
+        let request = client.get("/");
 
-    // Standardize the way a file is used in these tests.
-    impl<'a> CachedFile<'a> {
-        fn dummy_write(self) {
-            match self {
-                CachedFile::InMemory(cached_file) => unsafe {
-                    let file: *const Accessor<'a, PathBuf, InMemoryFile> = Arc::into_raw(cached_file.file);
-                    let mut v: Vec<u8> = Vec::new();
-                    let _ = (*file).get().bytes.as_slice().read_to_end(&mut v).unwrap();
-                    let _ = Arc::from_raw(file); // To prevent a memory leak, an Arc needs to be reconstructed from the raw pointer.
-                },
-                CachedFile::FileSystem(mut named_file) => {
-                    let mut v: Vec<u8> = Vec::new();
-                    let _ = named_file.read_to_end(&mut v).unwrap();
-                }
-                CachedFile::NotFound => {
-                    panic!("tried to write using a non-existent file")
-                }
+        let contents: Vec<u8> = InMemoryFile::open(&path).unwrap().bytes.to_vec();
+        cache.set_expected_checksum(&path, checksum(&contents));
+        let mut corrupted: Vec<u8> = contents.clone();
+        {
+            let mut response = cache.get(&path).respond_to(request.inner()).unwrap();
+            assert_eq!(response.body_bytes(), Some(contents.clone()));
+
+            // Corrupt a single byte of the file.
+            corrupted[MEG1] = corrupted[MEG1].wrapping_add(1);
+            File::create(&path).unwrap().write_all(&corrupted).unwrap();
+
+            let cached_file: CachedFile = cache.get(&path);
+            if let CachedFile::Verified(_) = cached_file {
+            } else {
+                panic!("The file should have been streamed while verifying its checksum.")
             }
+            let mut response = cached_file.respond_to(request.inner()).unwrap();
+            assert_eq!(response.body_bytes(), None);
         }
 
-        fn get_in_memory_file(self) -> NamedInMemoryFile<'a> {
-            match self {
-                CachedFile::InMemory(n) => n,
-                _ => panic!("tried to get cached file for named file"),
+        // The mismatch can be logged instead of aborting the response.
+        cache.on_checksum_mismatch = ChecksumMismatch::Log;
+        let mut response = cache.get(&path).respond_to(request.inner()).unwrap();
+        assert_eq!(response.body_bytes(), Some(corrupted));
+    }
+
+    #[test]
+    fn rename_cached_file() {
+        let cache: Cache = Cache::new(MEG10);
+        let temp_dir = TempDir::new(DIR_TEST).unwrap();
+        let old_path = create_test_file(&temp_dir, 1024, "page.txt");
+        let new_path = temp_dir.path().join("page.html");
+        cache.get(&old_path);
+        cache.get(&old_path);
+        let priority: Option<usize> = cache.priority_of(&old_path);
+
+        fs::rename(&old_path, &new_path).unwrap();
+        assert!(cache.rename(&old_path, new_path.clone()));
+        assert!(!cache.rename(&old_path, new_path.clone()));
+        assert!(!cache.contains_key(&old_path));
+        assert_eq!(cache.priority_of(&new_path), priority);
+
+        // The file is served from the cache under its new name, with the Content-Type implied by the new name.
+        let (cached_file, origin): (CachedFile, Origin) = cache.get_with_origin(&new_path);
+        assert_eq!(origin, Origin::Cache);
+        let client = Client::new(rocket::ignite()).unwrap();
+        let request = client.get("/");
+        let response = cached_file.respond_to(request.inner()).unwrap();
+        assert_eq!(response.content_type(), Some(ContentType::HTML));
+        assert_eq!(cache.stats().files_read, 1);
+    }
+
+    #[test]
+    fn owned_file_outlives_cache_entry() {
+        fn assert_send_and_static<T: Send + 'static>(_: &T) {}
 
-            }
+        let cache: Cache = Cache::new(MEG10);
+        let temp_dir = TempDir::new(DIR_TEST).unwrap();
+        let path = create_test_file(&temp_dir, MEG1, FILE_MEG1);
+        let owned_file: OwnedInMemoryFile = cache.get(&path).get_in_memory_file().into_owned();
+        assert_send_and_static(&owned_file);
+
+        // The owned file doesn't borrow the cache, so the file can be removed while it is still in use.
+        assert!(cache.remove(&path));
+
+        let body: Vec<u8> = thread::spawn(move || {
+            let client = Client::new(rocket::ignite()).unwrap();
+            let request = client.get("/");
+            let mut response = owned_file.respond_to(request.inner()).unwrap();
+            assert_eq!(response.content_type(), Some(ContentType::Plain));
+            response.body_bytes().unwrap()
+        }).join().unwrap();
+        assert_eq!(body, InMemoryFile::open(&path).unwrap().bytes.to_vec());
+    }
+
+    thread_local!(static CAPTURED_LOGS: RefCell<Vec<String>> = RefCell::new(vec![]));
+
+    // A logger that records the messages logged by each thread, so tests can inspect their own logs.
+    struct CapturingLogger;
+
+    impl Log for CapturingLogger {
+        fn enabled(&self, _: &LogMetadata) -> bool {
+            true
         }
 
-        fn get_named_file(self) -> NamedFile {
-            match self {
-                CachedFile::FileSystem(n) => n,
-                _ =>  panic!("tried to get cached file for named file"),
-            }
+        fn log(&self, record: &Record) {
+            CAPTURED_LOGS.with(|logs| logs.borrow_mut().push(format!("{}", record.args())));
         }
+
+        fn flush(&self) {}
     }
 
-    #[bench]
-    fn cache_get_10mb(b: &mut Bencher) {
-        let cache: Cache = Cache::new(MEG1 * 20); //Cache can hold 20Mb
-        let temp_dir = TempDir::new(DIR_TEST).unwrap();
-        let path_10m = create_test_file(&temp_dir, MEG10, FILE_MEG10);
-        cache.get(&path_10m); // add the 10 mb file to the cache
+    static CAPTURING_LOGGER: CapturingLogger = CapturingLogger;
+    static INSTALL_CAPTURING_LOGGER: Once = Once::new();
 
-        b.iter(|| {
-            let cached_file = cache.get(&path_10m);
-            cached_file.dummy_write()
+    /// Installs the capturing logger the first time it is called, so every test that inspects logs shares it.
+    /// Nothing else in the tests sets a logger, so failing to set it is an error instead of a reason to skip the test.
+    fn install_capturing_logger() {
+        INSTALL_CAPTURING_LOGGER.call_once(|| {
+            ::log::set_logger(&CAPTURING_LOGGER).expect("Another logger was set before the capturing logger");
+            ::log::set_max_level(LevelFilter::Debug);
         });
     }
 
-    #[bench]
-    fn cache_miss_10mb(b: &mut Bencher) {
-        let cache: Cache = Cache::new(0);
+    #[test]
+    fn eviction_logging_can_be_toggled() {
+        install_capturing_logger();
+        CAPTURED_LOGS.with(|logs| logs.borrow_mut().clear());
+        debug!("Capturing logs.");
+        assert_eq!(CAPTURED_LOGS.with(|logs| logs.borrow().clone()), vec![String::from("Capturing logs.")]);
+
+        let evicted_logs = |log_evictions: bool| -> usize {
+            let mut cache: Cache = Cache::new(MEG5);
+            cache.log_evictions = log_evictions;
+            let temp_dir = TempDir::new(DIR_TEST).unwrap();
+            let path_5m = create_test_file(&temp_dir, MEG5, FILE_MEG5);
+            let path_2m = create_test_file(&temp_dir, MEG2, FILE_MEG2);
+            cache.get(&path_5m);
+            cache.touch(&path_2m, 10);
+
+            CAPTURED_LOGS.with(|logs| logs.borrow_mut().clear());
+            cache.get(&path_2m);
+            assert_eq!(cache.stats().evictions, 1);
+            CAPTURED_LOGS.with(|logs| logs.borrow().iter().filter(|log| log.starts_with("Evicted")).count())
+        };
+
+        assert_eq!(evicted_logs(false), 0);
+        assert_eq!(evicted_logs(true), 1);
+    }
+
+    #[test]
+    fn top_accessed_files() {
+        let cache: Cache = Cache::new(MEG10);
         let temp_dir = TempDir::new(DIR_TEST).unwrap();
-        let path_10m = create_test_file(&temp_dir, MEG10, FILE_MEG10);
+        let path_1m = create_test_file(&temp_dir, MEG1, FILE_MEG1);
+        cache.get(&path_1m);
+        cache.get(&path_1m);
+        cache.touch(&PathBuf::from("b.html"), 5);
+        cache.touch(&PathBuf::from("a.html"), 5);
+        cache.touch(&PathBuf::from("c.html"), 7);
+        cache.touch(&PathBuf::from("d.html"), 1);
 
-        b.iter(|| {
-            let cached_file = cache.get(&path_10m);
-            cached_file.dummy_write()
-        });
+        assert_eq!(
+            cache.top_accessed(3),
+            vec![
+                (PathBuf::from("c.html"), 7),
+                (PathBuf::from("a.html"), 5),
+                (PathBuf::from("b.html"), 5),
+            ]
+        );
+        assert_eq!(cache.top_accessed(10).len(), 5);
+        assert_eq!(cache.top_accessed(10)[3], (path_1m, 2));
+        assert!(cache.top_accessed(0).is_empty());
     }
 
-    #[bench]
-    fn named_file_read_10mb(b: &mut Bencher) {
+    #[test]
+    fn should_cache_excludes_private_files() {
+        let mut cache: Cache = Cache::new(MEG10);
+        let private_dir: &'static str = "private";
+        cache.should_cache = Some(Box::new(move |path: &Path, _: usize| {
+            !path.components().any(|component| component.as_os_str() == private_dir)
+        }));
         let temp_dir = TempDir::new(DIR_TEST).unwrap();
-        let path_10m = create_test_file(&temp_dir, MEG10, FILE_MEG10);
-        b.iter(|| {
-            let named_file = CachedFile::from(NamedFile::open(&path_10m).unwrap());
-            named_file.dummy_write()
-        });
+        fs::create_dir(temp_dir.path().join("private")).unwrap();
+        let public_path = create_test_file(&temp_dir, MEG1, FILE_MEG1);
+        let private_path = create_test_file(&temp_dir, MEG1, "private/secret.txt");
+
+        cache.get(&public_path);
+        let private_file: CachedFile = cache.get(&private_path);
+        if let CachedFile::FileSystem(_) = private_file {
+        } else {
+            panic!("The private file should have been streamed from the filesystem.")
+        }
+        assert!(!cache.would_store(&private_path, MEG1));
+        cache.preload_dir(temp_dir.path()).unwrap();
+
+        assert!(cache.contains_key(&public_path));
+        assert!(!cache.contains_key(&private_path));
+        assert_eq!(cache.used_bytes(), MEG1);
     }
 
-    #[bench]
-    fn cache_get_1mb(b: &mut Bencher) {
-        let cache: Cache = Cache::new(MEG1 * 20); //Cache can hold 20Mb
+    #[test]
+    fn response_time_is_recorded() {
+        let cache: Cache = Cache::new(MEG10);
         let temp_dir = TempDir::new(DIR_TEST).unwrap();
-        let path_1m = create_test_file(&temp_dir, MEG1, FILE_MEG1);
-        cache.get(&path_1m); // add the 10 mb file to the cache
+        let path = create_test_file(&temp_dir, MEG1, FILE_MEG1);
+        let client = Client::new(rocket::ignite()).unwrap();
+        let request = client.get("/");
+        assert_eq!(cache.stats().responses_built, 0);
+        assert_eq!(cache.stats().average_response_time, Duration::new(0, 0));
 
-        b.iter(|| {
-            let cached_file = cache.get(&path_1m);
-            cached_file.dummy_write()
-        });
+        cache.get(&path).respond_to(request.inner()).unwrap();
+        cache.get(&path).respond_to(request.inner()).unwrap();
+
+        assert_eq!(cache.stats().responses_built, 2);
+        assert!(cache.stats().average_response_time > Duration::new(0, 0));
     }
 
-    #[bench]
-    fn cache_miss_1mb(b: &mut Bencher) {
-        let cache: Cache = Cache::new(0);
+    // Helper function that creates a tar archive entry for a file.
+    fn tar_entry(name: &str, contents: &[u8]) -> Vec<u8> {
+        let mut header = [0u8; 512];
+        header[..name.len()].copy_from_slice(name.as_bytes());
+        header[100..108].copy_from_slice(b"0000644\0");
+        header[124..136].copy_from_slice(format!("{:011o}\0", contents.len()).as_bytes());
+        header[156] = b'0';
+        header[257..263].copy_from_slice(b"ustar\0");
+        // The checksum is calculated with the checksum field filled with spaces.
+        header[148..156].copy_from_slice(b"        ");
+        let header_checksum: u32 = header.iter().map(|byte| *byte as u32).sum();
+        header[148..156].copy_from_slice(format!("{:06o}\0 ", header_checksum).as_bytes());
+
+        let mut entry: Vec<u8> = header.to_vec();
+        entry.extend_from_slice(contents);
+        let padding: usize = (512 - contents.len() % 512) % 512;
+        entry.extend(vec![0u8; padding]);
+        entry
+    }
+
+    #[test]
+    fn serve_file_from_archive() {
+        let mut archive: Vec<u8> = vec![];
+        archive.extend(tar_entry("./index.html", b"<h1>Hello World!</h1>"));
+        archive.extend(tar_entry("css/style.css", &vec![b'a'; 1000]));
+        archive.extend(vec![0u8; 1024]);
+
+        let cache: Cache = Cache::from_archive(io::Cursor::new(archive), MEG1).unwrap();
+        assert!(!cache.contains_key("index.html"));
+
+        let client = Client::new(rocket::ignite()).unwrap();
+        let request = client.get("/");
+        let mut response = cache.get("index.html").respond_to(request.inner()).unwrap();
+        assert_eq!(response.content_type(), Some(ContentType::HTML));
+        assert_eq!(response.body_bytes(), Some(b"<h1>Hello World!</h1>".to_vec()));
+        assert!(cache.contains_key("index.html"));
+
+        let mut response = cache.get("css/style.css").respond_to(request.inner()).unwrap();
+        assert_eq!(response.body_bytes(), Some(vec![b'a'; 1000]));
+        assert_eq!(cache.used_bytes(), 1000 + 21);
+
+        assert_eq!(cache.get("missing.html"), CachedFile::NotFound);
+    }
+
+    #[test]
+    fn serve_file_from_zip_archive() {
+        let mut writer: ZipWriter<io::Cursor<Vec<u8>>> = ZipWriter::new(io::Cursor::new(vec![]));
+        writer.start_file("index.html", FileOptions::default().compression_method(CompressionMethod::Stored)).unwrap();
+        writer.write_all(b"<h1>Hello World!</h1>").unwrap();
+        writer.add_directory("css", FileOptions::default()).unwrap();
+        writer.start_file("css/style.css", FileOptions::default().compression_method(CompressionMethod::Deflated)).unwrap();
+        writer.write_all(&vec![b'a'; 1000]).unwrap();
+        writer.start_file("../escaped.html", FileOptions::default()).unwrap();
+        writer.write_all(b"<h1>Outside</h1>").unwrap();
+        let archive: Vec<u8> = writer.finish().unwrap().into_inner();
+
+        let cache: Cache = Cache::from_archive(io::Cursor::new(archive), MEG1).unwrap();
+        assert!(!cache.contains_key("index.html"));
+
+        let client = Client::new(rocket::ignite()).unwrap();
+        let request = client.get("/");
+        let mut response = cache.get("index.html").respond_to(request.inner()).unwrap();
+        assert_eq!(response.content_type(), Some(ContentType::HTML));
+        assert_eq!(response.body_bytes(), Some(b"<h1>Hello World!</h1>".to_vec()));
+        assert!(cache.contains_key("index.html"));
+
+        // Compressed entries are decompressed before they are stored.
+        let mut response = cache.get("css/style.css").respond_to(request.inner()).unwrap();
+        assert_eq!(response.body_bytes(), Some(vec![b'a'; 1000]));
+        assert_eq!(cache.used_bytes(), 1000 + 21);
+
+        assert_eq!(cache.get("css"), CachedFile::NotFound);
+        assert_eq!(cache.get("../escaped.html"), CachedFile::NotFound);
+        assert_eq!(cache.get("escaped.html"), CachedFile::NotFound);
+    }
+
+    #[test]
+    fn used_bytes_include_compressed_variants() {
+        let cache: Cache = Cache::new(MEG2 * 2);
         let temp_dir = TempDir::new(DIR_TEST).unwrap();
-        let path_1m = create_test_file(&temp_dir, MEG1, FILE_MEG1);
+        let path_js = create_test_file(&temp_dir, MEG2, "app.js");
+        let path_gz = create_test_file(&temp_dir, MEG1, "app.js.gz");
+        let path_css = create_test_file(&temp_dir, MEG2, "style.css");
+        let client = Client::new(rocket::ignite()).unwrap();
+
+        let request = client.get("/");
+        PrecompressedFile::open(&path_js, &cache).respond_to(request.inner()).unwrap();
+        let request = client.get("/").header(Header::new("Accept-Encoding", "gzip"));
+        PrecompressedFile::open(&path_js, &cache).respond_to(request.inner()).unwrap();
+
+        assert!(cache.contains_key(&path_js));
+        assert!(cache.contains_key(&path_gz));
+        assert_eq!(cache.used_bytes(), MEG2 + MEG1);
+        assert_eq!(cache.stats().used_bytes, MEG2 + MEG1);
+
+        // The compressed variant takes up room in the cache, so a file must be removed to store another file.
+        // The uncompressed file is removed instead of its compressed variant, which can still be served from memory.
+        assert_eq!(cache.eviction_cost(&path_css, MEG2), Some(MEG2));
+    }
 
-        b.iter(|| {
-            let cached_file = cache.get(&path_1m);
-            cached_file.dummy_write()
-        });
+    #[test]
+    fn uncompressed_files_with_cached_compressed_variants_are_evicted_first() {
+        let fill = |cache: &Cache| {
+            cache.touch("app.js", 8);
+            assert!(cache.offer("app.js", vec![0u8; MEG2]));
+            cache.touch("app.js.gz", 8);
+            assert!(cache.offer("app.js.gz", vec![1u8; MEG1]));
+            // The stylesheet has the lowest priority, so it would be evicted first if it weren't for the compressed variant.
+            assert!(cache.offer("style.css", vec![2u8; MEG2]));
+            assert!(cache.priority_of("style.css") < cache.priority_of("app.js"));
+        };
+
+        let cache: Cache = Cache::new(MEG5);
+        fill(&cache);
+        cache.touch("new.txt", 100);
+        assert!(cache.offer("new.txt", vec![3u8; MEG1]));
+        assert!(!cache.contains_key("app.js"));
+        assert!(cache.contains_key("app.js.gz"));
+        assert!(cache.contains_key("style.css"));
+        assert_eq!(cache.stats().evictions, 1);
+
+        let cache: Cache = Cache::new(MEG5);
+        fill(&cache);
+        assert_eq!(cache.trim_to(MEG1 * 3), MEG2);
+        assert!(!cache.contains_key("app.js"));
+        assert!(cache.contains_key("app.js.gz"));
+        assert!(cache.contains_key("style.css"));
     }
 
-    #[bench]
-    fn named_file_read_1mb(b: &mut Bencher) {
+    #[test]
+    fn evict_uncompressed_keeps_compressed_variant() {
+        let cache: Cache = Cache::new(MEG10);
         let temp_dir = TempDir::new(DIR_TEST).unwrap();
-        let path_1m = create_test_file(&temp_dir, MEG1, FILE_MEG1);
+        let path_js = create_test_file(&temp_dir, MEG2, "app.js");
+        let path_gz = create_test_file(&temp_dir, MEG1, "app.js.gz");
+        let path_css = create_test_file(&temp_dir, MEG2, "style.css");
+        let client = Client::new(rocket::ignite()).unwrap();
+        let identity_request = client.get("/");
+        let gzip_request = client.get("/").header(Header::new("Accept-Encoding", "gzip"));
+
+        PrecompressedFile::open(&path_js, &cache).respond_to(identity_request.inner()).unwrap();
+        PrecompressedFile::open(&path_js, &cache).respond_to(gzip_request.inner()).unwrap();
+        cache.get(&path_css);
+        assert_eq!(cache.used_bytes(), MEG2 + MEG1 + MEG2);
+
+        // Files without a compressed variant in the cache aren't removed.
+        assert!(!cache.evict_uncompressed(&path_css));
+        assert!(cache.contains_key(&path_css));
+
+        assert!(cache.evict_uncompressed(&path_js));
+        assert!(!cache.contains_key(&path_js));
+        assert!(cache.contains_key(&path_gz));
+        assert_eq!(cache.used_bytes(), MEG1 + MEG2);
+        assert_eq!(cache.stats().evictions, 1);
+
+        // Clients accepting gzip are still served from memory.
+        let hits: u64 = cache.stats().hits;
+        let misses: u64 = cache.stats().misses;
+        let mut response = PrecompressedFile::open(&path_js, &cache).respond_to(gzip_request.inner()).unwrap();
+        assert_eq!(response.headers().get_one("Content-Encoding"), Some("gzip"));
+        assert_eq!(response.body_bytes(), Some(InMemoryFile::open(&path_gz).unwrap().bytes.to_vec()));
+        assert_eq!(cache.stats().hits, hits + 1);
+        assert_eq!(cache.stats().misses, misses);
+
+        // Clients that need the uncompressed file miss.
+        let mut response = PrecompressedFile::open(&path_js, &cache).respond_to(identity_request.inner()).unwrap();
+        assert_eq!(response.headers().get_one("Content-Encoding"), None);
+        assert_eq!(response.body_bytes(), Some(InMemoryFile::open(&path_js).unwrap().bytes.to_vec()));
+        assert_eq!(cache.stats().misses, misses + 1);
+    }
 
-        b.iter(|| {
-            let named_file = CachedFile::from(NamedFile::open(&path_1m).unwrap());
-            named_file.dummy_write()
-        });
+    #[test]
+    fn size_is_taken_from_bytes_read() {
+        let temp_dir = TempDir::new(DIR_TEST).unwrap();
+        let path = create_test_file(&temp_dir, MEG2, FILE_MEG2);
+
+        // The file shrinks after its metadata has been read, but before its contents are read.
+        let file: File = File::open(&path).unwrap();
+        let stale_len: usize = file.metadata().unwrap().len() as usize;
+        assert_eq!(stale_len, MEG2);
+        File::create(&path).unwrap().write_all(&vec![1u8; MEG1]).unwrap();
+
+        let in_memory_file: InMemoryFile = InMemoryFile::read_from(file, stale_len).unwrap();
+        assert_eq!(in_memory_file.stats.size, MEG1);
+        assert_eq!(in_memory_file.bytes.len(), MEG1);
+
+        // The file grows past the length it had when its metadata was read.
+        let in_memory_file: InMemoryFile = InMemoryFile::read_from(io::Cursor::new(vec![1u8; MEG5]), MEG1).unwrap();
+        assert_eq!(in_memory_file.stats.size, MEG5);
     }
 
+    #[test]
+    fn snapshot_describes_cached_files() {
+        let cache: Cache = Cache::new(MEG10);
+        let temp_dir = TempDir::new(DIR_TEST).unwrap();
+        let path_1m = create_test_file(&temp_dir, MEG1, FILE_MEG1);
+        let path_2m = create_test_file(&temp_dir, MEG2, FILE_MEG2);
 
+        cache.get(&path_1m);
+        cache.get(&path_2m);
+        cache.get(&path_2m);
+        cache.get(&path_2m);
 
-    #[bench]
-    fn cache_get_5mb(b: &mut Bencher) {
-        let cache: Cache = Cache::new(MEG1 * 20); //Cache can hold 20Mb
+        let snapshot: CacheSnapshot = cache.snapshot();
+        assert_eq!(snapshot.size_limit, MEG10);
+        assert_eq!(snapshot.stats.file_count, 2);
+        assert_eq!(snapshot.stats.used_bytes, MEG1 + MEG2);
+        assert_eq!(
+            snapshot.files,
+            vec![
+                FileSnapshot {
+                    path: path_1m.clone(),
+                    size: MEG1,
+                    access_count: 1,
+                    priority: cache.priority_of(&path_1m).unwrap(),
+                },
+                FileSnapshot {
+                    path: path_2m.clone(),
+                    size: MEG2,
+                    access_count: 3,
+                    priority: cache.priority_of(&path_2m).unwrap(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn force_store_ignores_priority() {
+        let cache: Cache = Cache::new(MEG5);
         let temp_dir = TempDir::new(DIR_TEST).unwrap();
+        let path_1m = create_test_file(&temp_dir, MEG1, FILE_MEG1);
+        let path_2m = create_test_file(&temp_dir, MEG2, FILE_MEG2);
         let path_5m = create_test_file(&temp_dir, MEG5, FILE_MEG5);
-        cache.get(&path_5m); // add the 10 mb file to the cache
+        let path_10m = create_test_file(&temp_dir, MEG10, FILE_MEG10);
 
-        b.iter(|| {
-            let cached_file = cache.get(&path_5m);
-            cached_file.dummy_write()
-        });
+        cache.touch(&path_1m, 100);
+        cache.touch(&path_2m, 100);
+        cache.get(&path_1m);
+        cache.get(&path_2m);
+        assert_eq!(cache.used_bytes(), MEG1 + MEG2);
+
+        // The 5 MB file has never been accessed, so it wouldn't normally replace the other files.
+        let imf_5m: InMemoryFile = InMemoryFile::open(&path_5m).unwrap();
+        assert!(!cache.would_store(&path_5m, MEG5));
+        assert_eq!(cache.force_store(path_5m.clone(), imf_5m), Ok(()));
+        assert!(cache.contains_key(&path_5m));
+        assert!(!cache.contains_key(&path_1m));
+        assert!(!cache.contains_key(&path_2m));
+        assert_eq!(cache.used_bytes(), MEG5);
+
+        let imf_10m: InMemoryFile = InMemoryFile::open(&path_10m).unwrap();
+        assert_eq!(cache.force_store(path_10m.clone(), imf_10m), Err(CacheInvalidationError::FileIsLargerThanSizeLimit));
+        assert!(cache.contains_key(&path_5m));
     }
 
-    #[bench]
-    fn cache_miss_5mb(b: &mut Bencher) {
-        let cache: Cache = Cache::new(0);
+    #[test]
+    fn force_store_keeps_replaced_file_until_room_is_made() {
+        let cache: Cache = Cache::new(MEG5);
         let temp_dir = TempDir::new(DIR_TEST).unwrap();
+        let path_1m = create_test_file(&temp_dir, MEG1, FILE_MEG1);
+        let path_2m = create_test_file(&temp_dir, MEG2, FILE_MEG2);
+        let path_4m = create_test_file(&temp_dir, MEG2 * 2, "meg4.txt");
         let path_5m = create_test_file(&temp_dir, MEG5, FILE_MEG5);
-
-        b.iter(|| {
-            let cached_file = cache.get(&path_5m);
-            cached_file.dummy_write()
-        });
+        cache.pin(&path_1m);
+        cache.get(&path_1m);
+        cache.get(&path_2m);
+
+        // Only the pinned file and the file being replaced are cached, so no room can be made.
+        let imf_5m: InMemoryFile = InMemoryFile::open(&path_5m).unwrap();
+        assert_eq!(cache.force_store(path_2m.clone(), imf_5m), Err(CacheInvalidationError::PinnedFilesLeaveTooLittleSpace));
+        assert_eq!(cache.file_map.get(&path_2m).unwrap().stats.size, MEG2);
+        assert_eq!(cache.used_bytes(), MEG1 + MEG2);
+
+        // The replaced file's space is reused, so nothing else has to be removed.
+        let imf_4m: InMemoryFile = InMemoryFile::open(&path_4m).unwrap();
+        assert_eq!(cache.force_store(path_2m.clone(), imf_4m), Ok(()));
+        assert!(cache.contains_key(&path_1m));
+        assert_eq!(cache.file_map.get(&path_2m).unwrap().stats.size, MEG2 * 2);
+        assert_eq!(cache.used_bytes(), MEG5);
     }
 
-    #[bench]
-    fn named_file_read_5mb(b: &mut Bencher) {
+    #[test]
+    fn file_exceeding_max_single_file_fraction_is_never_cached() {
+        let mut cache: Cache = Cache::new(MEG10);
+        cache.max_single_file_fraction = Some(0.4);
         let temp_dir = TempDir::new(DIR_TEST).unwrap();
+        let path_2m = create_test_file(&temp_dir, MEG2, FILE_MEG2);
         let path_5m = create_test_file(&temp_dir, MEG5, FILE_MEG5);
 
-        b.iter(|| {
-            let named_file = CachedFile::from(NamedFile::open(&path_5m).unwrap());
-            named_file.dummy_write()
-        });
+        for _ in 0..10 {
+            match cache.get(&path_5m) {
+                CachedFile::FileSystem(_) => {}
+                _ => panic!("The file occupies more than 40% of the size limit, so it should be served from the filesystem."),
+            }
+        }
+        assert!(!cache.contains_key(&path_5m));
+        assert!(!cache.would_store(&path_5m, MEG5));
+
+        cache.get(&path_2m);
+        assert!(cache.contains_key(&path_2m));
+    }
+
+    #[test]
+    fn bundle_concatenates_files() {
+        let cache: Cache = Cache::new(MEG1);
+        let temp_dir = TempDir::new(DIR_TEST).unwrap();
+        let path_a = temp_dir.path().join("a.txt");
+        let path_b = temp_dir.path().join("b.txt");
+        File::create(&path_a).unwrap().write_all(b"Hello ").unwrap();
+        File::create(&path_b).unwrap().write_all(b"World!").unwrap();
+        let client = Client::new(rocket::ignite()).unwrap();
+        let request = client.get("/");
+
+        let mut response = Bundle::open(&[&path_a, &path_b], &cache).respond_to(request.inner()).unwrap();
+        assert_eq!(response.content_type(), Some(ContentType::Plain));
+        assert_eq!(response.body_bytes(), Some(b"Hello World!".to_vec()));
+        assert!(cache.contains_key(&path_a));
+        assert!(cache.contains_key(&path_b));
+
+        let missing = temp_dir.path().join("missing.txt");
+        let result = Bundle::open(&[&path_a, &missing], &cache).respond_to(request.inner());
+        assert_eq!(result.err(), Some(Status::NotFound));
     }
 
+    #[test]
+    fn getting_a_file_updates_its_last_access() {
+        let cache: Cache = Cache::new(MEG10);
+        let temp_dir = TempDir::new(DIR_TEST).unwrap();
+        let path_1m = create_test_file(&temp_dir, MEG1, FILE_MEG1);
+        assert_eq!(cache.last_access(&path_1m), None);
+
+        let before: Instant = Instant::now();
+        cache.get(&path_1m);
+        let first_access: Instant = cache.last_access(&path_1m).unwrap();
+        assert!(first_access >= before);
 
+        thread::sleep(Duration::from_millis(10));
+        cache.get(&path_1m);
+        let second_access: Instant = cache.last_access(&path_1m).unwrap();
+        assert!(second_access > first_access);
+    }
 
-    // Constant time access regardless of size.
-    #[bench]
-    fn cache_get_1mb_from_1000_entry_cache(b: &mut Bencher) {
+    #[test]
+    fn idle_files_are_evicted() {
+        let cache: Cache = Cache::new(MEG10);
         let temp_dir = TempDir::new(DIR_TEST).unwrap();
         let path_1m = create_test_file(&temp_dir, MEG1, FILE_MEG1);
-        let cache: Cache = Cache::new(MEG1 * 3); //Cache can hold 3Mb
-        cache.get(&path_1m); // add the file to the cache
+        let path_2m = create_test_file(&temp_dir, MEG2, FILE_MEG2);
+        cache.get(&path_1m);
+        cache.get(&path_2m);
 
-        // Add 1024 1kib files to the cache.
-        for i in 0..1024 {
-            let path = create_test_file(&temp_dir, 1024, format!("{}_1kib.txt", i).as_str());
-            cache.get(&path);
-        }
-        // make sure that the file has a high priority.
-        cache.alter_all_access_counts(|x| x + 1 * 100000);
+        thread::sleep(Duration::from_millis(100));
+        cache.get(&path_2m);
 
-        assert_eq!(cache.used_bytes(), MEG1 * 2);
+        assert_eq!(cache.evict_idle(Duration::from_millis(50)), 1);
+        assert!(!cache.contains_key(&path_1m));
+        assert!(cache.contains_key(&path_2m));
+        assert_eq!(cache.stats().evictions, 1);
+    }
 
-        let named_file = CachedFile::from(NamedFile::open(&path_1m).unwrap());
+    #[test]
+    fn custom_headers_are_set() {
+        let mut cache: Cache = Cache::new(MEG10);
+        cache.headers = vec![
+            Header::new("X-Content-Type-Options", "nosniff"),
+            Header::new("Access-Control-Allow-Origin", "https://example.com"),
+        ];
+        let temp_dir = TempDir::new(DIR_TEST).unwrap();
+        let path_txt = create_test_file(&temp_dir, MEG1, "data.txt");
+        let path_other = create_test_file(&temp_dir, MEG1, "other.txt");
+        cache.set_headers(&path_txt, vec![
+            Header::new("Access-Control-Allow-Origin", "*"),
+            Header::new("Content-Type", "application/json"),
+        ]);
+        let client = Client::new(rocket::ignite()).unwrap();
+        let request = client.get("/");
+
+        let response = cache.get(&path_txt).respond_to(request.inner()).unwrap();
+        assert_eq!(response.headers().get_one("X-Content-Type-Options"), Some("nosniff"));
+        assert_eq!(response.headers().get("Access-Control-Allow-Origin").collect::<Vec<&str>>(), vec!["*"]);
+        assert_eq!(response.content_type(), Some(ContentType::JSON));
+
+        let response = cache.get(&path_other).respond_to(request.inner()).unwrap();
+        assert_eq!(response.headers().get_one("Access-Control-Allow-Origin"), Some("https://example.com"));
+        assert_eq!(response.content_type(), Some(ContentType::Plain));
+    }
 
-        b.iter(|| {
-            let cached_file = cache.get(&path_1m);
-            assert!(mem::discriminant(&cached_file) != mem::discriminant(&named_file));
-            cached_file.dummy_write()
-        });
+    #[test]
+    fn pinned_file_is_not_evicted() {
+        let cache: Cache = Cache::new(MEG5);
+        let temp_dir = TempDir::new(DIR_TEST).unwrap();
+        let path_1m = create_test_file(&temp_dir, MEG1, FILE_MEG1);
+        let path_2m = create_test_file(&temp_dir, MEG2, FILE_MEG2);
+        let path_5m = create_test_file(&temp_dir, MEG5, FILE_MEG5);
+
+        let path_4m = create_test_file(&temp_dir, MEG2 * 2, "meg4.txt");
+
+        cache.pin(&path_1m);
+        cache.get(&path_1m);
+        cache.get(&path_2m);
+
+        // The 4 MB file is requested more often, so it would replace both files if the 1 MB file weren't pinned.
+        cache.touch(&path_4m, 100);
+        cache.get(&path_4m);
+        assert!(cache.contains_key(&path_1m));
+        assert!(!cache.contains_key(&path_2m));
+        assert!(cache.contains_key(&path_4m));
+
+        // Pinned files are kept even when a file is forcibly stored.
+        let imf_5m: InMemoryFile = InMemoryFile::open(&path_5m).unwrap();
+        assert_eq!(cache.force_store(path_5m.clone(), imf_5m), Err(CacheInvalidationError::PinnedFilesLeaveTooLittleSpace));
+        assert!(cache.contains_key(&path_1m));
+        assert!(cache.contains_key(&path_4m));
+
+        assert!(cache.unpin(&path_1m));
+        assert!(!cache.is_pinned(&path_1m));
+        let imf_5m: InMemoryFile = InMemoryFile::open(&path_5m).unwrap();
+        assert_eq!(cache.force_store(path_5m.clone(), imf_5m), Ok(()));
+        assert!(!cache.contains_key(&path_1m));
     }
 
-    // There is a penalty for missing the cache.
-    #[bench]
-    fn cache_miss_1mb_from_1000_entry_cache(b: &mut Bencher) {
+    #[test]
+    fn into_bytes_gets_file_contents() {
+        let cache: Cache = Cache::new(MEG5);
         let temp_dir = TempDir::new(DIR_TEST).unwrap();
         let path_1m = create_test_file(&temp_dir, MEG1, FILE_MEG1);
-        let cache: Cache = Cache::new(MEG1); //Cache can hold 1Mb
+        let path_10m = create_test_file(&temp_dir, MEG10, FILE_MEG10);
+        let mut contents_1m: Vec<u8> = vec![];
+        File::open(&path_1m).unwrap().read_to_end(&mut contents_1m).unwrap();
+        let mut contents_10m: Vec<u8> = vec![];
+        File::open(&path_10m).unwrap().read_to_end(&mut contents_10m).unwrap();
+
+        let in_memory: CachedFile = cache.get(&path_1m);
+        match in_memory {
+            CachedFile::InMemory(_) => {}
+            _ => panic!("The file should have been served as the InMemory variant."),
+        }
+        assert_eq!(in_memory.into_bytes().unwrap(), contents_1m);
 
-        // Add 1024 1kib files to the cache.
-        for i in 0..1024 {
-            let path = create_test_file(&temp_dir, 1024, format!("{}_1kib.txt", i).as_str());
-            cache.get(&path);
+        let file_system: CachedFile = cache.get(&path_10m);
+        match file_system {
+            CachedFile::FileSystem(_) => {}
+            _ => panic!("The file should have been served as the FileSystem variant."),
         }
-        // make sure that the file has a high priority.
-        cache.alter_all_access_counts(|x| x + 1 * 100_000_000_000_000_000);
-        let named_file = CachedFile::from(NamedFile::open(&path_1m).unwrap());
+        assert_eq!(file_system.into_bytes().unwrap(), contents_10m);
 
-        b.iter(|| {
-            let cached_file = cache.get(&path_1m);
-            assert!(mem::discriminant(&cached_file) == mem::discriminant(&named_file)); // get() in this case should only return files in the FS
-            cached_file.dummy_write()
-        });
+        assert_eq!(cache.get(temp_dir.path().join("missing.txt")).into_bytes().unwrap_err().kind(), io::ErrorKind::NotFound);
     }
 
-    // This is pretty much a worst-case scenario, where every file would try to be removed to make room for the new file.
-    // There is a penalty for missing the cache.
-    #[bench]
-    fn cache_miss_5mb_from_1000_entry_cache(b: &mut Bencher) {
+    #[test]
+    fn missing_files_are_remembered_until_their_ttl_passes() {
+        let mut cache: Cache = Cache::new(MEG5);
+        cache.negative_cache_ttl = Some(Duration::from_millis(200));
         let temp_dir = TempDir::new(DIR_TEST).unwrap();
-        let path_5m = create_test_file(&temp_dir, MEG5, FILE_MEG1);
-        let cache: Cache = Cache::new(MEG5); //Cache can hold 1Mb
+        let path = temp_dir.path().join(FILE_MEG1);
 
-        // Add 1024 5kib files to the cache.
-        for i in 0..1024 {
-            let path = create_test_file(&temp_dir, 1024 * 5, format!("{}_5kib.txt", i).as_str());
-            cache.get(&path);
+        assert_eq!(cache.get(&path), CachedFile::NotFound);
+
+        // The file now exists, but the filesystem isn't checked again until the time to live passes.
+        create_test_file(&temp_dir, MEG1, FILE_MEG1);
+        STAT_CALLS.with(|calls| calls.set(0));
+        assert_eq!(cache.get(&path), CachedFile::NotFound);
+        assert_eq!(STAT_CALLS.with(|calls| calls.get()), 0);
+        assert_eq!(cache.stats().files_read, 0);
+
+        thread::sleep(Duration::from_millis(250));
+        assert!(cache.get(&path).into_bytes().is_ok());
+        assert!(cache.contains_key(&path));
+    }
+
+    #[test]
+    fn expired_missing_paths_are_swept_periodically() {
+        let mut cache: Cache = Cache::new(MEG5);
+        cache.negative_cache_ttl = Some(Duration::from_millis(50));
+        cache.remember_missing("expired.txt");
+        thread::sleep(Duration::from_millis(100));
+
+        for i in 0..MISSING_PATHS_PER_SWEEP - 2 {
+            cache.remember_missing(format!("{}.txt", i));
         }
-        // make sure that the file has a high priority.
-        cache.alter_all_access_counts(|x| x + 1 * 100_000_000_000_000_000);
-        let named_file = CachedFile::from(NamedFile::open(&path_5m).unwrap());
+        // Until the next sweep, the expired path is only forgotten if it is looked up.
+        assert!(cache.missing.find(Path::new("expired.txt")).is_some());
 
-        b.iter(|| {
-            let cached_file: CachedFile = cache.get(&path_5m);
-            // Mimic what is done when the response body is set.
-            assert!(mem::discriminant(&cached_file) == mem::discriminant(&named_file));  // get() in this case should only return files in the FS
-            cached_file.dummy_write()
-        });
+        cache.remember_missing("last.txt");
+        assert!(cache.missing.find(Path::new("expired.txt")).is_none());
+        assert!(cache.missing.find(Path::new("last.txt")).is_some());
     }
 
+    #[test]
+    fn missing_index_file_is_remembered_before_checking_for_a_directory() {
+        let mut cache: Cache = Cache::new(MEG5);
+        cache.negative_cache_ttl = Some(Duration::from_secs(60));
+        cache.index_file = Some(String::from("index.html"));
+        let temp_dir = TempDir::new(DIR_TEST).unwrap();
+
+        STAT_CALLS.with(|calls| calls.set(0));
+        assert_eq!(cache.get(temp_dir.path()), CachedFile::NotFound);
+        assert!(STAT_CALLS.with(|calls| calls.get()) > 0);
 
-    #[bench]
-    fn in_memory_file_read_10mb(b: &mut Bencher) {
+        STAT_CALLS.with(|calls| calls.set(0));
+        assert_eq!(cache.get(temp_dir.path()), CachedFile::NotFound);
+        assert_eq!(STAT_CALLS.with(|calls| calls.get()), 0);
+        assert_eq!(cache.stats().misses, 2);
+    }
+
+    #[test]
+    fn hit_ratio_of_gets() {
+        let cache: Cache = Cache::new(MEG5);
         let temp_dir = TempDir::new(DIR_TEST).unwrap();
+        let path_1m = create_test_file(&temp_dir, MEG1, FILE_MEG1);
         let path_10m = create_test_file(&temp_dir, MEG10, FILE_MEG10);
+        assert_eq!(cache.hit_ratio(), 0.0);
 
-        b.iter(|| {
-            let in_memory_file = Arc::new(InMemoryFile::open(path_10m.clone()).unwrap());
-            let file: *const InMemoryFile = Arc::into_raw(in_memory_file);
-            unsafe {
-                let _ = (*file).bytes.clone();
-                let _ = Arc::from_raw(file);
+        cache.get(&path_1m); // miss
+        cache.get(&path_1m); // hit
+        cache.get(&path_1m); // hit
+        cache.get(&path_10m); // miss, the file is too large to be stored
+
+        let stats: CacheStats = cache.stats();
+        assert_eq!(stats.hits, 2);
+        assert_eq!(stats.misses, 2);
+        assert_eq!(cache.hit_ratio(), 0.5);
+
+        cache.get(&path_1m); // hit
+        cache.get(&path_1m); // hit
+        assert_eq!(cache.hit_ratio(), 4.0 / 6.0);
+    }
+
+    #[test]
+    fn zero_priority_vetoes_caching() {
+        fn no_two_meg_files(access_count: usize, size: usize) -> usize {
+            if size == MEG2 {
+                0
+            } else {
+                default_priority_function(access_count, size)
+            }
+        }
+        let mut cache: Cache = Cache::new(MEG10);
+        cache.priority_function = no_two_meg_files;
+        let temp_dir = TempDir::new(DIR_TEST).unwrap();
+        let path_1m = create_test_file(&temp_dir, MEG1, FILE_MEG1);
+        let path_2m = create_test_file(&temp_dir, MEG2, FILE_MEG2);
+
+        assert!(!cache.would_store(&path_2m, MEG2));
+        for _ in 0..5 {
+            match cache.get(&path_2m) {
+                CachedFile::FileSystem(_) => {}
+                _ => panic!("The priority function vetoed caching the file, so it should be streamed from the filesystem."),
             }
-        });
+        }
+        assert!(!cache.contains_key(&path_2m));
+        assert_eq!(cache.used_bytes(), 0);
+
+        cache.get(&path_1m);
+        assert!(cache.contains_key(&path_1m));
     }
 
+    #[test]
+    fn weighted_extension_wins_eviction() {
+        let temp_dir = TempDir::new(DIR_TEST).unwrap();
+        let path_png = create_test_file(&temp_dir, MEG2, "image.png");
+        let path_log = create_test_file(&temp_dir, MEG2, "server.log");
+
+        // Without weights, the files have equal priorities, so the log replaces the image.
+        let cache: Cache = Cache::new(MEG2);
+        cache.get(&path_png);
+        cache.get(&path_log);
+        assert!(!cache.contains_key(&path_png));
+        assert!(cache.contains_key(&path_log));
+
+        let mut cache: Cache = Cache::new(MEG2);
+        cache.set_extension_weight("PNG", 2.0);
+        cache.get(&path_png);
+        cache.get(&path_log);
+        assert!(cache.contains_key(&path_png));
+        assert!(!cache.contains_key(&path_log));
+        assert_eq!(cache.priority_of(&path_png), Some(default_priority_function(1, MEG2) * 2));
+    }
 
     #[test]
-    fn file_exceeds_size_limit() {
-        let cache: Cache = Cache::new(MEG1 * 8); // Cache can hold only 8Mb
+    fn files_read_correctly_when_releasing_page_cache() {
+        let mut cache: Cache = Cache::new(MEG5);
+        cache.release_page_cache = true;
         let temp_dir = TempDir::new(DIR_TEST).unwrap();
-        let path_10m = create_test_file(&temp_dir, MEG10, FILE_MEG10);
+        let path_2m = create_test_file(&temp_dir, MEG2, FILE_MEG2);
+        let mut contents: Vec<u8> = vec![];
+        File::open(&path_2m).unwrap().read_to_end(&mut contents).unwrap();
 
-        let named_file = NamedFile::open(path_10m.clone()).unwrap();
+        let imf: InMemoryFile = InMemoryFile::open_and_release_page_cache(&path_2m).unwrap();
+        assert_eq!(imf.bytes.as_ref(), contents.as_slice());
+        assert_eq!(imf.stats.size, MEG2);
 
-        // expect the cache to get the item from the FS.
-        assert_eq!(cache.try_insert(path_10m), CachedFile::from(named_file));
+        assert_eq!(cache.get(&path_2m).into_bytes().unwrap(), contents);
+        assert!(cache.contains_key(&path_2m));
+    }
+
+    #[test]
+    fn explicit_content_type_overrides_extension() {
+        let cache: Cache = Cache::new(MEG1);
+        let client = Client::new(rocket::ignite()).unwrap();
+        let request = client.get("/");
+
+        let cached_file: CachedFile = cache
+            .get_or_compute_with_content_type("api/status", ContentType::JSON, || Ok(b"{\"status\": \"ok\"}".to_vec()))
+            .unwrap();
+        let mut response = cached_file.respond_to(request.inner()).unwrap();
+        assert_eq!(response.content_type(), Some(ContentType::JSON));
+        assert_eq!(response.body_bytes(), Some(b"{\"status\": \"ok\"}".to_vec()));
+
+        // The Content-Type is kept with the stored content.
+        let response = cache.get("api/status").respond_to(request.inner()).unwrap();
+        assert_eq!(response.content_type(), Some(ContentType::JSON));
+
+        let cached_file: CachedFile = cache
+            .get_or_compute_with_content_type("api/report.txt", ContentType::CSV, || Ok(b"a,b".to_vec()))
+            .unwrap();
+        let response = cached_file.respond_to(request.inner()).unwrap();
+        assert_eq!(response.content_type(), Some(ContentType::CSV));
     }
 
+    #[test]
+    fn replace_contents_swaps_every_file() {
+        let mut cache: Cache = Cache::new(MEG10);
+        let temp_dir = TempDir::new(DIR_TEST).unwrap();
+        let path_1m = create_test_file(&temp_dir, MEG1, FILE_MEG1);
+        let path_2m = create_test_file(&temp_dir, MEG2, FILE_MEG2);
+        let path_5m = create_test_file(&temp_dir, MEG5, FILE_MEG5);
+        cache.get(&path_1m);
+        cache.get(&path_2m);
+        cache.get(&path_2m);
+
+        let mut files: HashMap<PathBuf, InMemoryFile> = HashMap::new();
+        files.insert(path_2m.clone(), InMemoryFile::open(&path_2m).unwrap());
+        files.insert(path_5m.clone(), InMemoryFile::open(&path_5m).unwrap());
+        let replaced: Vec<(PathBuf, InMemoryFile)> = cache.replace_contents(files, true);
+
+        assert_eq!(replaced.len(), 2);
+        assert!(!cache.contains_key(&path_1m));
+        assert!(cache.contains_key(&path_2m));
+        assert!(cache.contains_key(&path_5m));
+        assert_eq!(cache.used_bytes(), MEG2 + MEG5);
+        assert_eq!(cache.top_accessed(1), vec![(path_2m.clone(), 2)]);
+
+        let mut files: HashMap<PathBuf, InMemoryFile> = HashMap::new();
+        files.insert(path_1m.clone(), InMemoryFile::open(&path_1m).unwrap());
+        cache.replace_contents(files, false);
+        assert_eq!(cache.used_bytes(), MEG1);
+        assert_eq!(cache.top_accessed(10), vec![(path_1m.clone(), 0)]);
+    }
 
     #[test]
-    fn file_replaces_other_file() {
+    fn directory_serves_index_file() {
+        let mut cache: Cache = Cache::new(MEG5);
         let temp_dir = TempDir::new(DIR_TEST).unwrap();
+        fs::create_dir(temp_dir.path().join("docs")).unwrap();
+        let path_index = create_test_file(&temp_dir, MEG1, "docs/index.html");
+        let mut contents: Vec<u8> = vec![];
+        File::open(&path_index).unwrap().read_to_end(&mut contents).unwrap();
+
+        cache.index_file = Some(String::from("index.html"));
+        assert_eq!(cache.get(temp_dir.path().join("docs")).into_bytes().unwrap(), contents);
+        assert!(cache.contains_key(&path_index));
+        assert!(!cache.contains_key(temp_dir.path().join("docs")));
+    }
 
+    #[test]
+    fn distinct_files_seen_grows_with_new_paths() {
+        let cache: Cache = Cache::new(MEG2);
+        let temp_dir = TempDir::new(DIR_TEST).unwrap();
         let path_1m = create_test_file(&temp_dir, MEG1, FILE_MEG1);
+        let path_2m = create_test_file(&temp_dir, MEG2, FILE_MEG2);
         let path_5m = create_test_file(&temp_dir, MEG5, FILE_MEG5);
+        assert_eq!(cache.distinct_files_seen(), 0);
 
-        let named_file_1m = NamedFile::open(path_1m.clone()).unwrap();
-        let named_file_1m_2 = NamedFile::open(path_1m.clone()).unwrap();
+        cache.get(&path_1m);
+        cache.get(&path_1m);
+        assert_eq!(cache.distinct_files_seen(), 1);
 
+        cache.get(&path_2m);
+        assert_eq!(cache.distinct_files_seen(), 2);
 
-        let mut imf_5m = InMemoryFile::open(path_5m.clone()).unwrap();
-        let mut imf_1m = InMemoryFile::open(path_1m.clone()).unwrap();
+        // The file is too large to be cached, but it is still part of the working set.
+        cache.touch(&path_5m, 1);
+        assert_eq!(cache.distinct_files_seen(), 3);
+    }
 
-        // set expected stats for 5m
-        imf_5m.stats.access_count = 1;
-        imf_5m.stats.priority = 2289;
+    #[test]
+    fn background_evictor_eventually_stores_files() {
+        let cache: Arc<Cache> = Arc::new(Cache::new(MEG5));
+        let temp_dir = TempDir::new(DIR_TEST).expect("Couldn't create temp dir");
+        let path_1m: PathBuf = create_test_file(&temp_dir, MEG1, FILE_MEG1);
+        Cache::spawn_background_evictor(&cache, 8);
+
+        match cache.get(&path_1m) {
+            CachedFile::FileSystem(_) => {}
+            _ => panic!("The file should be streamed while it waits to be stored."),
+        }
 
+        let deadline: Instant = Instant::now() + Duration::from_secs(5);
+        while !cache.contains_key(&path_1m) && Instant::now() < deadline {
+            thread::sleep(Duration::from_millis(10));
+        }
+        assert!(cache.contains_key(&path_1m));
+        assert_eq!(cache.used_bytes(), MEG1);
+    }
 
-        let cache: Cache = Cache::new(5500000); //Cache can hold only 5.5Mib
+    #[test]
+    fn case_insensitive_paths_share_an_entry() {
+        let mut cache: Cache = Cache::new(MEG5);
+        cache.case_insensitive = true;
+        let temp_dir = TempDir::new(DIR_TEST).expect("Couldn't create temp dir");
+        let path: PathBuf = create_test_file(&temp_dir, MEG1, "logo.png");
+        let upper_case_path: PathBuf = temp_dir.path().join("Logo.PNG");
+        assert!(!upper_case_path.exists());
+
+        match cache.get(&upper_case_path) {
+            CachedFile::InMemory(_) => {}
+            _ => panic!("The file should be found ignoring case."),
+        }
+        match cache.get(&path) {
+            CachedFile::InMemory(_) => {}
+            _ => panic!("The file should already be in the cache."),
+        }
+        assert_eq!(cache.file_map.iter().count(), 1);
+        assert_eq!(cache.used_bytes(), MEG1);
+        assert!(cache.contains_key(&path));
+    }
 
-        println!("0:\n{:#?}", cache);
+    #[test]
+    fn case_insensitive_match_is_forgotten_when_the_file_is_renamed_on_disk() {
+        let mut cache: Cache = Cache::new(MEG5);
+        cache.case_insensitive = true;
+        let temp_dir = TempDir::new(DIR_TEST).expect("Couldn't create temp dir");
+        let path: PathBuf = create_test_file(&temp_dir, MEG1, "logo.png");
+        let upper_case_path: PathBuf = temp_dir.path().join("LOGO.PNG");
+        assert!(cache.get(&upper_case_path).into_bytes().is_ok());
+        assert_eq!(cache.case_folded_paths.iter().count(), 1);
+
+        // Removing the file from the cache forgets its match, so the renamed file is found.
+        let renamed_path: PathBuf = temp_dir.path().join("Logo.png");
+        fs::rename(&path, &renamed_path).unwrap();
+        assert!(cache.remove(&path));
+        assert_eq!(cache.case_folded_paths.iter().count(), 0);
+        assert!(cache.get(&upper_case_path).into_bytes().is_ok());
+        assert!(cache.contains_key(&renamed_path));
+
+        // Once the file is evicted, a match that no longer exists on disk is forgotten and searched for again.
+        fs::rename(&renamed_path, &path).unwrap();
+        assert!(cache.evict(&renamed_path));
+        assert!(cache.get(&upper_case_path).into_bytes().is_ok());
+        assert!(cache.contains_key(&path));
+        assert_eq!(cache.case_folded_paths.iter().map(|x| x.1.clone()).collect::<Vec<PathBuf>>(), vec![path.clone()]);
+
+        // Invalidating the file forgets its match as well.
+        assert_eq!(cache.invalidate_prefix(&path, false), 1);
+        assert_eq!(cache.case_folded_paths.iter().count(), 0);
+    }
 
-        assert_eq!(
-            cache
-                .try_insert(path_5m.clone())
-                .get_in_memory_file()
-                .file
-                .as_ref()
-                .get(),
-            &imf_5m
-        );
-        println!("1:\n{:#?}", cache);
-        assert_eq!(
-            cache.try_insert(path_1m.clone()),
-            CachedFile::from(named_file_1m)
-        );
-        println!("2:\n{:#?}", cache);
-        assert_eq!(
-            cache.try_insert(path_1m.clone()),
-            CachedFile::from(named_file_1m_2)
-        );
-        println!("3:\n{:#?}", cache);
+    #[test]
+    fn memory_overhead_estimate_grows_with_entries() {
+        let cache: Cache = Cache::new(MEG10);
+        let temp_dir = TempDir::new(DIR_TEST).expect("Couldn't create temp dir");
+        let path_1m: PathBuf = create_test_file(&temp_dir, MEG1, FILE_MEG1);
+        let path_2m: PathBuf = create_test_file(&temp_dir, MEG2, FILE_MEG2);
+
+        cache.get(&path_1m);
+        let one_entry: usize = cache.memory_overhead_estimate();
+        assert!(one_entry > 0);
+
+        cache.get(&path_2m);
+        let two_entries: usize = cache.memory_overhead_estimate();
+        assert!(two_entries > one_entry);
+        assert!(two_entries < MEG1);
+    }
 
-        // set the expected stats for 1m
-        imf_1m.stats.access_count = 3;
-        imf_1m.stats.priority = 3072;
+    #[test]
+    fn only_compressible_types_are_served_compressed() {
+        let cache: Cache = Cache::new(MEG10);
+        let temp_dir = TempDir::new(DIR_TEST).unwrap();
+        let path_png = create_test_file(&temp_dir, 2048, "logo.png");
+        create_test_file(&temp_dir, 1024, "logo.png.gz");
+        let path_css = create_test_file(&temp_dir, 2048, "style.css");
+        let path_css_gz = create_test_file(&temp_dir, 1024, "style.css.gz");
+        let client = Client::new(rocket::ignite()).unwrap();
+
+        let request = client.get("/").header(Header::new("Accept-Encoding", "gzip"));
+        let mut response = PrecompressedFile::open(&path_png, &cache).respond_to(request.inner()).unwrap();
+        assert_eq!(response.headers().get_one("Content-Encoding"), None);
+        assert_eq!(response.content_type(), Some(ContentType::PNG));
+        assert_eq!(response.body_bytes(), Some(InMemoryFile::open(&path_png).unwrap().bytes.to_vec()));
+
+        let mut response = PrecompressedFile::open(&path_css, &cache).respond_to(request.inner()).unwrap();
+        assert_eq!(response.headers().get_one("Content-Encoding"), Some("gzip"));
+        assert_eq!(response.content_type(), Some(ContentType::CSS));
+        assert_eq!(response.body_bytes(), Some(InMemoryFile::open(&path_css_gz).unwrap().bytes.to_vec()));
+    }
 
-        assert_eq!(
-            cache
-                .try_insert(path_1m.clone())
-                .get_in_memory_file()
-                .file
-                .as_ref()
-                .get(),
-            &imf_1m
-        );
-        println!("4:\n{:#?}", cache);
+    #[test]
+    fn range_request_is_not_served_compressed() {
+        let cache: Cache = Cache::new(MEG10);
+        let temp_dir = TempDir::new(DIR_TEST).unwrap();
+        let path_css = create_test_file(&temp_dir, 2048, "style.css");
+        create_test_file(&temp_dir, 1024, "style.css.gz");
+        let client = Client::new(rocket::ignite()).unwrap();
+
+        let request = client.get("/")
+            .header(Header::new("Accept-Encoding", "gzip"))
+            .header(Header::new("Range", "bytes=100-199"));
+        let mut response = PrecompressedFile::open(&path_css, &cache).respond_to(request.inner()).unwrap();
+        assert_eq!(response.status(), Status::PartialContent);
+        assert_eq!(response.headers().get_one("Content-Encoding"), None);
+        assert_eq!(response.headers().get_one("Vary"), Some("Accept-Encoding"));
+        assert_eq!(response.headers().get_one("Content-Range"), Some("bytes 100-199/2048"));
+        assert_eq!(response.body_bytes(), Some(InMemoryFile::open(&path_css).unwrap().bytes[100..200].to_vec()));
     }
 
+    #[test]
+    fn modern_formats_have_content_types() {
+        let cache: Cache = Cache::new(MEG10);
+        let temp_dir = TempDir::new(DIR_TEST).unwrap();
+        let path_wasm = create_test_file(&temp_dir, 2048, "app.wasm");
+        let path_avif = create_test_file(&temp_dir, 2048, "photo.avif");
+        let client = Client::new(rocket::ignite()).unwrap();
+        let request = client.get("/");
 
+        let response = cache.get(&path_wasm).respond_to(request.inner()).unwrap();
+        assert_eq!(response.content_type(), Some(ContentType::new("application", "wasm")));
+        let response = cache.get(&path_avif).respond_to(request.inner()).unwrap();
+        assert_eq!(response.content_type(), Some(ContentType::new("image", "avif")));
 
+        // Files served from the filesystem get the same Content-Types.
+        let cache: Cache = Cache::new(0);
+        let response = cache.get(&path_avif).respond_to(request.inner()).unwrap();
+        assert_eq!(response.content_type(), Some(ContentType::new("image", "avif")));
+    }
 
     #[test]
-    fn new_file_replaces_lowest_priority_file() {
+    fn serve_language_variant() {
+        let cache: Cache = Cache::new(MEG10);
         let temp_dir = TempDir::new(DIR_TEST).unwrap();
-        let path_1m = create_test_file(&temp_dir, MEG1, FILE_MEG1);
-        let path_2m = create_test_file(&temp_dir, MEG2, FILE_MEG2);
-        let path_5m = create_test_file(&temp_dir, MEG5, FILE_MEG5);
+        let path_index = temp_dir.path().join("index.html");
+        let path_en = create_test_file(&temp_dir, 1024, "index.en.html");
+        let path_fr = create_test_file(&temp_dir, 2048, "index.fr.html");
+        cache.add_language_variant(&path_index, "en", &path_en);
+        cache.add_language_variant(&path_index, "fr", &path_fr);
+        let client = Client::new(rocket::ignite()).unwrap();
+
+        let request = client.get("/").header(Header::new("Accept-Language", "fr"));
+        let mut response = LocalizedFile::open(&path_index, &cache).respond_to(request.inner()).unwrap();
+        assert_eq!(response.headers().get_one("Content-Language"), Some("fr"));
+        assert_eq!(response.headers().get_one("Vary"), Some("Accept-Language"));
+        assert_eq!(response.content_type(), Some(ContentType::HTML));
+        assert_eq!(response.body_bytes(), Some(InMemoryFile::open(&path_fr).unwrap().bytes.to_vec()));
+        assert!(cache.contains_key(&path_fr));
+        assert!(!cache.contains_key(&path_en));
+
+        let request = client.get("/").header(Header::new("Accept-Language", "fr-CA;q=0.5, en-GB"));
+        let response = LocalizedFile::open(&path_index, &cache).respond_to(request.inner()).unwrap();
+        assert_eq!(response.headers().get_one("Content-Language"), Some("en"));
+
+        // Clients that accept none of the variants get the first one registered.
+        let request = client.get("/").header(Header::new("Accept-Language", "de"));
+        let mut response = LocalizedFile::open(&path_index, &cache).respond_to(request.inner()).unwrap();
+        assert_eq!(response.headers().get_one("Content-Language"), Some("en"));
+        assert_eq!(response.body_bytes(), Some(InMemoryFile::open(&path_en).unwrap().bytes.to_vec()));
+
+        // Multibyte characters in a language range don't match any variant.
+        let request = client.get("/").header(Header::new("Accept-Language", "féx, fé-x"));
+        let response = LocalizedFile::open(&path_index, &cache).respond_to(request.inner()).unwrap();
+        assert_eq!(response.headers().get_one("Content-Language"), Some("en"));
+    }
 
+    #[test]
+    fn warm_from_dump_round_trip() {
+        let active: Cache = Cache::new(MEG10);
+        let temp_dir = TempDir::new(DIR_TEST).expect("Couldn't create temp dir");
+        let path_1m: PathBuf = create_test_file(&temp_dir, MEG1, FILE_MEG1);
+        let path_2m: PathBuf = create_test_file(&temp_dir, MEG2, FILE_MEG2);
+        let path_5m: PathBuf = create_test_file(&temp_dir, MEG5, FILE_MEG5);
+        active.get(&path_1m);
+        active.get(&path_1m);
+        active.get(&path_2m);
+        active.get(&path_2m);
+        active.get(&path_5m);
+
+        let mut dump: Vec<u8> = Vec::new();
+        assert_eq!(active.dump_hottest(2, &mut dump).unwrap(), 2);
+
+        // The files are removed from the filesystem, so they can only come from the dump.
+        drop(temp_dir);
+        let standby: Cache = Cache::new(MEG10);
+        assert_eq!(standby.warm_from_dump(dump.as_slice()).unwrap(), 2);
+        assert!(standby.contains_key(&path_1m));
+        assert!(standby.contains_key(&path_2m));
+        assert!(!standby.contains_key(&path_5m));
+        assert_eq!(standby.used_bytes(), MEG1 + MEG2);
+
+        let standby_1m = standby.get(&path_1m).into_bytes().unwrap();
+        let active_1m = active.get(&path_1m).into_bytes().unwrap();
+        assert_eq!(standby_1m, active_1m);
+
+        assert!(standby.warm_from_dump(&b"not a dump"[..]).is_err());
+    }
 
-        #[allow(unused_variables)]
-        let named_file_1m = NamedFile::open(path_1m.clone()).unwrap();
+    #[test]
+    fn warm_from_dump_respects_cache_constraints() {
+        let active: Cache = Cache::new(MEG10);
+        let temp_dir = TempDir::new(DIR_TEST).expect("Couldn't create temp dir");
+        let path_1m: PathBuf = create_test_file(&temp_dir, MEG1, FILE_MEG1);
+        let path_2m: PathBuf = create_test_file(&temp_dir, MEG2, FILE_MEG2);
+        active.get(&path_1m);
+        active.get(&path_2m);
+        let mut dump: Vec<u8> = Vec::new();
+        assert_eq!(active.dump_hottest(2, &mut dump).unwrap(), 2);
+
+        let mut small_files_only: Cache = Cache::new(MEG10);
+        small_files_only.max_file_size = MEG1;
+        assert_eq!(small_files_only.warm_from_dump(dump.as_slice()).unwrap(), 1);
+        assert!(small_files_only.contains_key(&path_1m));
+        assert!(!small_files_only.contains_key(&path_2m));
+
+        let mut large_files_only: Cache = Cache::new(MEG10);
+        large_files_only.min_file_size = MEG2;
+        assert_eq!(large_files_only.warm_from_dump(dump.as_slice()).unwrap(), 1);
+        assert!(large_files_only.contains_key(&path_2m));
+
+        let mut excluding: Cache = Cache::new(MEG10);
+        let excluded: PathBuf = path_1m.clone();
+        excluding.should_cache = Some(Box::new(move |path: &Path, _: usize| path != excluded));
+        assert_eq!(excluding.warm_from_dump(dump.as_slice()).unwrap(), 1);
+        assert!(!excluding.contains_key(&path_1m));
+        assert!(excluding.contains_key(&path_2m));
+    }
 
-        let cache: Cache = Cache::new(MEG1 * 7 + 2000); // cache can hold a little more than 7MB
+    #[test]
+    fn dump_with_corrupted_length_is_rejected() {
+        let cache: Cache = Cache::new(MEG10);
+        let mut dump: Vec<u8> = b"RFCDUMP1".to_vec();
+        // The path claims to be far longer than the dump, and longer than could ever be allocated.
+        dump.extend_from_slice(&[0xFF; 8]);
+        dump.extend_from_slice(b"index.html");
+
+        let error: io::Error = cache.warm_from_dump(dump.as_slice()).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::UnexpectedEof);
+        assert_eq!(cache.used_bytes(), 0);
+    }
 
-        println!("1:\n{:#?}", cache);
-        let mut imf_5m: InMemoryFile = InMemoryFile::open(path_5m.clone()).unwrap();
-        imf_5m.stats.priority = 2289;
-        imf_5m.stats.access_count = 1;
+    #[test]
+    fn with_entry_mut_appends_and_updates_size() {
+        let cache: Cache = Cache::new(MEG10);
+        let temp_dir = TempDir::new(DIR_TEST).expect("Couldn't create temp dir");
+        let path_1m: PathBuf = create_test_file(&temp_dir, MEG1, FILE_MEG1);
+        cache.get(&path_1m);
+        let etag: String = cache.file_map.find(&path_1m).unwrap().get().etag().to_string();
+
+        assert!(cache.with_entry_mut(&path_1m, |bytes| bytes.extend_from_slice(&[0u8; 1024])));
+        assert_eq!(cache.used_bytes(), MEG1 + 1024);
+        assert_eq!(cache.file_map.find(&path_1m).unwrap().get().stats.size, MEG1 + 1024);
+        assert_ne!(cache.file_map.find(&path_1m).unwrap().get().etag(), etag.as_str());
+
+        // The bytes are modified in their own buffer, and the file keeps the time it was read.
+        let buffer: *const u8 = cache.file_map.find(&path_1m).unwrap().get().bytes.as_ptr();
+        let read_at: Instant = cache.file_map.find(&path_1m).unwrap().get().read_at;
+        assert!(cache.with_entry_mut(&path_1m, |bytes| bytes.truncate(MEG1)));
+        assert_eq!(cache.file_map.find(&path_1m).unwrap().get().bytes.as_ptr(), buffer);
+        assert_eq!(cache.file_map.find(&path_1m).unwrap().get().read_at, read_at);
+        assert_eq!(cache.file_map.find(&path_1m).unwrap().get().etag(), etag.as_str());
+        assert_eq!(cache.used_bytes(), MEG1);
+
+        // The bytes can't be modified while another holder shares them.
+        let shared = cache.file_map.find(&path_1m).unwrap().get().clone();
+        assert!(!cache.with_entry_mut(&path_1m, |bytes| bytes.clear()));
+        assert_eq!(cache.used_bytes(), MEG1);
+        drop(shared);
+
+        assert!(!cache.with_entry_mut(&PathBuf::from("missing.log"), |bytes| bytes.clear()));
+    }
 
-        assert_eq!(
-            cache.get(&path_5m)
-               .get_in_memory_file()
-               .file
-               .as_ref()
-               .get(),
-            &imf_5m
-        );
+    #[test]
+    fn equal_priorities_are_evicted_oldest_first() {
+        let cache: Cache = Cache::new(MEG10);
+        let temp_dir = TempDir::new(DIR_TEST).expect("Couldn't create temp dir");
+        let path_b: PathBuf = create_test_file(&temp_dir, MEG1, "b.txt");
+        let path_c: PathBuf = create_test_file(&temp_dir, MEG1, "c.txt");
+        let path_a: PathBuf = create_test_file(&temp_dir, MEG1, "a.txt");
+        let path_d: PathBuf = create_test_file(&temp_dir, MEG1, "d.txt");
+
+        for path in vec![&path_b, &path_c, &path_a] {
+            cache.get(path);
+            thread::sleep(Duration::from_millis(10));
+        }
+        // A file that has never been gotten has no last access time, so it is evicted before the others.
+        let mut file_d: InMemoryFile = InMemoryFile::open(&path_d).unwrap();
+        file_d.stats.access_count = 1;
+        cache.extend(vec![(path_d.clone(), file_d)]);
+
+        let mut order: Vec<PathBuf> = cache.eviction_order().into_iter().map(|x| x.0).collect();
+        order.reverse();
+        assert_eq!(order, vec![path_d, path_b, path_c, path_a]);
+    }
 
-        println!("2:\n{:#?}", cache);
-        let mut imf_2m: InMemoryFile = InMemoryFile::open(path_2m.clone()).unwrap();
-        imf_2m.stats.priority = 1448;
-        imf_2m.stats.access_count = 1;
-        assert_eq!(
-            cache.get(&path_2m)
-                .get_in_memory_file()
-                .file
-                .as_ref()
-                .get(),
-            &imf_2m
-        );
+    #[test]
+    fn etag_is_calculated_while_reading() {
+        let temp_dir = TempDir::new(DIR_TEST).expect("Couldn't create temp dir");
+        let path_1m: PathBuf = create_test_file(&temp_dir, MEG1 + 17, FILE_MEG1);
+        let mut bytes: Vec<u8> = Vec::new();
+        File::open(&path_1m).unwrap().read_to_end(&mut bytes).unwrap();
+
+        let file: InMemoryFile = InMemoryFile::open(&path_1m).unwrap();
+        assert_eq!(file.etag(), format!("\"{:016x}\"", checksum(&bytes)));
+        assert_eq!(file.etag(), InMemoryFile::from_bytes(bytes).etag());
+    }
 
+    #[test]
+    fn fairing_serves_files_from_managed_cache() {
+        let temp_dir = TempDir::new(DIR_TEST).expect("Couldn't create temp dir");
+        let path_1m: PathBuf = create_test_file(&temp_dir, MEG1, FILE_MEG1);
+        let rocket = rocket::ignite().attach(Cache::new(MEG5).fairing("/static", temp_dir.path()));
+        let client = Client::new(rocket).unwrap();
+
+        let mut response = client.get(format!("/static/{}", FILE_MEG1)).dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        assert_eq!(response.body_bytes(), Some(InMemoryFile::open(&path_1m).unwrap().bytes.to_vec()));
+
+        let response = client.get("/static/missing.txt").dispatch();
+        assert_eq!(response.status(), Status::NotFound);
+    }
 
-        println!("3:\n{:#?}", cache);
-        let mut named_1m = NamedFile::open(path_1m.clone()).unwrap();
-        let mut v: Vec<u8> = Vec::new();
-        let _ = cache
-            .get(&path_1m)
-            .get_named_file()
-            .read_to_end(&mut v)
-            .unwrap();
+    #[test]
+    fn state_fairing_manages_cache_without_mounting_a_route() {
+        use rocket::{Data, Route, State};
+        use rocket::handler::Outcome;
+        use rocket::http::Method;
+
+        fn serve_1m<'r>(request: &'r Request, _: Data) -> Outcome<'r> {
+            let cache: State<'r, Cache> = request.guard::<State<Cache>>().succeeded().unwrap();
+            let root: State<'r, PathBuf> = request.guard::<State<PathBuf>>().succeeded().unwrap();
+            Outcome::from(request, cache.inner().get(root.inner().join(FILE_MEG1)))
+        }
 
-        let mut file_vec: Vec<u8> = Vec::new();
-        let _ = named_1m.read_to_end(&mut file_vec);
-        assert_eq!(
-            v,
-            file_vec
-        );
+        let temp_dir = TempDir::new(DIR_TEST).expect("Couldn't create temp dir");
+        let path_1m: PathBuf = create_test_file(&temp_dir, MEG1, FILE_MEG1);
+        let rocket = rocket::ignite()
+            .attach(Cache::new(MEG5).state_fairing())
+            .manage(temp_dir.path().to_path_buf());
+        let client = Client::new(rocket).unwrap();
+
+        // No route was mounted by the fairing.
+        let response = client.get(format!("/{}", FILE_MEG1)).dispatch();
+        assert_eq!(response.status(), Status::NotFound);
+
+        let rocket = rocket::ignite()
+            .attach(Cache::new(MEG5).state_fairing())
+            .manage(temp_dir.path().to_path_buf())
+            .mount("/", vec![Route::new(Method::Get, "/1m", serve_1m)]);
+        let client = Client::new(rocket).unwrap();
+        let mut response = client.get("/1m").dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        assert_eq!(response.body_bytes(), Some(InMemoryFile::open(&path_1m).unwrap().bytes.to_vec()));
+    }
 
+    #[test]
+    fn access_count_increments_with_each_get() {
+        let cache: Cache = Cache::new(MEG5);
+        let temp_dir = TempDir::new(DIR_TEST).expect("Couldn't create temp dir");
+        let path_1m: PathBuf = create_test_file(&temp_dir, MEG1, FILE_MEG1);
+        let path_10m: PathBuf = create_test_file(&temp_dir, MEG10, FILE_MEG10);
+        assert_eq!(cache.access_count(&path_1m), 0);
+
+        for expected in 1..4 {
+            cache.get(&path_1m);
+            assert_eq!(cache.access_count(&path_1m), expected);
+        }
 
-        println!("4:\n{:#?}", cache);
-        let mut imf_1m: InMemoryFile = InMemoryFile::open(path_1m.clone()).unwrap();
-        imf_1m.stats.priority = 2048; // This priority is higher than the in memory file - 2m's 1448, and therefore will replace it now
-        imf_1m.stats.access_count = 1;
+        // Files that are too large to be cached are still counted.
+        cache.get(&path_10m);
+        cache.get(&path_10m);
+        assert_eq!(cache.access_count(&path_10m), 2);
+    }
 
-        // The cache will now accept the 1 meg file because (sqrt(2)_size * 1_access) for the old
-        // file is less than (sqrt(1)_size * 2_access) for the new file.
-        assert_eq!(
-            cache.get(&path_1m)
-                .get_in_memory_file()
-                .file
-                .as_ref()
-                .get()
-                .bytes,
-            imf_1m.bytes
-        );
-        println!("5:\n{:#?}", cache);
+    #[test]
+    fn zero_size_limit_caches_nothing() {
+        let mut cache: Cache = Cache::new(0);
+        cache.priority_function = |access_count: usize, _: usize| access_count;
+        let temp_dir = TempDir::new(DIR_TEST).expect("Couldn't create temp dir");
+        let path_1m: PathBuf = create_test_file(&temp_dir, MEG1, FILE_MEG1);
+        let path_empty: PathBuf = create_test_file(&temp_dir, 0, "empty.txt");
+
+        for _ in 0..3 {
+            match cache.get(&path_1m) {
+                CachedFile::FileSystem(_) => {}
+                _ => panic!("The file should be read from the filesystem."),
+            }
+            match cache.get(&path_empty) {
+                CachedFile::FileSystem(_) => {}
+                _ => panic!("Even an empty file should be read from the filesystem."),
+            }
+        }
+        assert_eq!(cache.file_map.iter().count(), 0);
+        assert_eq!(cache.access_count(&path_1m), 3);
 
+        assert_eq!(cache.force_store(path_empty.clone(), InMemoryFile::open(&path_empty).unwrap()), Err(CacheInvalidationError::FileIsLargerThanSizeLimit));
+        assert!(cache.get_or_compute(PathBuf::from("generated.txt"), || Ok(vec![])).is_err());
+        assert_eq!(cache.file_map.iter().count(), 0);
+    }
 
-        if let CachedFile::NotFound = cache.get_from_cache(&path_1m) {
-            panic!("Expected 1m file to be in the cache");
+    #[test]
+    fn trim_to_evicts_lowest_priority_files() {
+        let cache: Cache = Cache::new(MEG5);
+        let temp_dir = TempDir::new(DIR_TEST).expect("Couldn't create temp dir");
+        let path_1m: PathBuf = create_test_file(&temp_dir, MEG1, FILE_MEG1);
+        let path_2m: PathBuf = create_test_file(&temp_dir, MEG2, FILE_MEG2);
+        let path_2m_b: PathBuf = create_test_file(&temp_dir, MEG2, "meg2_b");
+        cache.get(&path_1m);
+        cache.get(&path_1m);
+        cache.get(&path_1m);
+        cache.get(&path_2m);
+        cache.get(&path_2m);
+        cache.get(&path_2m_b);
+        assert_eq!(cache.used_bytes(), MEG5);
+
+        let freed: usize = cache.trim_to(MEG2 + MEG1);
+        assert_eq!(freed, MEG2);
+        assert_eq!(cache.used_bytes(), MEG2 + MEG1);
+        assert!(cache.contains_key(&path_2m));
+        assert!(cache.contains_key(&path_1m));
+        assert!(!cache.contains_key(&path_2m_b));
+
+        assert_eq!(cache.trim_to(MEG10), 0);
+        assert_eq!(cache.trim_to(0), MEG2 + MEG1);
+        assert_eq!(cache.used_bytes(), 0);
+    }
+
+    #[test]
+    fn background_refresher_serves_stale_file_until_refreshed() {
+        let mut cache: Cache = Cache::new(MEG5);
+        cache.accesses_per_refresh = Some(2);
+        let cache: Arc<Cache> = Arc::new(cache);
+        let temp_dir = TempDir::new(DIR_TEST).expect("Couldn't create temp dir");
+        let path_1m: PathBuf = create_test_file(&temp_dir, MEG1, FILE_MEG1);
+        let stale_bytes: Vec<u8> = cache.get(&path_1m).into_bytes().unwrap();
+        Cache::spawn_background_refresher(&cache, 8);
+
+        create_test_file(&temp_dir, MEG1, FILE_MEG1);
+        let fresh_bytes: Vec<u8> = InMemoryFile::open(&path_1m).unwrap().bytes.to_vec();
+        assert_ne!(stale_bytes, fresh_bytes);
+
+        // The second access is due for a refresh, but the stale file is served while it happens.
+        assert_eq!(cache.get(&path_1m).into_bytes().unwrap(), stale_bytes);
+
+        let deadline: Instant = Instant::now() + Duration::from_secs(5);
+        while cache.file_map.find(&path_1m).unwrap().get().bytes.to_vec() != fresh_bytes && Instant::now() < deadline {
+            thread::sleep(Duration::from_millis(10));
         }
+        assert_eq!(cache.get(&path_1m).into_bytes().unwrap(), fresh_bytes);
+    }
 
-        // Check if the 5m file is still in the cache
-        if let CachedFile::NotFound = cache.get_from_cache(&path_5m) {
-            panic!("Expected 5m file to be in the cache");
+    #[test]
+    fn iter_borrows_entries() {
+        let cache: Cache = Cache::new(MEG10);
+        let temp_dir = TempDir::new(DIR_TEST).expect("Couldn't create temp dir");
+        let path_1m: PathBuf = create_test_file(&temp_dir, MEG1, FILE_MEG1);
+        let path_2m: PathBuf = create_test_file(&temp_dir, MEG2, FILE_MEG2);
+        let path_5m: PathBuf = create_test_file(&temp_dir, MEG5, FILE_MEG5);
+        cache.get(&path_1m);
+        cache.get(&path_2m);
+        cache.get(&path_5m);
+
+        let mut paths: Vec<PathBuf> = cache.iter().map(|(path, _)| path.clone()).collect();
+        paths.sort();
+        let mut expected: Vec<PathBuf> = vec![path_1m.clone(), path_2m, path_5m];
+        expected.sort();
+        assert_eq!(paths, expected);
+        assert_eq!(cache.iter().map(|(_, file)| file.stats.size).sum::<usize>(), cache.used_bytes());
+
+        // The keys are borrowed from the cache's map, rather than being copies of them.
+        let map_keys: Vec<*const PathBuf> = cache.file_map.iter().map(|(path, _)| path as *const PathBuf).collect();
+        for (path, _) in cache.iter() {
+            assert!(map_keys.contains(&(path as *const PathBuf)));
         }
 
-        //
-        if let CachedFile::InMemory(_) = cache.get_from_cache(&path_2m) {
-            panic!("Expected 2m file to not be in the cache");
+        // The bytes are shared with the cache instead of being copied.
+        let shared: Arc<Vec<u8>> = cache.iter()
+            .find(|&(path, _)| *path == path_1m)
+            .map(|(_, file)| file.bytes().clone())
+            .unwrap();
+        assert_eq!(Arc::strong_count(&shared), 2);
+    }
+
+    #[test]
+    fn bytes_are_moved_into_in_memory_file_without_copying() {
+        let bytes: Vec<u8> = vec![7u8; MEG1];
+        let buffer: *const u8 = bytes.as_ptr();
+        let file: InMemoryFile = InMemoryFile::from_bytes(bytes);
+        // The file holds the same allocation that was read into, rather than a second full-size copy of it.
+        assert_eq!(file.bytes().as_ptr(), buffer);
+        assert_eq!(file.stats.size, MEG1);
+    }
+
+    #[test]
+    fn ttl_remaining_counts_down() {
+        let mut cache: Cache = Cache::new(MEG5);
+        let temp_dir = TempDir::new(DIR_TEST).expect("Couldn't create temp dir");
+        let path_1m: PathBuf = create_test_file(&temp_dir, MEG1, FILE_MEG1);
+        cache.get(&path_1m);
+        assert_eq!(cache.ttl_remaining(&path_1m), None);
+
+        cache.ttl = Some(Duration::from_secs(60));
+        thread::sleep(Duration::from_millis(50));
+        let remaining: Duration = cache.ttl_remaining(&path_1m).unwrap();
+        assert!(remaining <= Duration::from_millis(59950));
+        assert!(remaining > Duration::from_secs(50));
+        assert_eq!(cache.ttl_remaining(&PathBuf::from("missing.txt")), None);
+
+        // Once the ttl has passed, the file is read again the next time it is gotten.
+        cache.ttl = Some(Duration::from_millis(10));
+        assert_eq!(cache.ttl_remaining(&path_1m), Some(Duration::from_secs(0)));
+        let new_path_1m: PathBuf = create_test_file(&temp_dir, MEG2, FILE_MEG1);
+        cache.get(&new_path_1m);
+        assert_eq!(cache.used_bytes(), MEG2);
+        cache.ttl = Some(Duration::from_secs(60));
+        assert!(cache.ttl_remaining(&path_1m).unwrap() > Duration::from_secs(50));
+    }
+
+    #[test]
+    fn failed_read_is_not_cached() {
+        /// A reader that fails after producing some of its bytes.
+        struct FailingReader {
+            remaining: usize,
         }
 
-        drop(cache);
+        impl Read for FailingReader {
+            fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+                if self.remaining == 0 {
+                    return Err(io::Error::new(io::ErrorKind::Other, "The mount was lost."));
+                }
+                let read: usize = cmp::min(self.remaining, buf.len());
+                self.remaining -= read;
+                Ok(read)
+            }
+        }
+
+        let result = InMemoryFile::read_from(FailingReader { remaining: MEG1 }, MEG2);
+        assert_eq!(result.unwrap_err().kind(), io::ErrorKind::Other);
+
+        let cache: Cache = Cache::new(MEG10);
+        let key: PathBuf = PathBuf::from("mounted/file.bin");
+        let result = cache.get_or_compute_file(&key, || InMemoryFile::read_from(FailingReader { remaining: MEG1 }, MEG2));
+        assert!(result.is_err());
+        assert!(!cache.contains_key(&key));
+        assert_eq!(cache.used_bytes(), 0);
     }
 
+    #[test]
+    fn cached_file_from_bytes_infers_content_type() {
+        let client = Client::new(rocket::ignite()).unwrap();
+        let request = client.get("/");
+        let bytes: Vec<u8> = b"{\"status\": \"ok\"}".to_vec();
+
+        let cached_file: CachedFile = CachedFile::from_bytes(PathBuf::from("api/status.json"), bytes.clone());
+        let mut response = cached_file.respond_to(request.inner()).unwrap();
+        assert_eq!(response.status(), Status::Ok);
+        assert_eq!(response.content_type(), Some(ContentType::JSON));
+        assert_eq!(response.headers().get_one("ETag"), Some(InMemoryFile::from_bytes(bytes.clone()).etag()));
+        assert_eq!(response.body_bytes(), Some(bytes));
+    }
 
+    #[test]
+    fn priorities_saturate_instead_of_wrapping() {
+        let huge_access_count: usize = usize::MAX / 2 + 1;
+        assert_eq!(default_priority_function(huge_access_count, MEG1), usize::MAX);
+        assert_eq!(normal_priority_function(huge_access_count, 4), usize::MAX);
+        assert_eq!(small_files_access_priority_function(huge_access_count, 2), usize::MAX);
+
+        let cache: Cache = Cache::new(MEG5);
+        let temp_dir = TempDir::new(DIR_TEST).expect("Couldn't create temp dir");
+        let path_1m: PathBuf = create_test_file(&temp_dir, MEG1, FILE_MEG1);
+        cache.get(&path_1m);
+        cache.touch(&path_1m, usize::MAX);
+        cache.get(&path_1m);
+        assert_eq!(cache.access_count(&path_1m), usize::MAX);
+        assert_eq!(cache.priority_of(&path_1m), Some(usize::MAX));
+    }
 
+    #[test]
+    fn no_cache_request_rereads_file() {
+        let cache: Cache = Cache::new(MEG5);
+        let temp_dir = TempDir::new(DIR_TEST).expect("Couldn't create temp dir");
+        let path_1m: PathBuf = create_test_file(&temp_dir, MEG1, FILE_MEG1);
+        let client = Client::new(rocket::ignite()).unwrap();
+
+        let request = client.get("/");
+        let stale_bytes: Vec<u8> = cache.get_for_request(&path_1m, request.inner()).into_bytes().unwrap();
+        assert_eq!(cache.get_for_request(&path_1m, request.inner()).into_bytes().unwrap(), stale_bytes);
+        assert_eq!(cache.stats().files_read, 1);
+
+        create_test_file(&temp_dir, MEG1, FILE_MEG1);
+        let fresh_bytes: Vec<u8> = InMemoryFile::open(&path_1m).unwrap().bytes.to_vec();
+
+        let request = client.get("/").header(Header::new("Cache-Control", "max-age=0, no-cache"));
+        assert_eq!(cache.get_for_request(&path_1m, request.inner()).into_bytes().unwrap(), fresh_bytes);
+        assert_eq!(cache.stats().files_read, 2);
+
+        // The refreshed copy is served to requests that don't ask for fresh content.
+        let request = client.get("/");
+        assert_eq!(cache.get_for_request(&path_1m, request.inner()).into_bytes().unwrap(), fresh_bytes);
+        assert_eq!(cache.stats().files_read, 2);
+    }
 
     #[test]
-    fn remove_file() {
-        let cache: Cache = Cache::new(MEG1 * 10);
-        let temp_dir = TempDir::new(DIR_TEST).unwrap();
-        let path_5m = create_test_file(&temp_dir, MEG5, FILE_MEG5);
+    fn eviction_waste_is_recorded() {
+        let cache: Cache = Cache::new(MEG5);
+        let temp_dir = TempDir::new(DIR_TEST).expect("Couldn't create temp dir");
+        let path_a: PathBuf = create_test_file(&temp_dir, MEG2, "meg2_a");
+        let path_b: PathBuf = create_test_file(&temp_dir, MEG2, "meg2_b");
+        let path_c: PathBuf = create_test_file(&temp_dir, MEG2, "meg2_c");
+        cache.get(&path_a);
+        cache.get(&path_b);
+        assert_eq!(cache.stats().eviction_waste_bytes, 0);
+
+        // Storing the third file requires 1 MB, but a whole 2 MB file has to be removed for it.
+        cache.touch(&path_c, 10);
+        cache.get(&path_c);
+        assert!(cache.contains_key(&path_c));
+        assert_eq!(cache.stats().evictions, 1);
+        assert_eq!(cache.stats().eviction_waste_bytes, MEG1 as u64);
+    }
 
-        let mut imf: InMemoryFile = InMemoryFile::open(path_5m.clone()).unwrap();
+    #[test]
+    fn new_with_named_priority() {
+        let cache: Cache = Cache::new_with_named_priority(MEG10, "balanced").unwrap();
+        assert_eq!(cache.priority_function as usize, default_priority_function as usize);
+        let cache: Cache = Cache::new_with_named_priority(MEG10, "access").unwrap();
+        assert_eq!(cache.priority_function as usize, access_priority_function as usize);
+        assert_eq!(cache.size_limit(), MEG10);
+    }
 
-        // Set the expected values for the stats in IMF.
-        imf.stats.priority = 2289;
-        imf.stats.access_count = 1;
+    #[test]
+    fn new_with_unknown_priority_name() {
+        match Cache::new_with_named_priority(MEG10, "fastest") {
+            Err(error) => assert_eq!(error, UnknownPriorityFunction("fastest".to_string())),
+            Ok(_) => panic!("A cache was created with an unknown priority function."),
+        }
+    }
 
-        // expect the cache to get the item from the FS.
-        assert_eq!(
-            cache
-                .get(&path_5m)
-                .get_in_memory_file()
-                .file
-                .as_ref()
-                .get(),
-            &imf
-        );
+    #[test]
+    fn concurrent_reads_are_limited() {
+        let mut cache: Cache = Cache::new(MEG10 * 2);
+        cache.max_concurrent_reads = Some(2);
+        let cache: Arc<Cache> = Arc::new(cache);
+        let temp_dir = TempDir::new(DIR_TEST).expect("Couldn't create temp dir");
+        let paths: Vec<PathBuf> = (0..16)
+            .map(|i| create_test_file(&temp_dir, MEG1, &format!("{}_1mib.txt", i)))
+            .collect();
 
-        cache.remove(&path_5m);
+        let handles: Vec<thread::JoinHandle<()>> = paths.iter()
+            .cloned()
+            .map(|path| {
+                let cache: Arc<Cache> = cache.clone();
+                thread::spawn(move || {
+                    cache.get(&path).dummy_write();
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
 
-        assert_eq!(cache.contains_key(&path_5m.clone()), false);
+        let peak: usize = cache.read_limiter.peak.load(Ordering::Relaxed);
+        assert!(peak >= 1 && peak <= 2, "{} files were read at once", peak);
+        assert_eq!(cache.stats().files_read, 16);
+        assert_eq!(*cache.read_limiter.reading.lock().unwrap(), 0);
+    }
+
+    #[test]
+    fn prometheus_metrics() {
+        let cache: Cache = Cache::new(MEG5);
+        let temp_dir = TempDir::new(DIR_TEST).expect("Couldn't create temp dir");
+        let path_2m_a: PathBuf = create_test_file(&temp_dir, MEG2, "a.txt");
+        let path_2m_b: PathBuf = create_test_file(&temp_dir, MEG2, "b.txt");
+        let path_2m_c: PathBuf = create_test_file(&temp_dir, MEG2, "c.txt");
+        cache.get(&path_2m_a);
+        cache.get(&path_2m_a);
+        cache.get(&path_2m_b);
+        cache.touch(&path_2m_c, 10);
+        cache.get(&path_2m_c);
+
+        let metrics: String = cache.prometheus_metrics("file_cache");
+        let lines: Vec<&str> = metrics.lines().collect();
+        assert!(lines.contains(&"# TYPE file_cache_hits_total counter"));
+        assert!(lines.contains(&"file_cache_hits_total 1"));
+        assert!(lines.contains(&"file_cache_misses_total 3"));
+        assert!(lines.contains(&"file_cache_evictions_total 1"));
+        assert!(lines.contains(&"# TYPE file_cache_used_bytes gauge"));
+        assert!(lines.contains(&format!("file_cache_used_bytes {}", MEG2 * 2).as_str()));
+        assert!(lines.contains(&format!("file_cache_size_limit_bytes {}", MEG5).as_str()));
+        assert!(lines.contains(&"file_cache_files 2"));
+        assert!(metrics.ends_with('\n'));
     }
 
     #[test]
@@ -1224,9 +7429,8 @@ mod tests {
 
         assert_eq!(
             match cache.get(&path_5m) {
-                CachedFile::InMemory(c) => c.file.get().stats.size,
-                CachedFile::FileSystem(_) => unreachable!(),
-                CachedFile::NotFound => unreachable!()
+                CachedFile::InMemory(c) => c.file.stats.size,
+                _ => unreachable!(),
             },
             MEG5
         );
@@ -1238,9 +7442,8 @@ mod tests {
 
         assert_eq!(
             match cache.get(&path_of_file_with_10mb_but_path_name_5m) {
-                CachedFile::InMemory(c) => c.file.get().stats.size,
-                CachedFile::FileSystem(_) => unreachable!(),
-                CachedFile::NotFound => unreachable!()
+                CachedFile::InMemory(c) => c.file.stats.size,
+                _ => unreachable!(),
             },
             MEG10
         );
@@ -1248,4 +7451,88 @@ mod tests {
         drop(cache);
     }
 
+    /// A backend that holds its files in a locked map, and counts how often files are gotten from it
+    /// and how often its files are walked to sum their sizes.
+    #[derive(Default)]
+    struct MockBackend {
+        files: Mutex<HashMap<PathBuf, InMemoryFile>>,
+        gets: AtomicUsize,
+        size_walks: AtomicUsize,
+    }
+
+    impl CacheBackend for MockBackend {
+        fn get(&self, path: &Path) -> Option<InMemoryFile> {
+            self.gets.fetch_add(1, Ordering::Relaxed);
+            self.files.lock().unwrap().get(path).cloned()
+        }
+
+        fn insert(&self, path: PathBuf, file: InMemoryFile) -> Option<InMemoryFile> {
+            self.files.lock().unwrap().insert(path, file)
+        }
+
+        fn remove(&self, path: &Path) -> Option<InMemoryFile> {
+            self.files.lock().unwrap().remove(path)
+        }
+
+        fn size_bytes(&self) -> usize {
+            self.size_walks.fetch_add(1, Ordering::Relaxed);
+            self.files.lock().unwrap().values().map(|file| file.stats.size).sum()
+        }
+
+        fn entries(&self) -> Vec<(PathBuf, InMemoryFile)> {
+            self.files.lock().unwrap().iter().map(|(path, file)| (path.clone(), file.clone())).collect()
+        }
+    }
+
+    #[test]
+    fn files_are_held_in_the_given_backend() {
+        let cache: Cache<MockBackend> = Cache::with_backend(MEG10, MockBackend::default());
+        let temp_dir = TempDir::new(DIR_TEST).expect("Couldn't create temp dir");
+        let path_1m: PathBuf = create_test_file(&temp_dir, MEG1, FILE_MEG1);
+        let path_2m: PathBuf = create_test_file(&temp_dir, MEG2, FILE_MEG2);
+
+        cache.get(&path_1m);
+        cache.get(&path_2m);
+        assert_eq!(cache.file_map.files.lock().unwrap().len(), 2);
+        assert_eq!(cache.used_bytes(), MEG1 + MEG2);
+
+        let gets: usize = cache.file_map.gets.load(Ordering::Relaxed);
+        let cached_file: NamedInMemoryFile = cache.get(&path_1m).get_in_memory_file();
+        assert!(cache.file_map.gets.load(Ordering::Relaxed) > gets);
+        assert_eq!(cached_file.file.bytes, InMemoryFile::open(&path_1m).unwrap().bytes);
+
+        // The stats of the file are updated through the backend.
+        assert_eq!(cache.file_map.files.lock().unwrap()[&path_1m].stats.access_count, 2);
+
+        assert!(cache.remove(&path_1m));
+        assert!(!cache.file_map.files.lock().unwrap().contains_key(&path_1m));
+        assert_eq!(cache.used_bytes(), MEG2);
+        assert_eq!(cache.stats().file_count, 1);
+    }
+
+    #[test]
+    fn used_bytes_are_counted_without_walking_the_backend() {
+        let cache: Cache<MockBackend> = Cache::with_backend(MEG2, MockBackend::default());
+        let temp_dir = TempDir::new(DIR_TEST).expect("Couldn't create temp dir");
+        let path_1m: PathBuf = create_test_file(&temp_dir, MEG1, FILE_MEG1);
+        let path_2m: PathBuf = create_test_file(&temp_dir, MEG2, FILE_MEG2);
+        let size_walks: usize = cache.file_map.size_walks.load(Ordering::Relaxed);
+
+        cache.get(&path_1m);
+        cache.get(&path_1m);
+        // Storing this file would require evicting the first one, which has been accessed more.
+        cache.get(&path_2m);
+        assert!(cache.rename(&path_1m, PathBuf::from("renamed.txt")));
+        assert_eq!(cache.used_bytes(), MEG1);
+
+        cache.get(&path_2m);
+        cache.get(&path_2m);
+        assert!(cache.contains_key(&path_2m));
+        assert_eq!(cache.used_bytes(), MEG2);
+        assert!(cache.remove(&path_2m));
+        assert_eq!(cache.used_bytes(), 0);
+
+        assert_eq!(cache.file_map.size_walks.load(Ordering::Relaxed), size_walks);
+    }
+
 }
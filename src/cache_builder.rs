@@ -1,11 +1,23 @@
-use cache::{Cache, AgeOut};
+use cache::{Cache, AgeOut, EvictionPolicy, ResponseTimer, ReadLimiter};
+use backend::CacheBackend;
 
 use priority_function::default_priority_function;
 use std::usize;
 
 use concurrent_hashmap::{ConcHashMap, Options};
 use std::collections::hash_map::RandomState;
-use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::{AtomicUsize, AtomicU64};
+use std::sync::Mutex;
+use std::collections::HashMap;
+#[cfg(feature = "rocket")]
+use rocket::http::{ContentType, Header};
+use in_memory_file::InMemoryFile;
+use std::path::{Path, PathBuf};
+use checksum::ChecksumMismatch;
+use std::time::Duration;
+use std::fmt;
+#[cfg(feature = "rocket")]
+use precompressed_file::default_compressible_types;
 
 
 
@@ -13,6 +25,16 @@ use std::sync::atomic::AtomicUsize;
 #[derive(Debug, PartialEq)]
 pub enum CacheBuildError {
     MinFileSizeIsLargerThanMaxFileSize,
+    LowWatermarkIsLargerThanHighWatermark,
+}
+
+/// A function given to the builder, which is formatted without its contents so the builder can still be debugged.
+struct BoxedFn<F: ?Sized>(Box<F>);
+
+impl<F: ?Sized> fmt::Debug for BoxedFn<F> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "BoxedFn")
+    }
 }
 
 /// A builder for Caches.
@@ -24,7 +46,37 @@ pub struct CacheBuilder {
     priority_function: Option<fn(usize, usize) -> usize>,
     min_file_size: Option<usize>,
     max_file_size: Option<usize>,
-    age_out: Option<AgeOut>
+    max_single_file_fraction: Option<f64>,
+    age_out: Option<AgeOut>,
+    high_watermark: Option<usize>,
+    low_watermark: Option<usize>,
+    reserve_fraction: Option<f64>,
+    max_ages: HashMap<String, usize>,
+    default_max_age: Option<usize>,
+    #[cfg(feature = "rocket")]
+    default_content_type: Option<ContentType>,
+    #[cfg(feature = "rocket")]
+    headers: Vec<Header<'static>>,
+    #[cfg(feature = "rocket")]
+    compressible_types: Option<Vec<ContentType>>,
+    #[cfg(feature = "rocket")]
+    max_range_multiple: Option<usize>,
+    zstd_level: Option<i32>,
+    compression_dictionary: Option<Vec<u8>>,
+    extension_weights: HashMap<String, f64>,
+    on_evict: Option<BoxedFn<dyn Fn(&PathBuf, &InMemoryFile) + Send + Sync>>,
+    passthrough: bool,
+    index_file: Option<String>,
+    case_insensitive: bool,
+    eviction_policy: Option<EvictionPolicy>,
+    eviction_seed: Option<u64>,
+    on_checksum_mismatch: Option<ChecksumMismatch>,
+    log_evictions: bool,
+    release_page_cache: bool,
+    should_cache: Option<BoxedFn<dyn Fn(&Path, usize) -> bool + Send + Sync>>,
+    negative_cache_ttl: Option<Duration>,
+    ttl: Option<Duration>,
+    max_concurrent_reads: Option<usize>,
 }
 
 
@@ -41,7 +93,37 @@ impl CacheBuilder {
             priority_function: None,
             min_file_size: None,
             max_file_size: None,
+            max_single_file_fraction: None,
             age_out: None,
+            high_watermark: None,
+            low_watermark: None,
+            reserve_fraction: None,
+            max_ages: HashMap::new(),
+            default_max_age: None,
+            #[cfg(feature = "rocket")]
+            default_content_type: None,
+            #[cfg(feature = "rocket")]
+            headers: vec![],
+            #[cfg(feature = "rocket")]
+            compressible_types: None,
+            #[cfg(feature = "rocket")]
+            max_range_multiple: None,
+            zstd_level: None,
+            compression_dictionary: None,
+            extension_weights: HashMap::new(),
+            on_evict: None,
+            passthrough: false,
+            index_file: None,
+            case_insensitive: false,
+            eviction_policy: None,
+            eviction_seed: None,
+            on_checksum_mismatch: None,
+            log_evictions: false,
+            release_page_cache: false,
+            should_cache: None,
+            negative_cache_ttl: None,
+            ttl: None,
+            max_concurrent_reads: None,
         }
     }
 
@@ -65,9 +147,13 @@ impl CacheBuilder {
         self
     }
 
-    /// Sets the concurrency setting of the concurrent hashmap backing the cache.
+    /// Sets the concurrency setting of the concurrent hashmaps backing the cache.
     /// A higher concurrency setting allows more threads to access the hashmap at the expense of more memory use.
     /// The default is 16.
+    ///
+    /// Both the files and their access counts are split by the hash of their paths into this many
+    /// separately locked shards, so recording accesses to different files will rarely contend for the same lock.
+    /// If the cache is built with another backend, only its access counts are split.
     pub fn concurrency<'a>(&'a mut self, concurrency: u16) -> &mut Self {
         self.concurrency = Some(concurrency);
         self
@@ -107,10 +193,16 @@ impl CacheBuilder {
     /// added.
     /// If there isn't room in the cache for two files, the one with the lower score will be removed /
     /// won't be added.
+    /// A score of 0 vetoes caching: a file the function scores as 0 will never be stored, even in an empty cache.
+    /// Note that the default function scores empty files as 0, so they are never stored.
     ///
     /// The priority function should be kept simple, as it is calculated on every file in the cache
     /// every time a new file is attempted to be added.
     ///
+    /// Access counts can grow very large on a long running server, so the function should use
+    /// saturating arithmetic, like the built in priority functions do. A multiplication that wraps
+    /// around would give a very popular file a very low priority.
+    ///
     /// # Example
     ///
     /// ```
@@ -118,7 +210,7 @@ impl CacheBuilder {
     /// use rocket_file_cache::CacheBuilder;
     /// let cache: Cache = CacheBuilder::new(1024 * 1024 * 50) // 50 MB cache
     ///     .priority_function(|access_count, size| {
-    ///         access_count * access_count * size
+    ///         access_count.saturating_mul(access_count).saturating_mul(size)
     ///     })
     ///     .build()
     ///     .unwrap();
@@ -140,6 +232,275 @@ impl CacheBuilder {
         self
     }
 
+    /// Set the largest fraction of the size limit that a single file may occupy.
+    /// Files larger than this fraction are served from the filesystem and are never stored,
+    /// so a single large file can't keep many smaller files out of the cache.
+    ///
+    /// # Panics
+    /// This function will panic if the fraction is not between 0.0 (exclusive) and 1.0 (inclusive).
+    pub fn max_single_file_fraction<'a>(&'a mut self, max_single_file_fraction: f64) -> &mut Self {
+        if max_single_file_fraction <= 0.0 || max_single_file_fraction > 1.0 {
+            panic!("Incorrectly configured max_single_file_fraction. Values must be between 0.0 and 1.0.");
+        } else {
+            self.max_single_file_fraction = Some(max_single_file_fraction);
+        }
+        self
+    }
+
+    /// Set the number of used bytes that, once exceeded, will cause the cache to evict files
+    /// until its usage falls to the low watermark.
+    ///
+    /// Evicting many files in one pass keeps subsequent inserts from having to search for files
+    /// to remove when the cache is nearly full.
+    /// Both the high and low watermarks must be set for this eviction to occur.
+    pub fn high_watermark<'a>(&'a mut self, high_watermark: usize) -> &mut Self {
+        self.high_watermark = Some(high_watermark);
+        self
+    }
+
+    /// Set the number of used bytes the cache will evict down to once the high watermark has been crossed.
+    pub fn low_watermark<'a>(&'a mut self, low_watermark: usize) -> &mut Self {
+        self.low_watermark = Some(low_watermark);
+        self
+    }
+
+    /// Sets the fraction of the size limit that the cache will try to keep free.
+    /// While the cache's usage is below the reserve, new files will be stored without having to
+    /// calculate which files should be removed to make room for them.
+    /// Once the reserve is reached, files will be removed to keep the reserve free, although files
+    /// larger than the reserve allows can still be stored as long as they fit within the size limit.
+    /// The default is 0.0.
+    ///
+    /// # Panics
+    /// This function will panic if the fraction is not between 0.0 (inclusive) and 1.0 (exclusive).
+    pub fn reserve_fraction<'a>(&'a mut self, reserve_fraction: f64) -> &mut Self {
+        if reserve_fraction < 0.0 || reserve_fraction >= 1.0 {
+            panic!("Incorrectly configured reserve_fraction. Values must be between 0.0 and 1.0.");
+        } else {
+            self.reserve_fraction = Some(reserve_fraction);
+        }
+        self
+    }
+
+    /// Sets the number of seconds that clients may cache files with the given extension.
+    /// This is used to set the `Cache-Control: max-age` header when files are served from the cache.
+    ///
+    /// # Arguments
+    /// * extension - The extension of the files, without a leading period. Extensions are matched case-insensitively.
+    /// * max_age - The number of seconds the files may be cached by clients.
+    pub fn max_age<'a>(&'a mut self, extension: &str, max_age: usize) -> &mut Self {
+        self.max_ages.insert(extension.trim_start_matches('.').to_lowercase(), max_age);
+        self
+    }
+
+    /// Sets a multiplier that is applied to the priority scores of files with the given extension,
+    /// so files of some types are preferred over others with the same access counts and sizes.
+    ///
+    /// # Arguments
+    /// * extension - The extension of the files, without a leading period. Extensions are matched case-insensitively.
+    /// * weight - The multiplier applied to the priority scores of the files.
+    ///
+    /// # Panics
+    /// This function will panic if the weight is negative.
+    pub fn extension_weight<'a>(&'a mut self, extension: &str, weight: f64) -> &mut Self {
+        if weight < 0.0 {
+            panic!("Incorrectly configured extension weight. Values must not be negative.");
+        } else {
+            self.extension_weights.insert(extension.trim_start_matches('.').to_lowercase(), weight);
+        }
+        self
+    }
+
+    /// Sets the number of seconds that clients may cache files whose extensions don't have their own max-age.
+    /// By default, the Cache-Control header will not be set for those files.
+    pub fn default_max_age<'a>(&'a mut self, max_age: usize) -> &mut Self {
+        self.default_max_age = Some(max_age);
+        self
+    }
+
+    /// Sets the Content-Type used when serving files whose extensions are missing or unrecognized,
+    /// such as `ContentType::Binary` (application/octet-stream).
+    /// By default, the Content-Type header will not be set for those files.
+    #[cfg(feature = "rocket")]
+    pub fn default_content_type<'a>(&'a mut self, content_type: ContentType) -> &mut Self {
+        self.default_content_type = Some(content_type);
+        self
+    }
+
+    /// Sets the Content-Types that `PrecompressedFile` will serve from compressed sidecar files,
+    /// replacing the default set. A subtype of `*` matches every subtype, such as `text/*`.
+    /// Files of other types, such as images that are already compressed, are always served uncompressed.
+    /// By default, this contains `text/*`, JSON, JavaScript, XML, SVG and WebAssembly.
+    #[cfg(feature = "rocket")]
+    pub fn compressible_types<'a>(&'a mut self, compressible_types: Vec<ContentType>) -> &mut Self {
+        self.compressible_types = Some(compressible_types);
+        self
+    }
+
+    /// Sets the most bytes that a Range header can request from a file served from memory, as a multiple of the file's size.
+    /// Ranges may overlap, so a single request could otherwise ask for many times the file's size.
+    /// Requests for more bytes are answered with 416 Range Not Satisfiable.
+    /// The default is 1, so no request can ask for more bytes than the file has.
+    #[cfg(feature = "rocket")]
+    pub fn max_range_multiple<'a>(&'a mut self, max_range_multiple: usize) -> &mut Self {
+        self.max_range_multiple = Some(max_range_multiple);
+        self
+    }
+
+    /// Sets the level that `PrecompressedFile` compresses files in the cache with, for clients that accept zstd.
+    /// Levels range from 1, the fastest, to 22, which produces the smallest files; 0 selects zstd's default level.
+    /// Each file is only compressed once while it is in the cache, so higher levels are usually worth their cost.
+    /// By default, the cache doesn't compress files itself, and only serves `.zst` sidecar files.
+    pub fn zstd_level<'a>(&'a mut self, level: i32) -> &mut Self {
+        self.zstd_level = Some(level);
+        self
+    }
+
+    /// Sets a zstd dictionary, such as one trained with `zstd --train` on samples of the files, that files are
+    /// compressed with when a `zstd_level` is set. Dictionaries greatly improve the compression of small,
+    /// similar files, such as JSON fragments.
+    /// Files compressed with a dictionary can only be decoded by clients that have the same dictionary.
+    /// By default, files are compressed without a dictionary.
+    pub fn compression_dictionary<'a>(&'a mut self, dictionary: Vec<u8>) -> &mut Self {
+        self.compression_dictionary = Some(dictionary);
+        self
+    }
+
+    /// Adds a header that will be set on the responses of every file served from the cache,
+    /// such as `X-Content-Type-Options: nosniff`.
+    /// Headers registered for a specific file using `Cache::set_headers()` take precedence over this header.
+    #[cfg(feature = "rocket")]
+    pub fn header<'a>(&'a mut self, header: Header<'static>) -> &mut Self {
+        self.headers.push(header);
+        self
+    }
+
+    /// Sets a function that will be given the path and contents of each file that is evicted to make
+    /// room for other files, just before the file is dropped.
+    /// This can be used to write the file's bytes elsewhere before they are lost.
+    /// The function may capture state, such as a connection to where the files are written.
+    pub fn on_evict<'a, F>(&'a mut self, on_evict: F) -> &mut Self
+        where F: Fn(&PathBuf, &InMemoryFile) + Send + Sync + 'static
+    {
+        self.on_evict = Some(BoxedFn(Box::new(on_evict)));
+        self
+    }
+
+    /// Sets whether the cache should serve every file from the filesystem without storing it.
+    /// This allows changes to files to be seen immediately during development, without changing
+    /// the code that gets files from the cache.
+    /// The default is false.
+    pub fn passthrough<'a>(&'a mut self, passthrough: bool) -> &mut Self {
+        self.passthrough = passthrough;
+        self
+    }
+
+    /// Sets the name of the file that is served when a directory is gotten from the cache, such as `index.html`.
+    /// The file is stored in the cache under its own path, inside the directory.
+    /// By default, getting a directory returns `CachedFile::NotFound`.
+    pub fn index_file<'a>(&'a mut self, index_file: &str) -> &mut Self {
+        self.index_file = Some(String::from(index_file));
+        self
+    }
+
+    /// Sets whether paths that only differ by case refer to the same file, even on case-sensitive filesystems.
+    /// Each file is stored under the path it has on disk, so every casing of a path shares a single entry.
+    /// The default is false.
+    pub fn case_insensitive<'a>(&'a mut self, case_insensitive: bool) -> &mut Self {
+        self.case_insensitive = case_insensitive;
+        self
+    }
+
+    /// Sets the policy used to choose which files are removed when room must be made for new files.
+    /// The default is `EvictionPolicy::LowestPriority`.
+    pub fn eviction_policy<'a>(&'a mut self, eviction_policy: EvictionPolicy) -> &mut Self {
+        self.eviction_policy = Some(eviction_policy);
+        self
+    }
+
+    /// Sets the seed of the random number generator used by the `EvictionPolicy::WeightedRandom` policy.
+    /// Using the same seed will cause the same files to be chosen for removal, which is useful for testing.
+    /// By default, the seed is taken from the system clock.
+    pub fn eviction_seed<'a>(&'a mut self, seed: u64) -> &mut Self {
+        self.eviction_seed = Some(seed);
+        self
+    }
+
+    /// Sets what happens when a file streamed from the filesystem doesn't match the checksum
+    /// registered for it with `Cache::set_expected_checksum()`.
+    /// The default is `ChecksumMismatch::Abort`.
+    pub fn on_checksum_mismatch<'a>(&'a mut self, on_mismatch: ChecksumMismatch) -> &mut Self {
+        self.on_checksum_mismatch = Some(on_mismatch);
+        self
+    }
+
+    /// Sets whether the cache should log its decisions about which files to evict at the debug level.
+    /// This allows eviction to be inspected for one cache without enabling the logs of every cache.
+    /// The default is false.
+    pub fn log_evictions<'a>(&'a mut self, log_evictions: bool) -> &mut Self {
+        self.log_evictions = log_evictions;
+        self
+    }
+
+    /// Sets whether the operating system should be advised to drop files from its page cache once
+    /// they have been read into the cache, so large files aren't held in memory twice.
+    /// This only has an effect on Linux. By default, the page cache is left alone.
+    pub fn release_page_cache<'a>(&'a mut self, release_page_cache: bool) -> &mut Self {
+        self.release_page_cache = release_page_cache;
+        self
+    }
+
+    /// Sets a function that decides if a file may be stored in the cache, given its path and size.
+    /// Files it returns false for will always be streamed from the filesystem, even if they would fit in the cache.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use rocket_file_cache::{Cache, CacheBuilder};
+    /// use std::path::Path;
+    ///
+    /// let private_dir: &'static str = "www/private";
+    /// let cache: Cache = CacheBuilder::new()
+    ///     .should_cache(move |path: &Path, _size: usize| !path.starts_with(private_dir))
+    ///     .build()
+    ///     .unwrap();
+    /// ```
+    pub fn should_cache<'a, F>(&'a mut self, should_cache: F) -> &mut Self
+        where F: Fn(&Path, usize) -> bool + Send + Sync + 'static
+    {
+        self.should_cache = Some(BoxedFn(Box::new(should_cache)));
+        self
+    }
+
+    /// Sets how long paths that couldn't be found are remembered as missing.
+    /// While a path is remembered, getting it returns `CachedFile::NotFound` without checking the filesystem,
+    /// so repeatedly probing for files that don't exist is cheap.
+    /// Files created at a remembered path won't be found until the time has passed.
+    /// By default, missing paths aren't remembered.
+    pub fn negative_cache_ttl<'a>(&'a mut self, ttl: Duration) -> &mut Self {
+        self.negative_cache_ttl = Some(ttl);
+        self
+    }
+
+    /// Sets how long a file can be served from the cache after it was read, before it is read again.
+    /// Files that have outlived the ttl are refreshed the next time they are gotten, and removed
+    /// if they can no longer be found.
+    /// By default, files are never refreshed because of their age.
+    pub fn ttl<'a>(&'a mut self, ttl: Duration) -> &mut Self {
+        self.ttl = Some(ttl);
+        self
+    }
+
+    /// Sets the most files that can be read from the filesystem into the cache at once.
+    /// When that many files are being read, cache misses wait for one of the reads to finish
+    /// before reading their own file, so a burst of misses on a cold cache won't saturate the disk.
+    /// A limit of 0 is treated as 1.
+    /// By default, the number of concurrent reads isn't limited.
+    pub fn max_concurrent_reads<'a>(&'a mut self, max_concurrent_reads: usize) -> &mut Self {
+        self.max_concurrent_reads = Some(max_concurrent_reads);
+        self
+    }
+
     /// Finalize the cache.
     ///
     /// # Example
@@ -155,6 +516,34 @@ impl CacheBuilder {
     ///     .unwrap();
     /// ```
     pub fn build(self) -> Result<Cache, CacheBuildError> {
+        let mut options_files_map: Options<RandomState> = Options::default();
+        if let Some(conc) = self.concurrency {
+            options_files_map.concurrency = conc;
+        }
+        self.build_with_backend(ConcHashMap::with_options(options_files_map))
+    }
+
+    /// Finalize the cache, holding its files in the given backend instead of a concurrent hashmap.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # extern crate concurrent_hashmap;
+    /// # extern crate rocket_file_cache;
+    ///
+    /// # fn main() {
+    /// use concurrent_hashmap::ConcHashMap;
+    /// use rocket_file_cache::{Cache, CacheBuilder, InMemoryFile};
+    /// use std::path::PathBuf;
+    ///
+    /// let backend: ConcHashMap<PathBuf, InMemoryFile> = ConcHashMap::new();
+    /// let cache: Cache<ConcHashMap<PathBuf, InMemoryFile>> = CacheBuilder::new()
+    ///     .size_limit(1024 * 1024 * 50)
+    ///     .build_with_backend(backend)
+    ///     .unwrap();
+    /// # }
+    /// ```
+    pub fn build_with_backend<B: CacheBackend>(self, backend: B) -> Result<Cache<B>, CacheBuildError> {
 
         let size_limit: usize = match self.size_limit {
             Some(s) => s,
@@ -177,6 +566,14 @@ impl CacheBuilder {
             }
         }
 
+        if let Some(high_watermark) = self.high_watermark {
+            if let Some(low_watermark) = self.low_watermark {
+                if low_watermark > high_watermark {
+                    return Err(CacheBuildError::LowWatermarkIsLargerThanHighWatermark);
+                }
+            }
+        }
+
         let min_file_size: usize = match self.min_file_size {
             Some(min) => min,
             None => 0,
@@ -189,24 +586,77 @@ impl CacheBuilder {
 
 
 
-        let mut options_files_map: Options<RandomState> = Options::default();
         let mut options_access_map: Options<RandomState> = Options::default();
 
         if let Some(conc) = self.concurrency {
-            options_files_map.concurrency = conc;
             options_access_map.concurrency = conc;
         }
 
 
+        let stored_bytes: usize = backend.size_bytes();
         Ok(Cache {
-            size_limit: size_limit,
+            size_limit: AtomicUsize::new(size_limit),
             min_file_size,
             max_file_size,
+            max_single_file_fraction: self.max_single_file_fraction,
             priority_function,
             age_out: self.age_out,
             accesses_per_refresh: self.accesses_per_refresh,
-            file_map: ConcHashMap::with_options(options_files_map),
+            ttl: self.ttl,
+            max_concurrent_reads: self.max_concurrent_reads,
+            high_watermark: self.high_watermark,
+            low_watermark: self.low_watermark,
+            reserve_fraction: self.reserve_fraction.unwrap_or(0.0),
+            max_ages: self.max_ages,
+            default_max_age: self.default_max_age,
+            #[cfg(feature = "rocket")]
+            default_content_type: self.default_content_type,
+            #[cfg(feature = "rocket")]
+            headers: self.headers,
+            #[cfg(feature = "rocket")]
+            compressible_types: self.compressible_types.unwrap_or_else(default_compressible_types),
+            #[cfg(feature = "rocket")]
+            max_range_multiple: self.max_range_multiple.unwrap_or(1),
+            zstd_level: self.zstd_level,
+            compression_dictionary: self.compression_dictionary,
+            extension_weights: self.extension_weights,
+            on_evict: self.on_evict.map(|on_evict| on_evict.0),
+            passthrough: self.passthrough,
+            index_file: self.index_file,
+            case_insensitive: self.case_insensitive,
+            eviction_policy: self.eviction_policy.unwrap_or(EvictionPolicy::LowestPriority),
+            on_checksum_mismatch: self.on_checksum_mismatch.unwrap_or(ChecksumMismatch::Abort),
+            log_evictions: self.log_evictions,
+            release_page_cache: self.release_page_cache,
+            should_cache: self.should_cache.map(|should_cache| should_cache.0),
+            negative_cache_ttl: self.negative_cache_ttl,
+            file_map: backend,
+            stored_bytes: AtomicUsize::new(stored_bytes),
             access_count_map: ConcHashMap::with_options(options_access_map),
+            last_access_map: ConcHashMap::new(),
+            evictions: AtomicU64::new(0),
+            eviction_waste_bytes: AtomicU64::new(0),
+            expected_checksums: ConcHashMap::new(),
+            missing: ConcHashMap::new(),
+            missing_inserts: AtomicUsize::new(0),
+            case_folded_paths: ConcHashMap::new(),
+            pinned: ConcHashMap::new(),
+            #[cfg(feature = "rocket")]
+            path_headers: ConcHashMap::new(),
+            #[cfg(feature = "rocket")]
+            language_variants: ConcHashMap::new(),
+            #[cfg(feature = "rocket")]
+            zstd_sources: ConcHashMap::new(),
+            files_read: AtomicU64::new(0),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+            in_flight: Mutex::new(HashMap::new()),
+            background_inserts: Mutex::new(None),
+            background_refreshes: Mutex::new(None),
+            random_state: Mutex::new(self.eviction_seed.unwrap_or_else(Cache::<B>::clock_seed)),
+            response_timer: ResponseTimer::new(),
+            read_limiter: ReadLimiter::new(),
+            archive: None,
         })
 
     }
@@ -228,14 +678,51 @@ mod tests {
     }
 
     #[test]
+    fn low_watermark_greater_than_high_watermark() {
+        let e: CacheBuildError = CacheBuilder::new()
+            .high_watermark(1024 * 1024 * 4)
+            .low_watermark(1024 * 1024 * 5)
+            .build()
+            .unwrap_err();
+        assert_eq!(CacheBuildError::LowWatermarkIsLargerThanHighWatermark, e);
+    }
+
+    #[test]
+    #[cfg(feature = "rocket")]
     fn all_options_used_in_build() {
         let _: Cache = CacheBuilder::new()
             .size_limit(1024 * 1024 * 20)
             .priority_function(|access_count: usize, size: usize| access_count * size)
             .max_file_size(1024 * 1024 * 10)
             .min_file_size(1024 * 10)
+            .max_single_file_fraction(0.5)
             .concurrency(20)
             .accesses_per_refresh(1000)
+            .high_watermark(1024 * 1024 * 18)
+            .low_watermark(1024 * 1024 * 12)
+            .reserve_fraction(0.1)
+            .max_age("png", 60 * 60 * 24 * 365)
+            .extension_weight("png", 2.0)
+            .default_max_age(60)
+            .default_content_type(ContentType::Binary)
+            .header(Header::new("X-Content-Type-Options", "nosniff"))
+            .compressible_types(vec![ContentType::new("text", "*"), ContentType::JSON])
+            .max_range_multiple(2)
+            .zstd_level(19)
+            .on_evict(|_, _| {})
+            .should_cache(|path: &Path, _| !path.starts_with("private"))
+            .compression_dictionary(b"{\"id\": 1}".to_vec())
+            .passthrough(false)
+            .index_file("index.html")
+            .case_insensitive(true)
+            .eviction_policy(EvictionPolicy::WeightedRandom)
+            .eviction_seed(42)
+            .on_checksum_mismatch(ChecksumMismatch::Log)
+            .log_evictions(true)
+            .release_page_cache(true)
+            .negative_cache_ttl(Duration::from_secs(5))
+            .ttl(Duration::from_secs(60))
+            .max_concurrent_reads(8)
             .build()
             .unwrap();
     }
@@ -1,10 +1,21 @@
+#[cfg(feature = "rocket")]
 use rocket::http::Status;
-use rocket::response::{Response, Responder, NamedFile};
+#[cfg(feature = "rocket")]
+use rocket::response::{Response, Responder};
+#[cfg(feature = "rocket")]
 use rocket::request::Request;
 use cache::Cache;
-use std::path::Path;
+use named_file::NamedFile;
+use std::path::{Path, PathBuf};
+use std::io;
+use std::io::Read;
 
 use named_in_memory_file::NamedInMemoryFile;
+use owned_in_memory_file::OwnedInMemoryFile;
+use in_memory_file::InMemoryFile;
+use verified_file::VerifiedFile;
+use streamed_file::StreamedFile;
+use checksum::{checksum, ChecksumMismatch};
 
 
 /// Wrapper around data that can represent a file - either in memory (cache), or on disk.
@@ -12,13 +23,21 @@ use named_in_memory_file::NamedInMemoryFile;
 /// When getting a `CachedFile` from the cache:
 /// * An `InMemory` variant indicates that the file was read into the cache and a reference to that file is attached to the variant.
 /// * A `FileSystem` variant indicates that the file is not in the cache, but it can be accessed from the filesystem.
+/// * A `Verified` variant indicates that the file is not in the cache, and will be checked against its expected checksum as it is streamed from the filesystem.
 /// * A `NotFound` variant indicates that the file can not be found in the filesystem or the cache.
+///
+/// An `Owned` variant is never gotten from the cache, but is created by `CachedFile::from_bytes()`
+/// for content that isn't backed by the cache.
 #[derive(Debug)]
 pub enum CachedFile<'a> {
     /// A file that has been loaded into the cache.
     InMemory(NamedInMemoryFile<'a>),
     /// A file that exists in the filesystem.
-    FileSystem(NamedFile),
+    FileSystem(StreamedFile),
+    /// A file that exists in the filesystem, and has an expected checksum.
+    Verified(VerifiedFile),
+    /// A file in memory that isn't stored in the cache.
+    Owned(OwnedInMemoryFile),
     /// The file does not exist in either the cache or the filesystem.
     NotFound
 }
@@ -32,6 +51,118 @@ impl<'a> CachedFile<'a> {
     pub fn open<P: AsRef<Path>>(path: P, cache: &'a Cache) -> CachedFile<'a> {
         cache.get(path)
     }
+
+    /// Creates a file from bytes that aren't backed by the cache, so routes can respond with a `CachedFile`
+    /// whether or not the content came from the cache.
+    ///
+    /// The file is served the same way as a file from the cache, with the Content-Type implied by the
+    /// path's extension and an ETag calculated from the bytes.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use rocket_file_cache::CachedFile;
+    /// use std::path::PathBuf;
+    ///
+    /// let file: CachedFile = CachedFile::from_bytes(PathBuf::from("status.json"), b"{\"ok\": true}".to_vec());
+    /// assert_eq!(file.into_bytes().unwrap(), b"{\"ok\": true}".to_vec());
+    /// ```
+    pub fn from_bytes(path: PathBuf, bytes: Vec<u8>) -> CachedFile<'static> {
+        CachedFile::Owned(OwnedInMemoryFile::new(path, InMemoryFile::from_bytes(bytes)))
+    }
+
+    /// Gets the contents of the file, as they would be served in the body of a response.
+    ///
+    /// Files in memory have their bytes copied, while files in the filesystem are read.
+    /// This allows the contents to be used without responding to a request, such as in tests.
+    ///
+    /// # Return
+    ///
+    /// The bytes of the file, or an error if the file couldn't be found or read.
+    /// If the file doesn't match its expected checksum, and the mismatch should abort the response,
+    /// an error is returned as well.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use rocket_file_cache::{Cache, CachedFile};
+    ///
+    /// let cache = Cache::new(1024 * 1024 * 30);
+    /// assert!(CachedFile::open("www/missing.html", &cache).into_bytes().is_err());
+    /// ```
+    pub fn into_bytes(self) -> io::Result<Vec<u8>> {
+        match self {
+            CachedFile::InMemory(cached_file) => Ok(cached_file.file.bytes.to_vec()),
+            CachedFile::Owned(owned_file) => Ok(owned_file.bytes().to_vec()),
+            CachedFile::FileSystem(streamed_file) => {
+                let mut bytes: Vec<u8> = vec![];
+                streamed_file.take_file().read_to_end(&mut bytes)?;
+                Ok(bytes)
+            }
+            CachedFile::Verified(verified_file) => {
+                let expected_checksum: u64 = verified_file.expected_checksum;
+                let on_mismatch: ChecksumMismatch = verified_file.on_mismatch;
+                let mut bytes: Vec<u8> = vec![];
+                verified_file.file.take_file().read_to_end(&mut bytes)?;
+                if checksum(&bytes) != expected_checksum {
+                    error!("The file does not match its expected checksum.");
+                    if on_mismatch == ChecksumMismatch::Abort {
+                        return Err(io::Error::new(
+                            io::ErrorKind::InvalidData,
+                            "The file does not match its expected checksum.",
+                        ));
+                    }
+                }
+                Ok(bytes)
+            }
+            CachedFile::NotFound => Err(io::Error::new(io::ErrorKind::NotFound, "The file could not be found.")),
+        }
+    }
+
+    /// Sets the status the file will be served with, instead of 200 OK.
+    ///
+    /// Byte ranges only apply to 200 OK responses, so the request's Range and If-Range headers are ignored,
+    /// and the whole file is always served with the status.
+    ///
+    /// If the file can't be found, the response will still fail with a 404 Not Found,
+    /// as there is no body to attach the status to.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # extern crate rocket;
+    /// # extern crate rocket_file_cache;
+    /// # fn main() {
+    /// use rocket_file_cache::{Cache, CachedFile};
+    /// use rocket::http::Status;
+    ///
+    /// fn maintenance_page(cache: &Cache) -> CachedFile {
+    ///     CachedFile::open("www/maintenance.html", cache).with_status(Status::ServiceUnavailable)
+    /// }
+    /// # }
+    /// ```
+    #[cfg(feature = "rocket")]
+    pub fn with_status(self, status: Status) -> CachedFile<'a> {
+        match self {
+            CachedFile::InMemory(mut cached_file) => {
+                cached_file.status = Some(status);
+                CachedFile::InMemory(cached_file)
+            }
+            CachedFile::FileSystem(mut streamed_file) => {
+                streamed_file.status = Some(status);
+                CachedFile::FileSystem(streamed_file)
+            }
+            CachedFile::Verified(mut verified_file) => {
+                verified_file.status = Some(status);
+                CachedFile::Verified(verified_file)
+            }
+            CachedFile::Owned(mut owned_file) => {
+                owned_file.status = Some(status);
+                CachedFile::Owned(owned_file)
+            }
+            CachedFile::NotFound => CachedFile::NotFound,
+        }
+    }
 }
 
 
@@ -43,16 +174,31 @@ impl<'a> From<NamedInMemoryFile<'a>> for CachedFile<'a> {
 
 impl From<NamedFile> for CachedFile<'static> {
     fn from(named_file: NamedFile) -> Self {
-        CachedFile::FileSystem(named_file)
+        CachedFile::FileSystem(StreamedFile::from(named_file))
     }
 }
 
+impl From<StreamedFile> for CachedFile<'static> {
+    fn from(streamed_file: StreamedFile) -> Self {
+        CachedFile::FileSystem(streamed_file)
+    }
+}
+
+impl From<VerifiedFile> for CachedFile<'static> {
+    fn from(verified_file: VerifiedFile) -> Self {
+        CachedFile::Verified(verified_file)
+    }
+}
+
+#[cfg(feature = "rocket")]
 impl<'a> Responder<'a> for CachedFile<'a> {
     fn respond_to(self, request: &Request) -> Result<Response<'a>, Status> {
 
         match self {
             CachedFile::InMemory(cached_file) => cached_file.respond_to(request),
-            CachedFile::FileSystem(named_file) => named_file.respond_to(request),
+            CachedFile::FileSystem(streamed_file) => streamed_file.respond_to(request),
+            CachedFile::Verified(verified_file) => verified_file.respond_to(request),
+            CachedFile::Owned(owned_file) => owned_file.respond_to(request),
             CachedFile::NotFound => {
                 error!("Response was `FileNotFound`.",);
                 Err(Status::NotFound)
@@ -64,32 +210,22 @@ impl<'a> Responder<'a> for CachedFile<'a> {
 
 impl<'a> PartialEq for CachedFile<'a> {
     fn eq(&self, other: &CachedFile) -> bool {
-        match *self {
-            CachedFile::InMemory(ref lhs_cached_file) => {
-                match *other {
-                    CachedFile::InMemory(ref rhs_cached_file) => (*rhs_cached_file.file).get() == (*lhs_cached_file.file).get(),
-                    CachedFile::FileSystem(_) => false,
-                    CachedFile::NotFound => false
-                }
+        match (self, other) {
+            (&CachedFile::InMemory(ref lhs_cached_file), &CachedFile::InMemory(ref rhs_cached_file)) => {
+                rhs_cached_file.file == lhs_cached_file.file
             }
-            CachedFile::FileSystem(ref lhs_named_file) => {
-                match *other {
-                    CachedFile::InMemory(_) => false,
-                    CachedFile::FileSystem(ref rhs_named_file) => {
-                        // This just compares the file paths
-                        *lhs_named_file.path() == *rhs_named_file.path()
-                    }
-                    CachedFile::NotFound => false
-                }
+            (&CachedFile::FileSystem(ref lhs_streamed_file), &CachedFile::FileSystem(ref rhs_streamed_file)) => {
+                // This just compares the file paths
+                *lhs_streamed_file.path() == *rhs_streamed_file.path()
             }
-            CachedFile::NotFound => {
-                match *other {
-                    CachedFile::InMemory(_) => false,
-                    CachedFile::FileSystem(_) => false,
-                    CachedFile::NotFound => true
-                }
+            (&CachedFile::Verified(ref lhs_verified_file), &CachedFile::Verified(ref rhs_verified_file)) => {
+                lhs_verified_file.path() == rhs_verified_file.path()
             }
+            (&CachedFile::Owned(ref lhs_owned_file), &CachedFile::Owned(ref rhs_owned_file)) => {
+                lhs_owned_file.path() == rhs_owned_file.path() && lhs_owned_file.bytes() == rhs_owned_file.bytes()
+            }
+            (&CachedFile::NotFound, &CachedFile::NotFound) => true,
+            _ => false,
         }
-
     }
 }
@@ -0,0 +1,121 @@
+use std::u64;
+#[cfg(feature = "rocket")]
+use std::io;
+#[cfg(feature = "rocket")]
+use std::io::Read;
+#[cfg(feature = "rocket")]
+use std::path::PathBuf;
+
+const FNV_OFFSET_BASIS: u64 = 0xcbf29ce484222325;
+const FNV_PRIME: u64 = 0x100000001b3;
+
+/// Calculates a 64 bit FNV-1a checksum of the provided bytes.
+///
+/// This is used to verify that files read from the filesystem match the checksum that was
+/// expected for them before they are stored in the cache.
+/// It is fast, but is not cryptographically secure.
+///
+/// # Example
+///
+/// ```
+/// use rocket_file_cache::checksum;
+///
+/// assert_eq!(checksum(b""), 0xcbf29ce484222325);
+/// assert_ne!(checksum(b"Hello World!"), checksum(b"Hello World?"));
+/// ```
+pub fn checksum(bytes: &[u8]) -> u64 {
+    let mut checksum = Checksum::new();
+    checksum.update(bytes);
+    checksum.value()
+}
+
+/// Calculates the same checksum as `checksum()`, but allows the bytes to be provided incrementally.
+///
+/// This allows the checksum of a file to be calculated while it is streamed, without holding the whole file in memory.
+///
+/// # Example
+///
+/// ```
+/// use rocket_file_cache::{checksum, Checksum};
+///
+/// let mut incremental = Checksum::new();
+/// incremental.update(b"Hello ");
+/// incremental.update(b"World!");
+/// assert_eq!(incremental.value(), checksum(b"Hello World!"));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Checksum {
+    hash: u64,
+}
+
+impl Checksum {
+    /// Creates a checksum that hasn't been given any bytes.
+    pub fn new() -> Checksum {
+        Checksum { hash: FNV_OFFSET_BASIS }
+    }
+
+    /// Adds the bytes to the checksum.
+    pub fn update(&mut self, bytes: &[u8]) {
+        self.hash = bytes.iter().fold(self.hash, |hash, byte| {
+            (hash ^ (*byte as u64)).wrapping_mul(FNV_PRIME)
+        });
+    }
+
+    /// Gets the checksum of all of the bytes that have been added.
+    pub fn value(&self) -> u64 {
+        self.hash
+    }
+}
+
+/// Determines what happens when a file streamed from the filesystem doesn't match its expected checksum.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum ChecksumMismatch {
+    /// The response is aborted before it completes, so the client will not receive a complete body.
+    Abort,
+    /// The mismatch is logged, and the response completes normally.
+    Log,
+}
+
+/// A reader that calculates the checksum of the bytes read through it, and checks it against
+/// the expected checksum once the end of the underlying reader is reached.
+#[cfg(feature = "rocket")]
+pub(crate) struct ChecksumReader<R: Read> {
+    reader: R,
+    path: PathBuf,
+    checksum: Checksum,
+    expected_checksum: u64,
+    on_mismatch: ChecksumMismatch,
+}
+
+#[cfg(feature = "rocket")]
+impl<R: Read> ChecksumReader<R> {
+    /// Wraps the reader, so the bytes read from it will be checked against the expected checksum.
+    pub(crate) fn new(reader: R, path: PathBuf, expected_checksum: u64, on_mismatch: ChecksumMismatch) -> ChecksumReader<R> {
+        ChecksumReader {
+            reader,
+            path,
+            checksum: Checksum::new(),
+            expected_checksum,
+            on_mismatch,
+        }
+    }
+}
+
+#[cfg(feature = "rocket")]
+impl<R: Read> Read for ChecksumReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read: usize = self.reader.read(buf)?;
+        if read > 0 {
+            self.checksum.update(&buf[..read]);
+        } else if self.checksum.value() != self.expected_checksum {
+            error!("The file at {:?} does not match its expected checksum.", self.path);
+            if self.on_mismatch == ChecksumMismatch::Abort {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "The streamed file does not match its expected checksum.",
+                ));
+            }
+        }
+        Ok(read)
+    }
+}
@@ -0,0 +1,28 @@
+use rocket::http::ContentType;
+
+
+/// Content-Types for formats that Rocket's `ContentType::from_extension()` doesn't know about,
+/// paired with the extension that implies them.
+const MODERN_CONTENT_TYPES: [(&'static str, &'static str, &'static str); 8] = [
+    ("wasm", "application", "wasm"),
+    ("mjs", "text", "javascript"),
+    ("webp", "image", "webp"),
+    ("avif", "image", "avif"),
+    ("apng", "image", "apng"),
+    ("woff2", "font", "woff2"),
+    ("woff", "font", "woff"),
+    ("webmanifest", "application", "manifest+json"),
+];
+
+/// Gets the Content-Type implied by a file's extension.
+///
+/// Rocket's lookup is tried first. If it doesn't recognize the extension,
+/// the Content-Types of more recent web formats are looked up instead.
+pub(crate) fn content_type_from_extension(extension: &str) -> Option<ContentType> {
+    ContentType::from_extension(extension).or_else(|| {
+        MODERN_CONTENT_TYPES
+            .iter()
+            .find(|&&(known_extension, _, _)| known_extension.eq_ignore_ascii_case(extension))
+            .map(|&(_, top, sub)| ContentType::new(top, sub))
+    })
+}
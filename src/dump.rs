@@ -0,0 +1,77 @@
+use std::io;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+
+
+/// The bytes that every dump starts with, identifying the format and its version.
+const MAGIC: &'static [u8; 8] = b"RFCDUMP1";
+
+/// Writes the start of a dump, which must come before any of its entries.
+pub(crate) fn write_header<W: Write>(writer: &mut W) -> io::Result<()> {
+    writer.write_all(MAGIC)
+}
+
+/// Reads the start of a dump, failing if the reader doesn't hold a dump.
+pub(crate) fn read_header<R: Read>(reader: &mut R) -> io::Result<()> {
+    let mut magic = [0u8; 8];
+    reader.read_exact(&mut magic)?;
+    if &magic != MAGIC {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "The reader does not contain a cache dump."));
+    }
+    Ok(())
+}
+
+/// Writes the path and contents of a file to a dump.
+///
+/// Each entry is the length of the path, the path as UTF-8, the length of the contents, and the contents.
+/// Lengths are written as little endian 64 bit integers.
+pub(crate) fn write_entry<W: Write>(writer: &mut W, path: &Path, bytes: &[u8]) -> io::Result<()> {
+    let path = path.to_string_lossy();
+    write_u64(writer, path.len() as u64)?;
+    writer.write_all(path.as_bytes())?;
+    write_u64(writer, bytes.len() as u64)?;
+    writer.write_all(bytes)
+}
+
+/// Reads the path and contents of the next file in a dump, or `None` if there are no more files.
+///
+/// The lengths in the dump aren't trusted to allocate buffers, so a corrupted length can't make the reader
+/// allocate more than the bytes that are actually in the dump.
+pub(crate) fn read_entry<R: Read>(reader: &mut R) -> io::Result<Option<(PathBuf, Vec<u8>)>> {
+    let path_len: u64 = match read_u64(reader) {
+        Ok(len) => len,
+        Err(ref e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(e),
+    };
+    let path: Vec<u8> = read_exact_len(reader, path_len)?;
+    let path: String = String::from_utf8(path)
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "A path in the cache dump is not valid UTF-8."))?;
+
+    let bytes_len: u64 = read_u64(reader)?;
+    let bytes: Vec<u8> = read_exact_len(reader, bytes_len)?;
+    Ok(Some((PathBuf::from(path), bytes)))
+}
+
+/// Reads exactly `len` bytes, growing the buffer as they are read instead of allocating it up front.
+fn read_exact_len<R: Read>(reader: &mut R, len: u64) -> io::Result<Vec<u8>> {
+    let mut bytes: Vec<u8> = Vec::new();
+    reader.take(len).read_to_end(&mut bytes)?;
+    if (bytes.len() as u64) < len {
+        return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "The cache dump ends part way through an entry."));
+    }
+    Ok(bytes)
+}
+
+fn write_u64<W: Write>(writer: &mut W, value: u64) -> io::Result<()> {
+    let mut bytes = [0u8; 8];
+    for (i, byte) in bytes.iter_mut().enumerate() {
+        *byte = (value >> (8 * i)) as u8;
+    }
+    writer.write_all(&bytes)
+}
+
+fn read_u64<R: Read>(reader: &mut R) -> io::Result<u64> {
+    let mut bytes = [0u8; 8];
+    reader.read_exact(&mut bytes)?;
+    Ok(bytes.iter().rev().fold(0u64, |value, byte| (value << 8) | *byte as u64))
+}
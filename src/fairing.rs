@@ -0,0 +1,105 @@
+use rocket::{Rocket, Route, Request, Data, State};
+use rocket::fairing::{Fairing, Info, Kind};
+use rocket::handler::Outcome;
+use rocket::http::{Method, Status};
+use cache::Cache;
+use cached_file::CachedFile;
+
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+
+/// The directory that the route registered by a `CacheFairing` serves files from.
+#[derive(Debug)]
+struct CacheRoot(PathBuf);
+
+/// A fairing that manages a cache as Rocket's state, and optionally serves the files in a directory from it.
+///
+/// The cache is available to other routes as `State<Cache>`.
+/// If the fairing was created by `Cache::fairing()`, a route is mounted that gets every file under the mount path
+/// from the cache, relative to the root directory. If it was created by `Cache::state_fairing()`, no route is mounted.
+/// Only one cache can be managed by a Rocket instance, so only one of these fairings can be attached.
+///
+/// # Example
+///
+/// ```
+/// # extern crate rocket;
+/// # extern crate rocket_file_cache;
+///
+/// # fn main() {
+/// use rocket_file_cache::{Cache, CacheBuilder};
+///
+/// let cache: Cache = CacheBuilder::new().size_limit(1024 * 1024 * 40).build().unwrap();
+/// let rocket = rocket::ignite().attach(cache.fairing("/static", "www/"));
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct CacheFairing {
+    cache: Mutex<Option<Cache>>,
+    /// The path the file-serving route is mounted at, and the directory it serves files from.
+    /// If this is `None`, the fairing only manages the cache.
+    route: Option<(String, PathBuf)>,
+}
+
+impl CacheFairing {
+    pub(crate) fn new<P: AsRef<Path>>(cache: Cache, mount_path: &str, root: P) -> CacheFairing {
+        CacheFairing {
+            cache: Mutex::new(Some(cache)),
+            route: Some((String::from(mount_path), root.as_ref().to_path_buf())),
+        }
+    }
+
+    pub(crate) fn without_route(cache: Cache) -> CacheFairing {
+        CacheFairing {
+            cache: Mutex::new(Some(cache)),
+            route: None,
+        }
+    }
+}
+
+/// Gets the requested file from the managed cache, relative to the managed root directory.
+fn serve_file<'r>(request: &'r Request, _: Data) -> Outcome<'r> {
+    let path: PathBuf = match request.get_segments::<PathBuf>(0) {
+        Ok(path) => path,
+        Err(_) => return Outcome::failure(Status::NotFound),
+    };
+    let cache: State<'r, Cache> = match request.guard::<State<Cache>>().succeeded() {
+        Some(cache) => cache,
+        None => return Outcome::failure(Status::InternalServerError),
+    };
+    let root: State<'r, CacheRoot> = match request.guard::<State<CacheRoot>>().succeeded() {
+        Some(root) => root,
+        None => return Outcome::failure(Status::InternalServerError),
+    };
+    let cached_file: CachedFile<'r> = cache.inner().get(root.inner().0.join(path));
+    Outcome::from(request, cached_file)
+}
+
+impl Fairing for CacheFairing {
+    fn info(&self) -> Info {
+        Info {
+            name: "File Cache",
+            kind: Kind::Attach,
+        }
+    }
+
+    fn on_attach(&self, rocket: Rocket) -> Result<Rocket, Rocket> {
+        let cache: Cache = match self.cache.lock() {
+            Ok(mut cache) => cache.take(),
+            Err(poisoned) => poisoned.into_inner().take(),
+        }.expect("The cache fairing can only be attached once.");
+
+        let rocket: Rocket = rocket.manage(cache);
+        match self.route {
+            Some((ref mount_path, ref root)) => {
+                let route: Route = Route::new(Method::Get, "/<path..>", serve_file);
+                Ok(
+                    rocket
+                        .manage(CacheRoot(root.clone()))
+                        .mount(mount_path, vec![route]),
+                )
+            }
+            None => Ok(rocket),
+        }
+    }
+}
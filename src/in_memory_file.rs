@@ -1,18 +1,36 @@
 
 use std::path::Path;
-use std::io::BufReader;
+use std::cmp;
 use std::fs::File;
 use std::io;
 use std::io::Read;
 use std::fmt;
+use std::sync::Arc;
+use std::time::Instant;
+use checksum::{checksum, Checksum};
+#[cfg(feature = "rocket")]
+use rocket::http::ContentType;
+#[cfg(target_os = "linux")]
+use std::os::unix::io::AsRawFd;
 
+/// The number of bytes read from a file at a time while it is read into memory.
+const READ_CHUNK_SIZE: usize = 64 * 1024;
 
 /// The structure that represents a file in memory.
 /// Keeps an up to date record of its stats so the cache can use this information to remove the file
 /// from the cache.
-#[derive(Clone, PartialEq)]
+///
+/// The bytes of the file are held behind an `Arc`, so cloning an InMemoryFile shares its bytes
+/// with the original instead of copying them.
+#[derive(Clone)]
 pub struct InMemoryFile {
-    pub(crate) bytes: Vec<u8>,
+    pub(crate) bytes: Arc<Vec<u8>>,
+    pub(crate) etag: String,
+    /// The Content-Type the file is served with, instead of the one implied by its extension.
+    #[cfg(feature = "rocket")]
+    pub(crate) content_type: Option<ContentType>,
+    /// When the bytes were read, used to determine when the file outlives the cache's `ttl`.
+    pub(crate) read_at: Instant,
     pub stats: FileStats,
 }
 
@@ -28,22 +46,177 @@ impl fmt::Debug for InMemoryFile {
     }
 }
 
+// When the file was read doesn't change what it is, so `read_at` isn't compared.
+impl PartialEq for InMemoryFile {
+    fn eq(&self, other: &InMemoryFile) -> bool {
+        #[cfg(feature = "rocket")]
+        {
+            if self.content_type != other.content_type {
+                return false;
+            }
+        }
+        self.bytes == other.bytes && self.etag == other.etag && self.stats == other.stats
+    }
+}
 
 impl InMemoryFile {
     /// Reads the file at the path into an InMemoryFile.
+    ///
+    /// The byte buffer is allocated up front using the length reported by the file's metadata,
+    /// so reading large files won't repeatedly reallocate the buffer as it grows.
     pub fn open<P: AsRef<Path>>(path: P) -> io::Result<InMemoryFile> {
         let file = File::open(path.as_ref())?;
-        let mut reader = BufReader::new(file);
-        let mut bytes: Vec<u8> = vec![];
-        let size: usize = reader.read_to_end(&mut bytes)?;
+        let len_hint: usize = InMemoryFile::len_from_metadata(&file);
+        InMemoryFile::read_from(file, len_hint)
+    }
+
+    /// Reads the file at the path into an InMemoryFile, then advises the operating system that the
+    /// file's pages won't be needed again, so they can be dropped from its page cache.
+    ///
+    /// Once a file is held in memory, keeping it in the page cache as well only pushes out other files
+    /// the operating system could be caching.
+    /// This is most useful when reading many large files at once, such as when preloading a directory.
+    ///
+    /// On Linux, this uses `posix_fadvise` with `POSIX_FADV_DONTNEED`.
+    /// On other platforms, the file is read the same way as by `open()`.
+    pub fn open_and_release_page_cache<P: AsRef<Path>>(path: P) -> io::Result<InMemoryFile> {
+        let file = File::open(path.as_ref())?;
+        let len_hint: usize = InMemoryFile::len_from_metadata(&file);
+        let in_memory_file: InMemoryFile = InMemoryFile::read_from(&file, len_hint)?;
+        InMemoryFile::release_page_cache(&file);
+        Ok(in_memory_file)
+    }
+
+    /// Advises the operating system that the pages of the file won't be needed again.
+    /// The advice is only a hint, so failing to give it isn't an error.
+    #[cfg(target_os = "linux")]
+    fn release_page_cache(file: &File) {
+        let result = unsafe { ::libc::posix_fadvise(file.as_raw_fd(), 0, 0, ::libc::POSIX_FADV_DONTNEED) };
+        if result != 0 {
+            debug!("Could not advise the kernel to release the page cache of a file, error code {}.", result);
+        }
+    }
 
+    #[cfg(not(target_os = "linux"))]
+    fn release_page_cache(_: &File) {}
+
+    /// Reads everything from the reader into an InMemoryFile.
+    ///
+    /// The length hint is only used to allocate the byte buffer.
+    /// The file may grow or shrink between its metadata being read and its contents being read,
+    /// so the size of the InMemoryFile is always taken from the number of bytes that were actually read.
+    ///
+    /// The checksum used for the ETag is calculated from each chunk as it is read,
+    /// so the bytes don't need to be scanned a second time once the whole file is in memory.
+    ///
+    /// If reading fails part way through, such as when a network mount is lost, the bytes read so far
+    /// are discarded and the error is returned, so a partially read file can never be cached.
+    pub(crate) fn read_from<R: Read>(reader: R, len_hint: usize) -> io::Result<InMemoryFile> {
+        InMemoryFile::read_in_chunks(reader, len_hint, READ_CHUNK_SIZE)
+    }
+
+    /// Reads everything from the reader into an InMemoryFile, reading at most `chunk_size` bytes at a time.
+    ///
+    /// Each chunk is read straight into the byte buffer, so the bytes are never copied.
+    /// One more byte than the length hint is allocated, so the read that finds the end of a file
+    /// whose length matches the hint doesn't need to grow the buffer.
+    fn read_in_chunks<R: Read>(mut reader: R, len_hint: usize, chunk_size: usize) -> io::Result<InMemoryFile> {
+        let chunk_size: usize = cmp::max(chunk_size, 1);
+        let mut bytes: Vec<u8> = Vec::with_capacity(len_hint.saturating_add(1));
+        let mut checksum: Checksum = Checksum::new();
+        let mut filled: usize = 0;
+        loop {
+            if filled == bytes.len() {
+                // The allocated capacity is used up before the buffer is grown past it.
+                let spare: usize = bytes.capacity() - filled;
+                let grow: usize = if spare > 0 { cmp::min(spare, chunk_size) } else { chunk_size };
+                bytes.resize(filled + grow, 0);
+            }
+            match reader.read(&mut bytes[filled..]) {
+                Ok(0) => break,
+                Ok(read) => {
+                    checksum.update(&bytes[filled..filled + read]);
+                    filled += read;
+                }
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            }
+        }
+        bytes.truncate(filled);
+
+        Ok(InMemoryFile::from_bytes_and_checksum(bytes, checksum.value()))
+    }
+
+    /// Creates an InMemoryFile from bytes that have been read from a file.
+    ///
+    /// The ETag for the bytes is calculated here, so it doesn't need to be recalculated every time the file is served.
+    pub(crate) fn from_bytes(bytes: Vec<u8>) -> InMemoryFile {
+        let checksum: u64 = checksum(&bytes);
+        InMemoryFile::from_bytes_and_checksum(bytes, checksum)
+    }
+
+    /// Creates an InMemoryFile from bytes whose checksum has already been calculated.
+    ///
+    /// The buffer is moved into the `Arc` as it is, so the bytes aren't copied.
+    fn from_bytes_and_checksum(bytes: Vec<u8>, checksum: u64) -> InMemoryFile {
+        let etag: String = InMemoryFile::etag_for(checksum);
         let stats = FileStats {
-            size,
+            size: bytes.len(),
             access_count: 0,
             priority: 0,
         };
 
-        Ok(InMemoryFile { bytes, stats })
+        InMemoryFile {
+            bytes: Arc::new(bytes),
+            etag,
+            #[cfg(feature = "rocket")]
+            content_type: None,
+            read_at: Instant::now(),
+            stats,
+        }
+    }
+
+    /// Formats a checksum of a file's bytes as its quoted entity tag.
+    fn etag_for(checksum: u64) -> String {
+        format!("\"{:016x}\"", checksum)
+    }
+
+    /// Recalculates the size and ETag of the file after its bytes were modified in place.
+    ///
+    /// The rest of the file's stats, and when it was read, are left as they were.
+    pub(crate) fn update_size_and_etag(&mut self) {
+        self.stats.size = self.bytes.len();
+        self.etag = InMemoryFile::etag_for(checksum(&self.bytes));
+    }
+
+    /// Gets the bytes of the file.
+    /// They are shared through an `Arc`, so they can be cloned without copying the file's contents.
+    pub fn bytes(&self) -> &Arc<Vec<u8>> {
+        &self.bytes
+    }
+
+    /// Gets the entity tag of the file, which is derived from a checksum of its contents.
+    /// The value is quoted, so it can be used directly as the value of an ETag header.
+    pub fn etag(&self) -> &str {
+        &self.etag
+    }
+
+    /// Gets the length of the file from its metadata, so the byte buffer can be allocated before reading.
+    /// If the metadata can't be read, 0 is returned and the buffer will grow as it is read into.
+    fn len_from_metadata(file: &File) -> usize {
+        match file.metadata() {
+            Ok(metadata) => metadata.len() as usize,
+            Err(_) => 0,
+        }
+    }
+
+    /// Reads the file at the path into an InMemoryFile, reading at most `capacity` bytes from the file at a time.
+    ///
+    /// Like `open()`, the byte buffer is allocated up front from the file's metadata.
+    pub fn open_with_capacity<P: AsRef<Path>>(path: P, capacity: usize) -> io::Result<InMemoryFile> {
+        let file = File::open(path.as_ref())?;
+        let len_hint: usize = InMemoryFile::len_from_metadata(&file);
+        InMemoryFile::read_in_chunks(file, len_hint, capacity)
     }
 }
 
@@ -1,19 +1,64 @@
 #![feature(test)]
 
+#[cfg(feature = "rocket")]
 extern crate rocket;
 #[macro_use]
 extern crate log;
+#[cfg(feature = "serde")]
+#[macro_use]
+extern crate serde;
 
 extern crate concurrent_hashmap;
+extern crate zstd;
+extern crate zip;
+#[cfg(target_os = "linux")]
+extern crate libc;
 
 mod cache;
+mod backend;
 mod in_memory_file;
 pub mod named_in_memory_file;
 mod cache_builder;
 mod priority_function;
 mod cached_file;
+mod checksum;
+#[cfg(feature = "rocket")]
+mod precompressed_file;
+#[cfg(feature = "rocket")]
+mod bundle;
+#[cfg(feature = "rocket")]
+mod fairing;
+#[cfg(feature = "rocket")]
+mod range;
+#[cfg(feature = "rocket")]
+mod content_type;
+#[cfg(feature = "rocket")]
+mod localized_file;
+mod verified_file;
+mod streamed_file;
+mod owned_in_memory_file;
+mod archive;
+mod dump;
+mod named_file;
+mod memory;
 
-pub use cache::Cache;
+pub use cache::{Cache, CacheStats, CacheSnapshot, FileSnapshot, Origin, EvictionPolicy, CacheInvalidationError, DEFAULT_SIZE_LIMIT};
 pub use cache_builder::{CacheBuilder, CacheBuildError};
+pub use backend::CacheBackend;
 pub use cached_file::CachedFile;
+pub use in_memory_file::{InMemoryFile, FileStats};
 pub use priority_function::*;
+pub use checksum::{checksum, Checksum, ChecksumMismatch};
+#[cfg(feature = "rocket")]
+pub use precompressed_file::PrecompressedFile;
+#[cfg(feature = "rocket")]
+pub use bundle::Bundle;
+#[cfg(feature = "rocket")]
+pub use fairing::CacheFairing;
+#[cfg(feature = "rocket")]
+pub use localized_file::LocalizedFile;
+pub use verified_file::VerifiedFile;
+pub use streamed_file::StreamedFile;
+pub use owned_in_memory_file::OwnedInMemoryFile;
+#[cfg(not(feature = "rocket"))]
+pub use named_file::NamedFile;
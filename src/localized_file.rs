@@ -0,0 +1,151 @@
+use rocket::http::{Status, Header};
+use rocket::response::{Response, Responder};
+use rocket::request::Request;
+use cache::Cache;
+
+use std::path::{Path, PathBuf};
+
+
+/// A file that has variants in several languages, of which the one that best matches the request's
+/// Accept-Language header is served.
+///
+/// The variants are registered under the file's path, which acts as a logical key, using
+/// `Cache::add_language_variant()`. Each variant is gotten from the cache under its own path,
+/// so the variants are cached independently of each other.
+///
+/// A language in the Accept-Language header matches a variant if they are the same, or if one is a more
+/// specific form of the other, so `fr` matches a variant registered as `fr-CA`, and `fr-CA` matches `fr`.
+/// Of the variants the client accepts, the one it prefers the most is served. Variants that the client
+/// prefers equally are chosen in the order they were registered.
+/// If the client accepts none of the variants, or the request has no Accept-Language header,
+/// the first registered variant is served.
+/// If no variants are registered, the file at the path itself is served.
+///
+/// Responses set the `Content-Language` header to the language of the served variant, and every response
+/// sets the `Vary: Accept-Language` header, so shared caches will keep the variants apart.
+///
+/// # Example
+///
+/// ```
+/// #![feature(attr_literals)]
+/// #![feature(custom_attribute)]
+/// # extern crate rocket;
+/// # extern crate rocket_file_cache;
+///
+/// # fn main() {
+/// use rocket_file_cache::{Cache, LocalizedFile};
+/// use rocket::State;
+///
+/// #[get("/")]
+/// fn index<'a>(cache: State<'a, Cache>) -> LocalizedFile<'a> {
+///     LocalizedFile::open("www/index.html", cache.inner())
+/// }
+///
+/// let cache = Cache::new(1024 * 1024 * 30);
+/// cache.add_language_variant("www/index.html", "en", "www/index.en.html");
+/// cache.add_language_variant("www/index.html", "fr", "www/index.fr.html");
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct LocalizedFile<'a> {
+    path: PathBuf,
+    cache: &'a Cache,
+}
+
+impl<'a> LocalizedFile<'a> {
+    /// Creates a LocalizedFile that will get the best matching variant of the file from the cache when responding.
+    pub fn open<P: AsRef<Path>>(path: P, cache: &'a Cache) -> LocalizedFile<'a> {
+        LocalizedFile {
+            path: path.as_ref().to_path_buf(),
+            cache,
+        }
+    }
+}
+
+/// Checks if a language range from an Accept-Language header matches the language of a variant.
+fn language_matches(range: &str, language: &str) -> bool {
+    fn is_prefix_of(prefix: &str, tag: &str) -> bool {
+        tag.len() > prefix.len()
+            && tag.as_bytes()[..prefix.len()].eq_ignore_ascii_case(prefix.as_bytes())
+            && tag.as_bytes()[prefix.len()] == b'-'
+    }
+    range == "*"
+        || range.eq_ignore_ascii_case(language)
+        || is_prefix_of(range, language)
+        || is_prefix_of(language, range)
+}
+
+/// Gets the language ranges in the request's Accept-Language header, paired with their quality values.
+fn accepted_languages(request: &Request) -> Vec<(String, f32)> {
+    request.headers()
+        .get("Accept-Language")
+        .flat_map(|value| value.split(','))
+        .filter_map(|value| {
+            let mut parameters = value.split(';');
+            let range: &str = parameters.next().unwrap_or("").trim();
+            if range.is_empty() {
+                return None;
+            }
+            let quality: f32 = parameters
+                .filter_map(|parameter| {
+                    let parameter: &str = parameter.trim();
+                    if parameter.starts_with("q=") {
+                        parameter[2..].parse::<f32>().ok()
+                    } else {
+                        None
+                    }
+                })
+                .next()
+                .unwrap_or(1.0);
+            Some((range.to_string(), quality))
+        })
+        .collect()
+}
+
+/// Gets the quality the accepted languages give to a variant's language.
+/// When several ranges match, the most specific one decides the quality.
+fn quality_of(accepted: &[(String, f32)], language: &str) -> Option<f32> {
+    accepted.iter()
+        .filter(|&&(ref range, _)| language_matches(range, language))
+        .max_by_key(|&&(ref range, _)| if range == "*" { 0 } else { range.len() })
+        .map(|&(_, quality)| quality)
+}
+
+/// Chooses the variant that the request's Accept-Language header prefers,
+/// falling back to the first variant when none of them are acceptable.
+fn best_variant<'v>(request: &Request, variants: &'v [(String, PathBuf)]) -> Option<&'v (String, PathBuf)> {
+    let accepted: Vec<(String, f32)> = accepted_languages(request);
+    let mut best: Option<(f32, &(String, PathBuf))> = None;
+    for variant in variants {
+        if let Some(quality) = quality_of(&accepted, &variant.0) {
+            let is_better: bool = match best {
+                Some((best_quality, _)) => quality > best_quality,
+                None => quality > 0.0,
+            };
+            if is_better {
+                best = Some((quality, variant));
+            }
+        }
+    }
+    best.map(|(_, variant)| variant).or_else(|| variants.first())
+}
+
+impl<'a> Responder<'a> for LocalizedFile<'a> {
+    fn respond_to(self, request: &Request) -> Result<Response<'a>, Status> {
+        let variants: Vec<(String, PathBuf)> = match self.cache.language_variants.find(&self.path) {
+            Some(variants) => variants.get().clone(),
+            None => Vec::new(),
+        };
+
+        let mut response: Response<'a> = match best_variant(request, &variants) {
+            Some(&(ref language, ref variant_path)) => {
+                let mut response: Response<'a> = self.cache.get(variant_path).respond_to(request)?;
+                response.set_header(Header::new("Content-Language", language.clone()));
+                response
+            }
+            None => self.cache.get(&self.path).respond_to(request)?,
+        };
+        response.set_header(Header::new("Vary", "Accept-Language"));
+        Ok(response)
+    }
+}
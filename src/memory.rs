@@ -0,0 +1,84 @@
+use std::io;
+use std::fs::File;
+use std::io::Read;
+
+
+/// The total and available memory of the system, in bytes.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub(crate) struct SystemMemory {
+    pub(crate) total: usize,
+    pub(crate) available: usize,
+}
+
+impl SystemMemory {
+    /// Reads the total and available memory of the system.
+    ///
+    /// This is only supported on Linux, where the figures are read from `/proc/meminfo`.
+    /// On other platforms, an error is returned.
+    pub(crate) fn read() -> io::Result<SystemMemory> {
+        let mut meminfo: String = String::new();
+        File::open("/proc/meminfo")?.read_to_string(&mut meminfo)?;
+        SystemMemory::parse_meminfo(&meminfo).ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidData, "Could not find the total and available memory in /proc/meminfo.")
+        })
+    }
+
+    /// Parses the contents of `/proc/meminfo`.
+    ///
+    /// Kernels older than 3.14 don't report `MemAvailable`, in which case the free memory is used instead.
+    pub(crate) fn parse_meminfo(meminfo: &str) -> Option<SystemMemory> {
+        let field = |name: &str| -> Option<usize> {
+            meminfo
+                .lines()
+                .find(|line| line.starts_with(name) && line[name.len()..].starts_with(':'))
+                .and_then(|line| line[name.len() + 1..].trim().split_whitespace().next())
+                .and_then(|kilobytes| kilobytes.parse::<usize>().ok())
+                .map(|kilobytes| kilobytes.saturating_mul(1024))
+        };
+        let total: usize = field("MemTotal")?;
+        let available: usize = field("MemAvailable").or_else(|| field("MemFree"))?;
+        Some(SystemMemory { total, available })
+    }
+
+    /// Gets the size limit that uses the fraction of the system's memory.
+    ///
+    /// The fraction is taken of the available memory, so the limit will be smaller when the system is under memory pressure.
+    pub(crate) fn size_limit(&self, fraction: f64) -> usize {
+        (self.available.min(self.total) as f64 * fraction) as usize
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MEMINFO: &'static str = "MemTotal:        8000000 kB
+MemFree:          500000 kB
+MemAvailable:    2000000 kB
+Buffers:          100000 kB
+";
+
+    #[test]
+    fn size_limit_from_meminfo() {
+        let memory: SystemMemory = SystemMemory::parse_meminfo(MEMINFO).unwrap();
+        assert_eq!(memory, SystemMemory { total: 8000000 * 1024, available: 2000000 * 1024 });
+
+        let size_limit: usize = memory.size_limit(0.25);
+        assert_eq!(size_limit, 500000 * 1024);
+        assert!(size_limit < memory.available);
+    }
+
+    #[test]
+    fn free_memory_is_used_without_mem_available() {
+        let meminfo: &str = "MemTotal:        8000000 kB\nMemFree:          500000 kB\n";
+        let memory: SystemMemory = SystemMemory::parse_meminfo(meminfo).unwrap();
+        assert_eq!(memory.available, 500000 * 1024);
+        assert_eq!(memory.size_limit(0.5), 250000 * 1024);
+    }
+
+    #[test]
+    fn incomplete_meminfo() {
+        assert_eq!(SystemMemory::parse_meminfo("MemFree: 500000 kB"), None);
+    }
+}
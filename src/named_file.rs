@@ -0,0 +1,49 @@
+#[cfg(feature = "rocket")]
+pub use rocket::response::NamedFile;
+
+#[cfg(not(feature = "rocket"))]
+pub use self::plain::NamedFile;
+
+#[cfg(not(feature = "rocket"))]
+mod plain {
+    use std::fs::File;
+    use std::io;
+    use std::io::Read;
+    use std::path::{Path, PathBuf};
+
+    /// A file in the filesystem, along with the path it was opened from.
+    ///
+    /// This takes the place of Rocket's `NamedFile` when the `rocket` feature is disabled,
+    /// and provides the same methods for accessing the file.
+    #[derive(Debug)]
+    pub struct NamedFile(PathBuf, File);
+
+    impl NamedFile {
+        /// Opens the file at the path in read-only mode.
+        pub fn open<P: AsRef<Path>>(path: P) -> io::Result<NamedFile> {
+            let file = File::open(path.as_ref())?;
+            Ok(NamedFile(path.as_ref().to_path_buf(), file))
+        }
+
+        /// Gets a reference to the underlying file.
+        pub fn file(&self) -> &File {
+            &self.1
+        }
+
+        /// Takes the underlying file.
+        pub fn take_file(self) -> File {
+            self.1
+        }
+
+        /// Gets the path of the file.
+        pub fn path(&self) -> &Path {
+            self.0.as_path()
+        }
+    }
+
+    impl Read for NamedFile {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            self.1.read(buf)
+        }
+    }
+}
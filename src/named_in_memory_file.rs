@@ -1,15 +1,22 @@
+#[cfg(feature = "rocket")]
 use rocket::response::{Response, Responder};
-use rocket::http::{Status, ContentType};
+#[cfg(feature = "rocket")]
+use rocket::http::{Status, ContentType, Header};
+#[cfg(feature = "rocket")]
 use rocket::request::Request;
-use rocket::response::Body;
 
+#[cfg(feature = "rocket")]
 use std::result;
-use std::sync::Arc;
 use std::path::{PathBuf, Path};
+#[cfg(feature = "rocket")]
+use std::time::Instant;
+#[cfg(not(feature = "rocket"))]
+use std::marker::PhantomData;
 
 use in_memory_file::InMemoryFile;
-
-use concurrent_hashmap::Accessor;
+use owned_in_memory_file::OwnedInMemoryFile;
+#[cfg(feature = "rocket")]
+use cache::ResponseTimer;
 
 use std::fmt::{Formatter, Debug};
 use std::fmt;
@@ -18,25 +25,89 @@ use std::fmt;
 /// A wrapper around an in-memory file.
 /// This struct is created when when a request to the cache is made.
 /// The CachedFile knows its path, so it can set the content type when it is serialized to a response.
+///
+/// The file is a copy of the one held by the cache's backend, which shares its bytes,
+/// so the file can be removed from or replaced in the cache while it is still being used.
 pub struct NamedInMemoryFile<'a> {
     pub(crate) path: PathBuf,
-    pub(crate) file: Arc<Accessor<'a, PathBuf, InMemoryFile>>,
+    pub(crate) file: InMemoryFile,
+    pub(crate) max_age: Option<usize>,
+    #[cfg(feature = "rocket")]
+    pub(crate) default_content_type: Option<ContentType>,
+    pub(crate) encoding: Option<&'static str>,
+    #[cfg(feature = "rocket")]
+    pub(crate) response_timer: Option<&'a ResponseTimer>,
+    #[cfg(feature = "rocket")]
+    pub(crate) headers: Vec<Header<'static>>,
+    #[cfg(feature = "rocket")]
+    pub(crate) max_range_multiple: usize,
+    #[cfg(feature = "rocket")]
+    pub(crate) status: Option<Status>,
+    /// Ties the file to the cache it was gotten from, as the response timer does when Rocket is used.
+    #[cfg(not(feature = "rocket"))]
+    pub(crate) cache: PhantomData<&'a ()>,
 }
 
 
 impl<'a> Debug for NamedInMemoryFile<'a> {
     fn fmt(&self, fmt: &mut Formatter) -> fmt::Result {
-        write!(fmt, "path: {:?}, file: {:?}", self.path, self.file.get())
+        write!(fmt, "path: {:?}, file: {:?}", self.path, self.file)
     }
 }
 
 
 impl<'a> NamedInMemoryFile<'a> {
     /// Reads the file at the path into a NamedInMemoryFile.
-    pub(crate) fn new<P: AsRef<Path>>(path: P, m: Accessor<'a, PathBuf, InMemoryFile>) -> NamedInMemoryFile<'a> {
+    pub(crate) fn new<P: AsRef<Path>>(path: P, file: InMemoryFile) -> NamedInMemoryFile<'a> {
         NamedInMemoryFile {
             path: path.as_ref().to_path_buf(),
-            file: Arc::new(m),
+            file,
+            max_age: None,
+            #[cfg(feature = "rocket")]
+            default_content_type: None,
+            encoding: None,
+            #[cfg(feature = "rocket")]
+            response_timer: None,
+            #[cfg(feature = "rocket")]
+            headers: vec![],
+            #[cfg(feature = "rocket")]
+            max_range_multiple: 1,
+            #[cfg(feature = "rocket")]
+            status: None,
+            #[cfg(not(feature = "rocket"))]
+            cache: PhantomData,
+        }
+    }
+
+    /// Gets the ETag of the file.
+    ///
+    /// If the file's contents are encoded, the encoding is appended to the entity tag, so that
+    /// differently encoded variants of the same file will never share an ETag.
+    pub fn etag(&self) -> String {
+        let etag: &str = self.file.etag();
+        match self.encoding {
+            Some(encoding) => format!("{}-{}\"", etag.trim_end_matches('"'), encoding),
+            None => String::from(etag),
+        }
+    }
+
+    /// Converts the file into one that shares the file's bytes, without borrowing from the cache.
+    pub fn into_owned(self) -> OwnedInMemoryFile {
+        OwnedInMemoryFile {
+            etag: self.etag(),
+            bytes: self.file.bytes.clone(),
+            path: self.path,
+            max_age: self.max_age,
+            #[cfg(feature = "rocket")]
+            content_type: self.file.content_type.clone(),
+            #[cfg(feature = "rocket")]
+            default_content_type: self.default_content_type,
+            #[cfg(feature = "rocket")]
+            headers: self.headers,
+            #[cfg(feature = "rocket")]
+            max_range_multiple: self.max_range_multiple,
+            #[cfg(feature = "rocket")]
+            status: self.status,
         }
     }
 }
@@ -44,26 +115,33 @@ impl<'a> NamedInMemoryFile<'a> {
 
 /// Streams the cached file to the client. Sets or overrides the Content-Type in
 /// the response according to the file's extension if the extension is recognized.
+/// Otherwise, the cache's default Content-Type is used, if it has one.
+///
+/// The file is converted into an `OwnedInMemoryFile` to respond, so the body doesn't need to borrow from the cache.
 ///
 /// If you would like to stream a file with a different Content-Type than that implied by its
 /// extension, convert the `CachedFile` to a `File`, and respond with that instead.
 ///
+/// The ETag header is set using the entity tag calculated when the file was read into memory
+/// and the file's encoding,
+/// and the Accept-Ranges header is set to indicate that byte ranges can be requested.
+/// If the cache has a max-age for the file, the Cache-Control header is set as well.
+///
+/// If the request is a HEAD request, the Content-Type and Content-Length headers are set, but
+/// the body is omitted.
+///
+/// The time spent building the response is recorded in the cache's stats.
+///
 /// Based on NamedFile from rocket::response::NamedFile
+#[cfg(feature = "rocket")]
 impl<'a> Responder<'a> for NamedInMemoryFile<'a> {
-    fn respond_to(self, _: &Request) -> result::Result<Response<'a>, Status> {
-        let mut response = Response::new();
-        if let Some(ext) = self.path.extension() {
-            if let Some(ct) = ContentType::from_extension(&ext.to_string_lossy()) {
-                response.set_header(ct);
-            }
+    fn respond_to(self, request: &Request) -> result::Result<Response<'a>, Status> {
+        let start: Instant = Instant::now();
+        let response_timer: Option<&ResponseTimer> = self.response_timer;
+        let response = self.into_owned().respond_to(request);
+        if let Some(response_timer) = response_timer {
+            response_timer.record(start.elapsed());
         }
-
-        unsafe {
-            let cloned_wrapper: *const Accessor<'a, PathBuf, InMemoryFile> = Arc::into_raw(self.file);
-            response.set_raw_body( Body::Sized((*cloned_wrapper).get().bytes.as_slice(), (*cloned_wrapper).get().stats.size as u64) );
-            let _ = Arc::from_raw(cloned_wrapper); // To prevent a memory leak, an Arc needs to be reconstructed from the raw pointer.
-        }
-
-        Ok(response)
+        response
     }
 }
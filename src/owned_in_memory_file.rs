@@ -0,0 +1,199 @@
+#[cfg(feature = "rocket")]
+use rocket::response::{Response, Responder};
+#[cfg(feature = "rocket")]
+use rocket::http::{Status, ContentType, Method, Header};
+#[cfg(feature = "rocket")]
+use rocket::request::Request;
+#[cfg(feature = "rocket")]
+use rocket::response::Body;
+
+#[cfg(feature = "rocket")]
+use std::result;
+use std::sync::Arc;
+use std::path::{PathBuf, Path};
+use in_memory_file::InMemoryFile;
+#[cfg(feature = "rocket")]
+use std::io::{Cursor, Read};
+#[cfg(feature = "rocket")]
+use range::{parse_range, ByteRange};
+#[cfg(feature = "rocket")]
+use content_type::content_type_from_extension;
+
+
+/// A file from the cache that owns a reference to its bytes, instead of borrowing the cache.
+///
+/// The bytes are shared with the cache through an `Arc`, so creating an OwnedInMemoryFile doesn't copy them.
+/// Because it doesn't borrow the cache, an OwnedInMemoryFile is `Send` and `'static`, so it can be
+/// held for as long as the response takes to be written, while the cache remains free to replace or remove the file.
+///
+/// An OwnedInMemoryFile is created by calling `into_owned()` on a `NamedInMemoryFile`.
+#[derive(Debug, Clone)]
+pub struct OwnedInMemoryFile {
+    pub(crate) path: PathBuf,
+    pub(crate) bytes: Arc<Vec<u8>>,
+    pub(crate) etag: String,
+    pub(crate) max_age: Option<usize>,
+    #[cfg(feature = "rocket")]
+    pub(crate) content_type: Option<ContentType>,
+    #[cfg(feature = "rocket")]
+    pub(crate) default_content_type: Option<ContentType>,
+    #[cfg(feature = "rocket")]
+    pub(crate) headers: Vec<Header<'static>>,
+    #[cfg(feature = "rocket")]
+    pub(crate) max_range_multiple: usize,
+    #[cfg(feature = "rocket")]
+    pub(crate) status: Option<Status>,
+}
+
+impl OwnedInMemoryFile {
+    /// Creates an OwnedInMemoryFile that isn't stored in a cache, which is served as if it were stored under the path.
+    pub(crate) fn new(path: PathBuf, file: InMemoryFile) -> OwnedInMemoryFile {
+        OwnedInMemoryFile {
+            path,
+            bytes: file.bytes,
+            etag: file.etag,
+            max_age: None,
+            #[cfg(feature = "rocket")]
+            content_type: file.content_type,
+            #[cfg(feature = "rocket")]
+            default_content_type: None,
+            #[cfg(feature = "rocket")]
+            headers: vec![],
+            #[cfg(feature = "rocket")]
+            max_range_multiple: 1,
+            #[cfg(feature = "rocket")]
+            status: None,
+        }
+    }
+
+    /// Gets the path of the file.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Gets the bytes of the file.
+    pub fn bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    /// Gets the entity tag of the file.
+    pub fn etag(&self) -> &str {
+        &self.etag
+    }
+
+    /// Gets the number of seconds that clients may cache the file, if the cache has a max-age for it.
+    pub fn max_age(&self) -> Option<usize> {
+        self.max_age
+    }
+}
+
+
+/// Streams the file to the client. Sets or overrides the Content-Type in
+/// the response according to the file's extension if the extension is recognized.
+/// Otherwise, the cache's default Content-Type is used, if it has one.
+/// If the file was stored with an explicit Content-Type, that is used instead.
+///
+/// The ETag header is set using the entity tag calculated when the file was read into memory
+/// and the file's encoding,
+/// and the Accept-Ranges header is set to indicate that byte ranges can be requested.
+/// If the cache has a max-age for the file, the Cache-Control header is set as well.
+/// Any headers registered with the cache are set last, replacing the headers above that have the same name.
+///
+/// If the request is a HEAD request, the Content-Type and Content-Length headers are set, but
+/// the body is omitted.
+///
+/// If the request has a Range header for a single range of bytes, only those bytes are served, with a
+/// 206 Partial Content status and the same ETag as the whole file, so clients can resume downloads.
+/// If the request also has an If-Range header that doesn't match the file's ETag, the file has changed
+/// since the client's partial download, so the whole file is served instead.
+/// Ranges that start past the end of the file are answered with 416 Range Not Satisfiable.
+/// So are Range headers whose ranges, which may overlap, request more bytes in total than the cache's
+/// `max_range_multiple` times the size of the file, to keep clients from making the server do
+/// more work than serving the file would take.
+///
+/// If the file was given a status by `CachedFile::with_status()`, the response has that status instead,
+/// and the Range header is ignored, so the whole file is always served.
+///
+/// The body reads from the shared bytes of the file, so it doesn't borrow from the cache.
+#[cfg(feature = "rocket")]
+impl<'a> Responder<'a> for OwnedInMemoryFile {
+    fn respond_to(self, request: &Request) -> result::Result<Response<'a>, Status> {
+        let mut response = Response::new();
+        let path: &Path = &self.path;
+        let content_type: Option<ContentType> = self.content_type
+            .or_else(|| {
+                path.extension()
+                    .and_then(|ext| content_type_from_extension(&ext.to_string_lossy()))
+            })
+            .or(self.default_content_type);
+        if let Some(ct) = content_type {
+            response.set_header(ct);
+        }
+
+        response.set_header(Header::new("ETag", self.etag.clone()));
+        // The whole file is in memory, so clients can be told that they can request byte ranges of it.
+        response.set_header(Header::new("Accept-Ranges", "bytes"));
+        if let Some(max_age) = self.max_age {
+            response.set_header(Header::new("Cache-Control", format!("max-age={}", max_age)));
+        }
+        for header in self.headers {
+            response.set_header(header);
+        }
+        if let Some(status) = self.status {
+            response.set_status(status);
+        }
+
+        let size: usize = self.bytes.len();
+        if request.method() == Method::Head {
+            response.set_header(Header::new("Content-Length", size.to_string()));
+            return Ok(response);
+        }
+
+        let range: ByteRange = match request.headers().get_one("Range") {
+            Some(range) if self.status.is_none() && if_range_matches(request, &self.etag) => {
+                parse_range(range, size, size.saturating_mul(self.max_range_multiple))
+            }
+            _ => ByteRange::Full,
+        };
+        match range {
+            ByteRange::Full => {
+                response.set_raw_body(Body::Sized(Cursor::new(SharedBytes(self.bytes)), size as u64));
+            }
+            ByteRange::Partial(start, end) => {
+                let length: u64 = (end - start + 1) as u64;
+                let mut body = Cursor::new(SharedBytes(self.bytes));
+                body.set_position(start as u64);
+                response.set_status(Status::PartialContent);
+                response.set_header(Header::new("Content-Range", format!("bytes {}-{}/{}", start, end, size)));
+                response.set_raw_body(Body::Sized(body.take(length), length));
+            }
+            ByteRange::Unsatisfiable => {
+                response.set_status(Status::RangeNotSatisfiable);
+                response.set_header(Header::new("Content-Range", format!("bytes */{}", size)));
+            }
+        }
+        Ok(response)
+    }
+}
+
+/// Checks if the request's If-Range header, if it has one, matches the ETag of the file.
+///
+/// Only entity tags are compared; an If-Range header with a date never matches, so the whole file is served.
+#[cfg(feature = "rocket")]
+fn if_range_matches(request: &Request, etag: &str) -> bool {
+    match request.headers().get_one("If-Range") {
+        Some(if_range) => if_range.trim() == etag,
+        None => true,
+    }
+}
+
+/// Bytes shared with the cache, which a `Cursor` can read from without copying them.
+#[cfg(feature = "rocket")]
+pub(crate) struct SharedBytes(pub(crate) Arc<Vec<u8>>);
+
+#[cfg(feature = "rocket")]
+impl AsRef<[u8]> for SharedBytes {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
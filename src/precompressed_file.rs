@@ -0,0 +1,224 @@
+use rocket::http::{Status, ContentType, Header};
+use rocket::response::{Response, Responder};
+use rocket::request::Request;
+use cache::Cache;
+use cached_file::CachedFile;
+use content_type::content_type_from_extension;
+
+use std::path::{Path, PathBuf};
+use std::cmp::Ordering;
+
+
+/// The encodings that sidecar files can be served with, paired with the extension of their sidecar files.
+///
+/// When the client values encodings equally, the encodings earlier in this list are preferred.
+const SIDECAR_ENCODINGS: [(&'static str, &'static str); 2] = [
+    ("zstd", "zst"),
+    ("gzip", "gz"),
+];
+
+/// Gets the path of the sidecar file of the file at the path, with the given extension.
+pub(crate) fn sidecar_path(path: &Path, extension: &str) -> PathBuf {
+    let mut sidecar_path = path.to_path_buf().into_os_string();
+    sidecar_path.push(".");
+    sidecar_path.push(extension);
+    PathBuf::from(sidecar_path)
+}
+
+/// Gets the paths of every sidecar file the file at the path could have.
+pub(crate) fn sidecar_paths(path: &Path) -> Vec<PathBuf> {
+    SIDECAR_ENCODINGS
+        .iter()
+        .map(|&(_, extension)| sidecar_path(path, extension))
+        .collect()
+}
+
+/// Gets the Content-Types that are compressed by default: text, and the structured text formats
+/// that aren't under `text/`. A subtype of `*` matches every subtype.
+pub(crate) fn default_compressible_types() -> Vec<ContentType> {
+    vec![
+        ContentType::new("text", "*"),
+        ContentType::JSON,
+        ContentType::JavaScript,
+        ContentType::XML,
+        ContentType::SVG,
+        ContentType::new("application", "wasm"),
+    ]
+}
+
+/// Checks if the Content-Type matches one of the compressible Content-Types, ignoring parameters.
+pub(crate) fn is_compressible(content_type: &ContentType, compressible_types: &[ContentType]) -> bool {
+    compressible_types.iter().any(|compressible| {
+        compressible.top() == content_type.top()
+            && (compressible.sub() == "*" || compressible.sub() == content_type.sub())
+    })
+}
+
+
+/// A file that will be served from a precompressed sidecar file if the client accepts its encoding.
+///
+/// Sidecar files have the same path as the original file, plus an extension indicating their encoding:
+/// * `.zst` files are served with `Content-Encoding: zstd`.
+/// * `.gz` files are served with `Content-Encoding: gzip`.
+///
+/// When responding, the encodings in the request's Accept-Encoding header are tried in order of
+/// the client's preference.
+/// The first encoding with an existing sidecar file is gotten from the cache and served
+/// with the Content-Type implied by the original file's extension, and an ETag that includes the encoding.
+/// Every response sets the `Vary: Accept-Encoding` header, so shared caches will keep the variants apart.
+/// If none of the acceptable encodings have a sidecar file, the original file is gotten from the cache and served.
+///
+/// Only files whose Content-Type is in the cache's `compressible_types` are served from sidecar files.
+/// Other files, such as images that are already compressed, are always served as they are.
+///
+/// Requests with a Range header are always served from the original file, so the range applies to its
+/// uncompressed bytes. A range of compressed bytes can't be decoded on its own, so it is never served.
+///
+/// Sidecar files are usually created ahead of time, such as by a build step. If the cache has a `zstd_level`,
+/// files in the cache that don't have a `.zst` sidecar file are compressed by the cache the first time a client
+/// that prefers zstd requests them, and the compressed bytes are kept in the cache as if they were a sidecar file.
+/// Sidecar files are served to any client that accepts their encoding, so they must be
+/// decodable without anything else; a `.zst` file compressed with a shared dictionary can't be decoded by
+/// clients that don't have the dictionary. The same applies to files the cache compresses with its
+/// `compression_dictionary`, so a dictionary should only be used when every client that accepts zstd has it.
+///
+/// # Example
+///
+/// ```
+/// #![feature(attr_literals)]
+/// #![feature(custom_attribute)]
+/// # extern crate rocket;
+/// # extern crate rocket_file_cache;
+///
+/// # fn main() {
+/// use rocket_file_cache::{Cache, PrecompressedFile};
+/// use std::path::{Path, PathBuf};
+/// use rocket::State;
+///
+/// #[get("/<file..>")]
+/// fn files<'a>(file: PathBuf, cache: State<'a, Cache>) -> PrecompressedFile<'a> {
+///     PrecompressedFile::open(Path::new("www/").join(file), cache.inner())
+/// }
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct PrecompressedFile<'a> {
+    path: PathBuf,
+    cache: &'a Cache,
+}
+
+impl<'a> PrecompressedFile<'a> {
+    /// Creates a PrecompressedFile that will get the file, or one of its sidecar files, from the cache when responding.
+    pub fn open<P: AsRef<Path>>(path: P, cache: &'a Cache) -> PrecompressedFile<'a> {
+        PrecompressedFile {
+            path: path.as_ref().to_path_buf(),
+            cache,
+        }
+    }
+
+    /// Gets the path of the sidecar file with the given extension.
+    fn sidecar_path(&self, extension: &str) -> PathBuf {
+        sidecar_path(&self.path, extension)
+    }
+
+    /// Gets the Content-Type implied by the extension of the file.
+    fn content_type(&self) -> Option<ContentType> {
+        self.path.extension()
+            .and_then(|ext| content_type_from_extension(&ext.to_string_lossy()))
+    }
+}
+
+/// Gets the sidecar encodings and extensions that the request's Accept-Encoding header allows,
+/// ordered from most to least preferred by the client.
+fn acceptable_encodings(request: &Request) -> Vec<(&'static str, &'static str)> {
+    let mut acceptable: Vec<(f32, (&'static str, &'static str))> = SIDECAR_ENCODINGS
+        .iter()
+        .filter_map(|&(encoding, extension)| {
+            quality_of(request, encoding).map(|quality| (quality, (encoding, extension)))
+        })
+        .filter(|&(quality, _)| quality > 0.0)
+        .collect();
+    // The sort is stable, so encodings of equal quality keep the server's order of preference.
+    acceptable.sort_by(|l, r| r.0.partial_cmp(&l.0).unwrap_or(Ordering::Equal));
+    acceptable.into_iter().map(|x| x.1).collect()
+}
+
+/// Gets the quality value the request's Accept-Encoding header gives to the encoding,
+/// or `None` if the header doesn't mention the encoding or a wildcard.
+fn quality_of(request: &Request, encoding: &str) -> Option<f32> {
+    let mut wildcard_quality: Option<f32> = None;
+    for value in request.headers().get("Accept-Encoding").flat_map(|value| value.split(',')) {
+        let mut parameters = value.split(';');
+        let name: &str = parameters.next().unwrap_or("").trim();
+        let quality: f32 = parameters
+            .filter_map(|parameter| {
+                let parameter: &str = parameter.trim();
+                if parameter.starts_with("q=") {
+                    parameter[2..].parse::<f32>().ok()
+                } else {
+                    None
+                }
+            })
+            .next()
+            .unwrap_or(1.0);
+
+        if name.eq_ignore_ascii_case(encoding) {
+            return Some(quality);
+        } else if name == "*" {
+            wildcard_quality = Some(quality);
+        }
+    }
+    wildcard_quality
+}
+
+impl<'a> Responder<'a> for PrecompressedFile<'a> {
+    fn respond_to(self, request: &Request) -> Result<Response<'a>, Status> {
+        let content_type: Option<ContentType> = self.content_type();
+        let compressible: bool = match content_type {
+            Some(ref content_type) => is_compressible(content_type, &self.cache.compressible_types),
+            None => false,
+        };
+        if !compressible {
+            return self.cache.get(&self.path).respond_to(request);
+        }
+
+        // A Range selects bytes of the identity representation, so sidecar files aren't considered.
+        let encodings = if request.headers().contains("Range") {
+            Vec::new()
+        } else {
+            acceptable_encodings(request)
+        };
+        for (encoding, extension) in encodings {
+            let compressed_file: Option<CachedFile<'a>> = if encoding == "zstd" {
+                self.cache.get_zstd_compressed(&self.path)
+            } else {
+                None
+            };
+            match compressed_file.unwrap_or_else(|| self.cache.get(self.sidecar_path(extension))) {
+                CachedFile::NotFound => {} // There is no sidecar for this encoding, so try the next one.
+                sidecar_file => {
+                    let sidecar_file: CachedFile<'a> = match sidecar_file {
+                        CachedFile::InMemory(mut named_in_memory_file) => {
+                            // Make sure the ETag of the encoded variant differs from that of the original file.
+                            named_in_memory_file.encoding = Some(encoding);
+                            CachedFile::InMemory(named_in_memory_file)
+                        }
+                        other => other,
+                    };
+                    let mut response: Response<'a> = sidecar_file.respond_to(request)?;
+                    response.remove_header("Content-Type");
+                    if let Some(ct) = content_type {
+                        response.set_header(ct);
+                    }
+                    response.set_header(Header::new("Content-Encoding", encoding));
+                    response.set_header(Header::new("Vary", "Accept-Encoding"));
+                    return Ok(response);
+                }
+            }
+        }
+
+        let mut response: Response<'a> = self.cache.get(&self.path).respond_to(request)?;
+        response.set_header(Header::new("Vary", "Accept-Encoding"));
+        Ok(response)
+    }
+}
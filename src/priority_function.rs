@@ -1,22 +1,42 @@
 use std::usize;
 
+/// Error returned when a priority function is looked up by a name that doesn't belong to a built in priority function.
+/// Holds the name that was looked up.
+#[derive(Debug, PartialEq, Clone)]
+pub struct UnknownPriorityFunction(pub String);
+
+/// Gets the built in priority function with the given name.
+///
+/// The names are those used in configuration files:
+/// * `"default"` or `"balanced"` - `default_priority_function`
+/// * `"normal"` - `normal_priority_function`
+/// * `"access"` - `access_priority_function`
+/// * `"small_files"` - `small_files_priority_function`
+/// * `"small_files_access"` - `small_files_access_priority_function`
+pub(crate) fn priority_function_by_name(name: &str) -> Result<fn(usize, usize) -> usize, UnknownPriorityFunction> {
+    match name {
+        "default" | "balanced" => Ok(default_priority_function),
+        "normal" => Ok(normal_priority_function),
+        "access" => Ok(access_priority_function),
+        "small_files" => Ok(small_files_priority_function),
+        "small_files_access" => Ok(small_files_access_priority_function),
+        _ => Err(UnknownPriorityFunction(name.to_string())),
+    }
+}
+
 /// The default priority function used for determining if a file should be in the cache.
 ///
 /// This function takes the square root of the size of the file times the number of times it has been accessed.
 /// This should give some priority to bigger files, while still allowing some smaller files to enter the cache.
+/// If the multiplication would overflow, the priority saturates at `usize::MAX`.
 pub fn default_priority_function(access_count: usize, size: usize) -> usize {
-    match usize::checked_mul(((size as f64).sqrt() as usize), access_count) {
-        Some(v) => v,
-        None => usize::MAX,
-    }
+    ((size as f64).sqrt() as usize).saturating_mul(access_count)
 }
 
 /// Priority is calculated as the size times the access count.
+/// If the multiplication would overflow, the priority saturates at `usize::MAX`.
 pub fn normal_priority_function(access_count: usize, size: usize) -> usize {
-    match usize::checked_mul(size, access_count) {
-        Some(v) => v,
-        None => usize::MAX,
-    }
+    size.saturating_mul(access_count)
 }
 
 /// This priority function will value files in the cache based solely on the number of times the file was accessed.
@@ -38,11 +58,6 @@ pub fn small_files_priority_function(_: usize, size: usize) -> usize {
 /// The smaller the file, the higher priority it will have.
 /// Does take into account the number of accesses the file has.
 pub fn small_files_access_priority_function(access_count: usize, size: usize) -> usize {
-    match usize::checked_mul(
-        usize::checked_div(usize::MAX, size).unwrap_or(0),
-        access_count,
-    ) {
-        Some(v) => v,
-        None => usize::MAX, // If the multiplication overflows, then the file will have the maximum priority.
-    }
+    // If the multiplication overflows, then the file will have the maximum priority.
+    usize::checked_div(usize::MAX, size).unwrap_or(0).saturating_mul(access_count)
 }
@@ -0,0 +1,88 @@
+/// The result of interpreting a request's Range header against a file of a known size.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub(crate) enum ByteRange {
+    /// The whole file should be served, because the header is missing, malformed, or unsupported.
+    Full,
+    /// Only the bytes from the start to the end, inclusive, should be served.
+    Partial(usize, usize),
+    /// The ranges don't overlap the file, or request too many bytes, so they can't be served.
+    Unsatisfiable,
+}
+
+/// Interprets the value of a Range header, such as `bytes=0-1023`, for a file of the given size.
+///
+/// Only a single range of bytes is served; headers with several ranges are treated as
+/// requesting the whole file, which servers are allowed to do.
+/// Ends that are past the end of the file are clamped to the last byte of the file.
+///
+/// Ranges can overlap, so a header with many ranges could request far more bytes than the file has.
+/// If the ranges request more than `max_total_bytes` in total, the header is treated as unsatisfiable,
+/// rather than doing any work to serve it.
+pub(crate) fn parse_range(header: &str, size: usize, max_total_bytes: usize) -> ByteRange {
+    let header: &str = header.trim();
+    if !header.get(..6).map_or(false, |unit| unit.eq_ignore_ascii_case("bytes=")) {
+        return ByteRange::Full;
+    }
+
+    let mut ranges: Vec<(usize, usize)> = vec![];
+    for spec in header[6..].split(',') {
+        match parse_range_spec(spec.trim(), size) {
+            Some(Some(range)) => ranges.push(range),
+            Some(None) => {} // This range doesn't overlap the file, but the others might.
+            None => return ByteRange::Full,
+        }
+    }
+
+    let total_bytes: usize = ranges.iter().fold(0usize, |total, &(start, end)| total.saturating_add(end - start + 1));
+    if total_bytes > max_total_bytes {
+        warn!("Rejected a Range header requesting {} bytes of a {} byte file.", total_bytes, size);
+        return ByteRange::Unsatisfiable;
+    }
+
+    match ranges.len() {
+        0 => ByteRange::Unsatisfiable,
+        1 => ByteRange::Partial(ranges[0].0, ranges[0].1),
+        _ => ByteRange::Full,
+    }
+}
+
+/// Interprets a single range, such as `0-1023`, `1024-` or `-512`, for a file of the given size.
+///
+/// Returns `None` if the range is malformed, or `Some(None)` if it doesn't overlap the file.
+fn parse_range_spec(spec: &str, size: usize) -> Option<Option<(usize, usize)>> {
+    let mut bounds = spec.splitn(2, '-');
+    let start: &str = bounds.next().unwrap_or("").trim();
+    let end: &str = match bounds.next() {
+        Some(end) => end.trim(),
+        None => return None,
+    };
+
+    if start.is_empty() {
+        // A suffix range requests the last bytes of the file.
+        let suffix_length: usize = match end.parse::<usize>() {
+            Ok(suffix_length) => suffix_length,
+            Err(_) => return None,
+        };
+        if suffix_length == 0 || size == 0 {
+            return Some(None);
+        }
+        return Some(Some((size.saturating_sub(suffix_length), size - 1)));
+    }
+
+    let start: usize = match start.parse::<usize>() {
+        Ok(start) => start,
+        Err(_) => return None,
+    };
+    let end: usize = if end.is_empty() {
+        usize::max_value()
+    } else {
+        match end.parse::<usize>() {
+            Ok(end) if end >= start => end,
+            _ => return None,
+        }
+    };
+    if start >= size {
+        return Some(None);
+    }
+    Some(Some((start, end.min(size - 1))))
+}
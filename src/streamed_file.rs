@@ -0,0 +1,96 @@
+#[cfg(feature = "rocket")]
+use rocket::response::{Response, Responder};
+#[cfg(feature = "rocket")]
+use rocket::http::{Status, ContentType, Header};
+#[cfg(feature = "rocket")]
+use rocket::request::Request;
+
+use std::fs::File;
+use std::io;
+use std::io::Read;
+use std::path::Path;
+#[cfg(feature = "rocket")]
+use std::result;
+
+use named_file::NamedFile;
+#[cfg(feature = "rocket")]
+use content_type::content_type_from_extension;
+
+
+/// A file that is streamed from the filesystem, because it isn't stored in the cache.
+///
+/// It holds the max-age the cache has for the file, so it is served with the same Cache-Control header
+/// as it would have been if it were served from memory.
+#[derive(Debug)]
+pub struct StreamedFile {
+    pub(crate) file: NamedFile,
+    pub(crate) max_age: Option<usize>,
+    #[cfg(feature = "rocket")]
+    pub(crate) status: Option<Status>,
+}
+
+impl StreamedFile {
+    /// Gets the path of the file.
+    pub fn path(&self) -> &Path {
+        self.file.path()
+    }
+
+    /// Gets a reference to the underlying file.
+    pub fn file(&self) -> &File {
+        self.file.file()
+    }
+
+    /// Takes the underlying file.
+    pub fn take_file(self) -> File {
+        self.file.take_file()
+    }
+
+    /// Gets the number of seconds that clients may cache the file, if the cache has a max-age for it.
+    pub fn max_age(&self) -> Option<usize> {
+        self.max_age
+    }
+}
+
+impl From<NamedFile> for StreamedFile {
+    fn from(named_file: NamedFile) -> StreamedFile {
+        StreamedFile {
+            file: named_file,
+            max_age: None,
+            #[cfg(feature = "rocket")]
+            status: None,
+        }
+    }
+}
+
+impl Read for StreamedFile {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.file.read(buf)
+    }
+}
+
+/// Streams the file to the client, setting the Content-Type according to the file's extension
+/// if the extension is recognized.
+/// If the cache has a max-age for the file, the Cache-Control header is set as well.
+/// If the file was given a status by `CachedFile::with_status()`, the response has that status instead of 200 OK.
+#[cfg(feature = "rocket")]
+impl<'a> Responder<'a> for StreamedFile {
+    fn respond_to(self, request: &Request) -> result::Result<Response<'a>, Status> {
+        let content_type: Option<ContentType> = self.file.path()
+            .extension()
+            .and_then(|ext| content_type_from_extension(&ext.to_string_lossy()));
+        let mut response: Response<'a> = self.file.respond_to(request)?;
+        // Rocket leaves the Content-Type of formats it doesn't recognize unset.
+        if response.content_type().is_none() {
+            if let Some(ct) = content_type {
+                response.set_header(ct);
+            }
+        }
+        if let Some(max_age) = self.max_age {
+            response.set_header(Header::new("Cache-Control", format!("max-age={}", max_age)));
+        }
+        if let Some(status) = self.status {
+            response.set_status(status);
+        }
+        Ok(response)
+    }
+}
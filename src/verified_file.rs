@@ -0,0 +1,86 @@
+#[cfg(feature = "rocket")]
+use rocket::response::{Response, Responder};
+#[cfg(feature = "rocket")]
+use rocket::http::{Status, Header};
+#[cfg(feature = "rocket")]
+use rocket::request::Request;
+
+use std::path::Path;
+#[cfg(feature = "rocket")]
+use std::result;
+
+use named_file::NamedFile;
+use checksum::ChecksumMismatch;
+#[cfg(feature = "rocket")]
+use checksum::ChecksumReader;
+#[cfg(feature = "rocket")]
+use content_type::content_type_from_extension;
+
+
+/// A file that is streamed from the filesystem while its checksum is verified.
+///
+/// The checksum is calculated incrementally as the file is streamed, so files that are too large
+/// to be held in memory can still be checked for corruption.
+/// Because the checksum can only be known once the whole file has been read, a mismatch is
+/// detected at the end of the stream.
+#[derive(Debug)]
+pub struct VerifiedFile {
+    pub(crate) file: NamedFile,
+    pub(crate) expected_checksum: u64,
+    pub(crate) on_mismatch: ChecksumMismatch,
+    pub(crate) max_age: Option<usize>,
+    #[cfg(feature = "rocket")]
+    pub(crate) status: Option<Status>,
+}
+
+impl VerifiedFile {
+    /// Gets the path of the file.
+    pub fn path(&self) -> &Path {
+        self.file.path()
+    }
+
+    /// Gets the checksum the file is expected to have.
+    pub fn expected_checksum(&self) -> u64 {
+        self.expected_checksum
+    }
+
+    /// Gets what will happen if the file doesn't match its expected checksum.
+    pub fn on_mismatch(&self) -> ChecksumMismatch {
+        self.on_mismatch
+    }
+
+    /// Gets the number of seconds that clients may cache the file, if the cache has a max-age for it.
+    pub fn max_age(&self) -> Option<usize> {
+        self.max_age
+    }
+}
+
+/// Streams the file to the client, setting the Content-Type according to the file's extension
+/// if the extension is recognized.
+/// If the cache has a max-age for the file, the Cache-Control header is set as well.
+/// If the file was given a status by `CachedFile::with_status()`, the response has that status instead of 200 OK.
+///
+/// If the file doesn't match its expected checksum and the mismatch should abort the response,
+/// reading the final chunk of the body will fail, so the client won't receive a complete response.
+#[cfg(feature = "rocket")]
+impl<'a> Responder<'a> for VerifiedFile {
+    fn respond_to(self, _: &Request) -> result::Result<Response<'a>, Status> {
+        let mut response = Response::new();
+        if let Some(ext) = self.file.path().extension() {
+            if let Some(ct) = content_type_from_extension(&ext.to_string_lossy()) {
+                response.set_header(ct);
+            }
+        }
+        if let Some(max_age) = self.max_age {
+            response.set_header(Header::new("Cache-Control", format!("max-age={}", max_age)));
+        }
+        if let Some(status) = self.status {
+            response.set_status(status);
+        }
+
+        let path = self.file.path().to_path_buf();
+        let reader = ChecksumReader::new(self.file.take_file(), path, self.expected_checksum, self.on_mismatch);
+        response.set_streamed_body(reader);
+        Ok(response)
+    }
+}